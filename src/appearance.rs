@@ -0,0 +1,329 @@
+use std::path::PathBuf;
+
+use gpui::{App, AppContext as _, Context, Global, InteractiveElement as _, IntoElement, ParentElement as _, Render, SharedString, StatefulInteractiveElement as _, Styled as _, Subscription, Window, WindowAppearance, actions, div, prelude::FluentBuilder as _, px};
+use gpui_component::{ActiveTheme as _, Icon, IconName, Selectable as _, Sizable as _, StyledExt as _, Theme, ThemeMode, ThemeRegistry, WindowExt as _, button::Button, h_flex, label::Label, scroll::ScrollableElement as _, v_flex};
+
+use crate::{
+    settings::{AppearanceMode, SettingsStore},
+    title_bar::{SwitchTheme, SwitchThemeMode},
+};
+
+actions!(themes, [FollowSystemMode, OpenThemePicker]);
+
+/// The themes of the app, the built-in ones plus the JSON theme files of the user.
+pub struct Appearance {
+    /// The last appearance reported by the operating system.
+    system_mode: ThemeMode,
+}
+
+impl Global for Appearance {}
+
+impl Appearance {
+    /// Restore the appearance from the settings and load the user themes, reloading them when a file changes.
+    pub fn init(cx: &mut App) {
+        cx.set_global(Self {
+            system_mode: cx.window_appearance().into(),
+        });
+        // Until the user themes are loaded the saved ones may be missing, the mode is right already.
+        Self::apply(cx);
+        let themes_dir = Self::themes_dir(cx);
+        _ = ThemeRegistry::watch_dir(themes_dir, cx, Self::apply);
+        // Only the first load calls back, a theme file changed later has to be applied again too.
+        cx.observe_global::<ThemeRegistry>(Self::apply).detach();
+        // Other settings change far more often, only a change of the appearance applies it again.
+        let mut applied = SettingsStore::get(cx).appearance.clone();
+        cx.observe_global::<SettingsStore>(move |cx| {
+            let appearance = &SettingsStore::get(cx).appearance;
+            if *appearance != applied {
+                applied = appearance.clone();
+                Self::apply(cx);
+            }
+        })
+        .detach();
+
+        cx.on_action(|switch: &SwitchTheme, cx| Self::set_theme(&switch.0, cx));
+        cx.on_action(|switch: &SwitchThemeMode, cx| {
+            let mode = if switch.0.is_dark() {
+                AppearanceMode::Dark
+            } else {
+                AppearanceMode::Light
+            };
+            Self::set_mode(mode, cx);
+        });
+        cx.on_action(|_: &FollowSystemMode, cx| Self::set_mode(AppearanceMode::System, cx));
+        cx.on_action(|_: &OpenThemePicker, cx| {
+            let Some(window) = cx.active_window() else {
+                return;
+            };
+            _ = window.update(cx, |_, window, cx| open_theme_picker(window, cx));
+        });
+    }
+
+    pub fn global(cx: &App) -> &Self {
+        cx.global::<Self>()
+    }
+
+    /// Where the user keeps theme files, each one a JSON theme set.
+    pub fn themes_dir(cx: &App) -> PathBuf {
+        SettingsStore::get(cx).storage.data_dir().join("themes")
+    }
+
+    /// Follow the appearance `window` has now and whenever the operating system changes it.
+    pub fn observe_system_appearance<V: 'static>(window: &mut Window, cx: &mut Context<V>) -> Subscription {
+        Self::set_system_appearance(window.appearance(), cx);
+        cx.observe_window_appearance(window, |_, window, cx| {
+            Self::set_system_appearance(window.appearance(), cx);
+        })
+    }
+
+    /// Record a change of the appearance of the operating system, tests call it to simulate one.
+    pub fn set_system_appearance(appearance: WindowAppearance, cx: &mut App) {
+        let mode = appearance.into();
+        if Self::global(cx).system_mode == mode {
+            return;
+        }
+        cx.global_mut::<Self>().system_mode = mode;
+        if SettingsStore::get(cx).appearance.mode == AppearanceMode::System {
+            Self::apply(cx);
+        }
+    }
+
+    /// The mode in use, the one of the operating system when following it.
+    pub fn mode(cx: &App) -> ThemeMode {
+        match SettingsStore::get(cx).appearance.mode {
+            AppearanceMode::Light => ThemeMode::Light,
+            AppearanceMode::Dark => ThemeMode::Dark,
+            AppearanceMode::System => Self::global(cx).system_mode,
+        }
+    }
+
+    /// Use the theme named `name` for its mode and switch to that mode, unless following the system.
+    pub fn set_theme(name: &SharedString, cx: &mut App) {
+        let Some(config) = ThemeRegistry::global(cx).themes().get(name).cloned() else {
+            return;
+        };
+        SettingsStore::update(cx, |settings| {
+            let appearance = &mut settings.appearance;
+            if appearance.mode != AppearanceMode::System {
+                appearance.mode = if config.mode.is_dark() {
+                    AppearanceMode::Dark
+                } else {
+                    AppearanceMode::Light
+                };
+            }
+            if config.mode.is_dark() {
+                appearance.dark_theme = Some(name.clone());
+            } else {
+                appearance.light_theme = Some(name.clone());
+            }
+        });
+    }
+
+    pub fn set_mode(mode: AppearanceMode, cx: &mut App) {
+        SettingsStore::update(cx, |settings| settings.appearance.mode = mode);
+    }
+
+    /// Switch to the saved mode with the saved themes, a theme no longer installed keeps the current one.
+    fn apply(cx: &mut App) {
+        let mode = Self::mode(cx);
+        let appearance = &SettingsStore::get(cx).appearance;
+        let themes = ThemeRegistry::global(cx).themes();
+        let light = appearance.light_theme.as_ref().and_then(|name| themes.get(name)).cloned();
+        let dark = appearance.dark_theme.as_ref().and_then(|name| themes.get(name)).cloned();
+
+        let theme = Theme::global_mut(cx);
+        if let Some(light) = light {
+            theme.light_theme = light;
+        }
+        if let Some(dark) = dark {
+            theme.dark_theme = dark;
+        }
+        Theme::change(mode, None, cx);
+        cx.refresh_windows();
+    }
+}
+
+pub fn open_theme_picker(window: &mut Window, cx: &mut App) {
+    let picker = cx.new(ThemePicker::new);
+    window.open_sheet(cx, move |sheet, _, _| {
+        sheet
+            .title("Themes")
+            .size(px(360.))
+            .child(picker.clone())
+    });
+}
+
+/// Lists the installed themes by mode, the settings page for the appearance.
+pub struct ThemePicker {
+    _subscriptions: Vec<Subscription>,
+}
+
+impl ThemePicker {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        let _subscriptions = vec![
+            cx.observe_global::<ThemeRegistry>(|_, cx| cx.notify()),
+            cx.observe_global::<Theme>(|_, cx| cx.notify()),
+        ];
+
+        Self { _subscriptions }
+    }
+
+    fn render_themes(&self, mode: ThemeMode, cx: &Context<Self>) -> impl IntoElement {
+        let current = match mode {
+            ThemeMode::Light => cx.theme().light_theme.name.clone(),
+            ThemeMode::Dark => cx.theme().dark_theme.name.clone(),
+        };
+        let themes = ThemeRegistry::global(cx)
+            .sorted_themes()
+            .into_iter()
+            .filter(|theme| theme.mode == mode)
+            .map(|theme| theme.name.clone())
+            .collect::<Vec<_>>();
+
+        v_flex()
+            .gap_1()
+            .child(
+                Label::new(match mode {
+                    ThemeMode::Light => "Light Themes",
+                    ThemeMode::Dark => "Dark Themes",
+                })
+                .text_sm()
+                .font_semibold(),
+            )
+            .children(themes.into_iter().enumerate().map(|(ix, name)| {
+                let selected = name == current;
+                h_flex()
+                    .id((SharedString::from(mode.name()), ix))
+                    .gap_2()
+                    .px_2()
+                    .py_1()
+                    .rounded(cx.theme().radius)
+                    .hover(|this| this.bg(cx.theme().list_hover))
+                    .when(selected, |this| this.bg(cx.theme().list_active))
+                    .child(Label::new(name.clone()).flex_1().text_sm())
+                    .when(selected, |this| this.child(Icon::new(IconName::Check).small()))
+                    .on_click(move |_, window, cx| {
+                        window.dispatch_action(Box::new(SwitchTheme(name.clone())), cx);
+                    })
+            }))
+    }
+}
+
+impl Render for ThemePicker {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let mode = SettingsStore::get(cx).appearance.mode;
+
+        v_flex()
+            .size_full()
+            .gap_4()
+            .child(
+                h_flex()
+                    .gap_2()
+                    .child(
+                        Button::new("system")
+                            .label("System")
+                            .small()
+                            .outline()
+                            .selected(mode == AppearanceMode::System)
+                            .on_click(|_, window, cx| {
+                                window.dispatch_action(Box::new(FollowSystemMode), cx);
+                            }),
+                    )
+                    .children([ThemeMode::Light, ThemeMode::Dark].map(|item| {
+                        Button::new(item.name())
+                            .label(match item {
+                                ThemeMode::Light => "Light",
+                                ThemeMode::Dark => "Dark",
+                            })
+                            .small()
+                            .outline()
+                            .selected(match item {
+                                ThemeMode::Light => mode == AppearanceMode::Light,
+                                ThemeMode::Dark => mode == AppearanceMode::Dark,
+                            })
+                            .on_click(move |_, window, cx| {
+                                window.dispatch_action(Box::new(SwitchThemeMode(item)), cx);
+                            })
+                    })),
+            )
+            .child(
+                v_flex()
+                    .flex_1()
+                    .gap_4()
+                    .overflow_y_scrollbar()
+                    .child(self.render_themes(ThemeMode::Light, cx))
+                    .child(self.render_themes(ThemeMode::Dark, cx)),
+            )
+            .child(
+                v_flex()
+                    .gap_2()
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(cx.theme().muted_foreground)
+                            .child("Theme files in the themes folder are loaded as soon as they change."),
+                    )
+                    .child(
+                        Button::new("open-themes-folder")
+                            .label("Open Themes Folder")
+                            .small()
+                            .outline()
+                            .on_click(|_, _, cx| {
+                                let dir = Appearance::themes_dir(cx);
+                                _ = std::fs::create_dir_all(&dir);
+                                cx.open_with_system(&dir);
+                            }),
+                    ),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gpui::TestAppContext;
+
+    use super::*;
+
+    fn init(cx: &mut TestAppContext) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        cx.update(|cx| {
+            gpui_component::init(cx);
+            SettingsStore::init_in(dir.path(), cx);
+            Appearance::init(cx);
+        });
+        dir
+    }
+
+    fn theme_mode(cx: &mut TestAppContext) -> ThemeMode {
+        cx.read(|cx| Theme::global(cx).mode)
+    }
+
+    #[gpui::test]
+    fn the_system_mode_is_followed(cx: &mut TestAppContext) {
+        let _dir = init(cx);
+        cx.update(|cx| Appearance::set_mode(AppearanceMode::System, cx));
+
+        cx.update(|cx| Appearance::set_system_appearance(WindowAppearance::Dark, cx));
+        assert_eq!(theme_mode(cx), ThemeMode::Dark);
+
+        cx.update(|cx| Appearance::set_system_appearance(WindowAppearance::Light, cx));
+        assert_eq!(theme_mode(cx), ThemeMode::Light);
+    }
+
+    #[gpui::test]
+    fn a_chosen_mode_ignores_the_system(cx: &mut TestAppContext) {
+        let _dir = init(cx);
+
+        cx.update(|cx| {
+            Appearance::set_mode(AppearanceMode::Light, cx);
+            Appearance::set_system_appearance(WindowAppearance::Dark, cx);
+        });
+        assert_eq!(theme_mode(cx), ThemeMode::Light);
+
+        cx.update(|cx| {
+            Appearance::set_mode(AppearanceMode::Dark, cx);
+            Appearance::set_system_appearance(WindowAppearance::Light, cx);
+        });
+        assert_eq!(theme_mode(cx), ThemeMode::Dark);
+    }
+}
@@ -0,0 +1,96 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use gpui::{ImageSource, Resource, SharedString};
+
+use crate::persist;
+
+/// The file in the cache directory listing the downloaded attachments.
+const INDEX_FILE: &str = "index.json";
+
+/// A file attached to a message, several messages may share one attachment.
+#[derive(Clone)]
+pub struct Attachment {
+    pub id: SharedString,
+    pub source: ImageSource,
+}
+
+impl Attachment {
+    /// The file of the attachment on this machine, `None` until it is downloaded.
+    pub fn local_path(&self, cache: &AttachmentCache) -> Option<PathBuf> {
+        if let Some(path) = cache.get(&self.id) {
+            return Some(path.to_path_buf());
+        }
+        match &self.source {
+            ImageSource::Resource(Resource::Path(path)) if path.exists() => Some(path.to_path_buf()),
+            _ => None,
+        }
+    }
+}
+
+/// Attachment files downloaded to the local cache directory.
+///
+/// The entries are saved next to the files, so files downloaded in an earlier run are
+/// still purged once no message references them.
+pub struct AttachmentCache {
+    root: PathBuf,
+    entries: HashMap<SharedString, PathBuf>,
+}
+
+impl AttachmentCache {
+    /// Load the entries saved under `root`.
+    pub fn load(root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        let entries = persist::load(&root.join(INDEX_FILE));
+        Self { root, entries }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn insert(&mut self, id: impl Into<SharedString>, path: impl Into<PathBuf>) {
+        self.entries.insert(id.into(), path.into());
+        self.save();
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Path> {
+        self.entries.get(id).map(|path| path.as_path())
+    }
+
+    /// Download an attachment into the cache, returns its file.
+    ///
+    /// Until there is a server only attachments of files on this machine can be downloaded,
+    /// the file is copied.
+    pub fn download(&mut self, attachment: &Attachment) -> Option<PathBuf> {
+        if let Some(path) = self.get(&attachment.id).filter(|path| path.exists()) {
+            return Some(path.to_path_buf());
+        }
+        let ImageSource::Resource(Resource::Path(source)) = &attachment.source else {
+            return None;
+        };
+
+        let path = self.root.join(attachment.id.replace(['/', '\\', ':'], "_"));
+        std::fs::create_dir_all(&self.root).ok()?;
+        std::fs::copy(source, &path).ok()?;
+        self.insert(attachment.id.clone(), path.clone());
+        Some(path)
+    }
+
+    /// Forget an entry and delete its file, files outside the cache directory are never touched.
+    pub fn purge(&mut self, id: &str) {
+        let Some(path) = self.entries.remove(id) else {
+            return;
+        };
+        self.save();
+        if path.starts_with(&self.root) {
+            _ = std::fs::remove_file(&path);
+        }
+    }
+
+    fn save(&self) {
+        persist::save(&self.root.join(INDEX_FILE), &self.entries);
+    }
+}
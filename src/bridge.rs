@@ -0,0 +1,374 @@
+use std::{collections::HashMap, rc::Rc};
+
+use anyhow::{Context as _, anyhow};
+use gpui::{App, Entity, Global, WeakEntity};
+use gpui_component::{webview::WebView, wry::http::Uri};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+
+use crate::{
+    chat::{ChatEvent, ChatStore, ConversationId, NewMessage},
+    settings::{SettingsStore, origin, parse_origin},
+};
+
+/// Run before every page, it gives the page `window.agpui`:
+///
+/// - `invoke(command, args)` calls a command and returns a promise of its result,
+/// - `listen(event, callback)` calls `callback` with the payload of every such event and
+///   returns a function to stop listening.
+pub(crate) const SCRIPT: &str = r#"
+(() => {
+    if (window.agpui || !window.ipc) return;
+    let next = 0;
+    const pending = new Map();
+    const listeners = new Map();
+    Object.defineProperty(window, "__agpuiReply", {
+        value(id, ok, value) {
+            const call = pending.get(id);
+            if (!call) return;
+            pending.delete(id);
+            ok ? call.resolve(value) : call.reject(new Error(value));
+        },
+    });
+    Object.defineProperty(window, "__agpuiEvent", {
+        value(event, payload) {
+            for (const callback of listeners.get(event) ?? []) callback(payload);
+        },
+    });
+    Object.defineProperty(window, "agpui", {
+        value: Object.freeze({
+            invoke(command, args = {}) {
+                return new Promise((resolve, reject) => {
+                    const id = ++next;
+                    pending.set(id, { resolve, reject });
+                    window.ipc.postMessage(JSON.stringify({ id, command, args }));
+                });
+            },
+            listen(event, callback) {
+                if (!listeners.has(event)) listeners.set(event, new Set());
+                listeners.get(event).add(callback);
+                return () => listeners.get(event).delete(callback);
+            },
+        }),
+    });
+})();
+"#;
+
+/// A command pages can invoke, it is deserialized from the arguments of the call.
+///
+/// Declare the arguments with `#[serde(deny_unknown_fields)]`, a call whose arguments do
+/// not match is rejected before the command runs.
+pub trait Command: DeserializeOwned + 'static {
+    const NAME: &'static str;
+    type Output: Serialize;
+
+    fn run(self, cx: &mut App) -> anyhow::Result<Self::Output>;
+}
+
+/// An event pages can listen to, its payload is the event serialized.
+pub trait Event: Serialize {
+    const NAME: &'static str;
+}
+
+/// A call of a page, as `window.agpui.invoke` posts it next to the id of the call.
+#[derive(Deserialize)]
+struct Request {
+    command: String,
+    #[serde(default)]
+    args: Option<Value>,
+}
+
+type Handler = Rc<dyn Fn(Value, &mut App) -> anyhow::Result<Value>>;
+
+/// The commands pages may call and the web views events are sent to.
+///
+/// Only pages of the origins in `network.bridge_origins` are answered or told about events.
+pub struct Bridge {
+    commands: HashMap<&'static str, Handler>,
+    webviews: Vec<WeakEntity<WebView>>,
+}
+
+impl Global for Bridge {}
+
+impl Bridge {
+    pub fn init(cx: &mut App) {
+        cx.set_global(Self {
+            commands: HashMap::new(),
+            webviews: vec![],
+        });
+        Self::register::<OpenConversation>(cx);
+        Self::register::<SendMessage>(cx);
+        Self::register::<ListConversations>(cx);
+
+        cx.subscribe(&ChatStore::global(cx), |store, event, cx| {
+            if let ChatEvent::Received { conversation, message } = event {
+                let store = store.read(cx);
+                let Some(message) = store.message(*conversation, *message) else {
+                    return;
+                };
+                let event = MessageReceived {
+                    conversation: conversation.0,
+                    message: message.id.0,
+                    sender: store.name(message.sender).to_string(),
+                    text: message.text.to_string(),
+                };
+                Self::emit(&event, cx);
+            }
+        })
+        .detach();
+    }
+
+    pub fn global(cx: &App) -> &Self {
+        cx.global::<Self>()
+    }
+
+    /// Let pages call `C` by its name, a command registered before under that name is replaced.
+    pub fn register<C: Command>(cx: &mut App) {
+        cx.global_mut::<Self>().commands.insert(C::NAME, handler::<C>());
+    }
+
+    /// Route the calls of the pages in `webview` here and send it the events.
+    pub fn attach(webview: &Entity<WebView>, cx: &mut App) {
+        cx.global_mut::<Self>().webviews.push(webview.downgrade());
+    }
+
+    /// Send an event to the pages that may hear it.
+    pub fn emit<E: Event>(event: &E, cx: &mut App) {
+        let Ok(payload) = serde_json::to_string(event) else {
+            return;
+        };
+        let script = format!("window.__agpuiEvent({}, {});", json!(E::NAME), payload);
+        cx.global_mut::<Self>().webviews.retain(|webview| webview.upgrade().is_some());
+        for webview in &Self::global(cx).webviews {
+            let Some(webview) = webview.upgrade() else {
+                continue;
+            };
+            let webview = webview.read(cx).raw();
+            if webview.url().is_ok_and(|url| is_allowed(&url, cx)) {
+                _ = webview.evaluate_script(&script);
+            }
+        }
+    }
+
+    /// Answer a message `window.ipc` got from the page at `url` in `webview`.
+    ///
+    /// A message without a call id cannot be answered and is dropped.
+    pub(crate) fn handle(webview: &Entity<WebView>, url: &str, body: &str, cx: &mut App) {
+        let Ok(message) = serde_json::from_str::<Value>(body) else {
+            return;
+        };
+        let Some(id) = message.get("id").and_then(Value::as_u64) else {
+            return;
+        };
+        let result = Self::call(webview, url, message, cx);
+        let script = match result {
+            Ok(value) => format!("window.__agpuiReply({}, true, {});", id, value),
+            Err(err) => format!("window.__agpuiReply({}, false, {});", id, json!(err.to_string())),
+        };
+        _ = webview.read(cx).raw().evaluate_script(&script);
+    }
+
+    fn call(webview: &Entity<WebView>, url: &str, message: Value, cx: &mut App) -> anyhow::Result<Value> {
+        let top = webview.read(cx).raw().url().unwrap_or_default();
+        check_caller(url, &top, cx)?;
+
+        let request = serde_json::from_value::<Request>(message).map_err(|err| anyhow!("invalid call: {}", err))?;
+        let handler = Self::global(cx)
+            .commands
+            .get(request.command.as_str())
+            .cloned()
+            .ok_or_else(|| anyhow!("unknown command {}", request.command))?;
+        handler(request.args.unwrap_or_else(|| json!({})), cx)
+    }
+}
+
+fn handler<C: Command>() -> Handler {
+    Rc::new(|args, cx| {
+        let command =
+            serde_json::from_value::<C>(args).map_err(|err| anyhow!("invalid arguments for {}: {}", C::NAME, err))?;
+        Ok(serde_json::to_value(command.run(cx)?)?)
+    })
+}
+
+/// Fail unless both the page at `url` and the top page `top` it is shown in may call the app.
+///
+/// The answer goes to the top page, so a frame of an allowed origin in another page
+/// does not make the call allowed.
+fn check_caller(url: &str, top: &str, cx: &App) -> anyhow::Result<()> {
+    match [url, top].into_iter().find(|url| !is_allowed(url, cx)) {
+        Some(url) => Err(anyhow!("{} may not call the app", url)),
+        None => Ok(()),
+    }
+}
+
+/// Whether the page at `url` may call the app, the fragment of the url plays no part.
+fn is_allowed(url: &str, cx: &App) -> bool {
+    let url = url.split('#').next().unwrap_or_default();
+    let Some(origin) = url.parse::<Uri>().ok().and_then(|url| origin(&url)) else {
+        return false;
+    };
+    SettingsStore::get(cx)
+        .network
+        .bridge_origins
+        .iter()
+        .any(|allowed| parse_origin(allowed).is_some_and(|allowed| allowed == origin))
+}
+
+/// A message of someone else arrived.
+#[derive(Serialize)]
+struct MessageReceived {
+    conversation: i64,
+    message: u64,
+    sender: String,
+    text: String,
+}
+
+impl Event for MessageReceived {
+    const NAME: &'static str = "message_received";
+}
+
+/// Show a conversation in the main window.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OpenConversation {
+    conversation: i64,
+}
+
+impl Command for OpenConversation {
+    const NAME: &'static str = "open_conversation";
+    type Output = ();
+
+    fn run(self, cx: &mut App) -> anyhow::Result<()> {
+        let conversation = known_conversation(self.conversation, cx)?;
+        ChatStore::global(cx).update(cx, |store, cx| store.reveal(conversation, None, cx));
+        Ok(())
+    }
+}
+
+/// Send a message as the user, the result is the id of the message.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SendMessage {
+    conversation: i64,
+    text: String,
+}
+
+impl Command for SendMessage {
+    const NAME: &'static str = "send_message";
+    type Output = u64;
+
+    fn run(self, cx: &mut App) -> anyhow::Result<u64> {
+        let conversation = known_conversation(self.conversation, cx)?;
+        let message = NewMessage {
+            text: self.text.into(),
+            ..Default::default()
+        };
+        let id = ChatStore::global(cx).update(cx, |store, cx| store.send_message(conversation, message, cx));
+        id.map(|id| id.0).context("the message was not sent")
+    }
+}
+
+/// The conversations with their titles.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ListConversations {}
+
+#[derive(Serialize)]
+struct ConversationInfo {
+    id: i64,
+    title: String,
+    unread: usize,
+}
+
+impl Command for ListConversations {
+    const NAME: &'static str = "list_conversations";
+    type Output = Vec<ConversationInfo>;
+
+    fn run(self, cx: &mut App) -> anyhow::Result<Vec<ConversationInfo>> {
+        let store = ChatStore::global(cx).read(cx);
+        Ok(store
+            .conversations()
+            .into_iter()
+            .map(|conversation| ConversationInfo {
+                id: conversation.0,
+                title: store.title(conversation).to_string(),
+                unread: store.unread(conversation).messages,
+            })
+            .collect())
+    }
+}
+
+fn known_conversation(id: i64, cx: &App) -> anyhow::Result<ConversationId> {
+    let conversation = ConversationId(id);
+    ChatStore::global(cx)
+        .read(cx)
+        .conversations()
+        .contains(&conversation)
+        .then_some(conversation)
+        .ok_or_else(|| anyhow!("unknown conversation {}", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use gpui::TestAppContext;
+
+    use super::*;
+
+    fn allow(origins: &[&str], cx: &mut TestAppContext) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        cx.update(|cx| {
+            SettingsStore::init_in(dir.path(), cx);
+            let origins = origins.iter().map(|origin| origin.to_string()).collect();
+            assert!(SettingsStore::update(cx, |settings| settings.network.bridge_origins = origins));
+        });
+        dir
+    }
+
+    #[test]
+    fn origins_drop_default_ports_and_case() {
+        assert_eq!(parse_origin("HTTPS://Example.COM:443/").as_deref(), Some("https://example.com"));
+        assert_eq!(parse_origin("http://example.com:80").as_deref(), Some("http://example.com"));
+        assert_eq!(parse_origin(" http://localhost:8080 ").as_deref(), Some("http://localhost:8080"));
+        assert_eq!(parse_origin("https://example.com/app"), None);
+        assert_eq!(parse_origin("https://example.com/?page=1"), None);
+        assert_eq!(parse_origin("file:///index.html"), None);
+        assert_eq!(parse_origin("example.com"), None);
+    }
+
+    #[gpui::test]
+    fn only_pages_of_allowed_origins_may_call(cx: &mut TestAppContext) {
+        let _dir = allow(&["https://example.com"], cx);
+        cx.update(|cx| {
+            assert!(is_allowed("https://example.com/app", cx));
+            assert!(is_allowed("https://EXAMPLE.com:443/app?page=1#top", cx));
+            assert!(!is_allowed("https://example.com:8443/app", cx));
+            assert!(!is_allowed("http://example.com/app", cx));
+            assert!(!is_allowed("https://example.com.evil.test/", cx));
+            assert!(!is_allowed("about:blank", cx));
+        });
+    }
+
+    #[gpui::test]
+    fn frames_may_only_call_from_allowed_pages(cx: &mut TestAppContext) {
+        let _dir = allow(&["https://example.com"], cx);
+        cx.update(|cx| {
+            assert!(check_caller("https://example.com/frame", "https://example.com/", cx).is_ok());
+            assert!(check_caller("https://example.com/frame", "https://evil.test/", cx).is_err());
+            assert!(check_caller("https://evil.test/frame", "https://example.com/", cx).is_err());
+        });
+    }
+
+    #[gpui::test]
+    fn unknown_arguments_are_rejected(cx: &mut TestAppContext) {
+        cx.update(|cx| {
+            let open = handler::<OpenConversation>();
+            let err = open(json!({ "conversation": 1, "message": 2 }), cx).unwrap_err();
+            assert!(err.to_string().contains("unknown field `message`"), "{}", err);
+            assert!(open(json!({}), cx).is_err());
+            assert!(open(json!({ "conversation": "1" }), cx).is_err());
+
+            let list = handler::<ListConversations>();
+            assert!(list(json!({ "all": true }), cx).is_err());
+        });
+    }
+}
@@ -0,0 +1,1742 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use chrono::{DateTime, Duration, Local, NaiveDateTime};
+use gpui::{App, AppContext as _, Context, Entity, EventEmitter, Global, SharedString};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    attachments::{Attachment, AttachmentCache},
+    drafts::Drafts,
+    flags::{ConversationFlags, SavedFlags},
+    mentions::find_mentions,
+    search::SearchIndex,
+    settings::{SettingsStore, StorageSettings},
+    transport::{LoopbackTransport, Op, Transport},
+};
+
+pub type UserId = i64;
+
+/// The user of this client.
+pub const LOCAL_USER: UserId = 0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ConversationId(pub i64);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct MessageId(pub u64);
+
+/// A previous version of a message text.
+#[derive(Clone, Debug)]
+pub struct Revision {
+    pub text: SharedString,
+    pub at: DateTime<Local>,
+}
+
+/// Everyone who reacted to a message with the same emoji.
+#[derive(Clone, Debug)]
+pub struct Reaction {
+    pub emoji: SharedString,
+    pub users: Vec<UserId>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MessageKind {
+    #[default]
+    Normal,
+    /// Recalled by its sender, only a tombstone is left.
+    Recalled,
+    /// A membership change in a group, written by the store.
+    System,
+}
+
+/// A conversation with more than one other member.
+#[derive(Clone, Debug)]
+pub struct Group {
+    pub title: SharedString,
+    pub admins: Vec<UserId>,
+}
+
+/// Messages not read yet in a conversation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Unread {
+    pub messages: usize,
+    /// The unread messages mentioning us.
+    pub mentions: usize,
+}
+
+#[derive(Clone)]
+pub struct Message {
+    pub id: MessageId,
+    pub conversation: ConversationId,
+    pub sender: UserId,
+    pub kind: MessageKind,
+    pub text: SharedString,
+    pub attachment: Option<Attachment>,
+    /// The message quoted above this one.
+    pub reply_to: Option<MessageId>,
+    /// The root message when this is a thread reply, thread replies are not listed in the conversation.
+    pub thread: Option<MessageId>,
+    pub sent_at: DateTime<Local>,
+    pub edited_at: Option<DateTime<Local>>,
+    /// Earlier versions of `text`, oldest first.
+    pub revisions: Vec<Revision>,
+    /// In the order the emojis were first used.
+    pub reactions: Vec<Reaction>,
+    /// Members mentioned with `@name` in the text.
+    pub mentions: Vec<UserId>,
+}
+
+impl Message {
+    fn fake(
+        id: MessageId,
+        conversation: ConversationId,
+        others: &[UserId],
+        i: usize,
+        count: usize,
+    ) -> Self {
+        let sender = if i % 2 == 0 || others.is_empty() {
+            LOCAL_USER
+        } else {
+            others[i / 2 % others.len()]
+        };
+        Self {
+            id,
+            conversation,
+            sender,
+            kind: MessageKind::Normal,
+            text: format!("Item {}", i).into(),
+            attachment: Some(Attachment {
+                id: format!("images/{}.png", i % 11).into(),
+                source: Path::new(&format!("G:/research/rustee/agpui/images/{}.png", i % 11)).into(),
+            }),
+            reply_to: (i % 10 == 9).then(|| MessageId(id.0 - 5)),
+            thread: None,
+            sent_at: Local::now() - Duration::minutes((count - i) as i64),
+            edited_at: None,
+            revisions: vec![],
+            reactions: if i % 17 == 0 {
+                vec![Reaction {
+                    emoji: "👍".into(),
+                    users: vec![sender],
+                }]
+            } else {
+                vec![]
+            },
+            mentions: vec![],
+        }
+    }
+
+    pub fn is_edited(&self) -> bool {
+        !self.revisions.is_empty()
+    }
+
+    pub fn is_recalled(&self) -> bool {
+        self.kind == MessageKind::Recalled
+    }
+
+    pub fn is_system(&self) -> bool {
+        self.kind == MessageKind::System
+    }
+
+    pub fn has_reacted(&self, user: UserId, emoji: &str) -> bool {
+        self.reactions
+            .iter()
+            .any(|reaction| reaction.emoji == emoji && reaction.users.contains(&user))
+    }
+
+    /// The first line of the text, used when quoting the message.
+    pub fn snippet(&self) -> SharedString {
+        if self.is_recalled() {
+            return "Message recalled".into();
+        }
+        let line = self.text.lines().next().unwrap_or_default();
+        if line.chars().count() > 80 {
+            format!("{}…", line.chars().take(80).collect::<String>()).into()
+        } else {
+            line.to_string().into()
+        }
+    }
+}
+
+/// A message about to be sent.
+#[derive(Clone, Debug, Default)]
+pub struct NewMessage {
+    pub text: SharedString,
+    pub reply_to: Option<MessageId>,
+    pub thread: Option<MessageId>,
+    /// Filled in by the store from the `@name` tokens in `text`.
+    pub mentions: Vec<UserId>,
+}
+
+pub enum ChatEvent {
+    /// Show a conversation, scrolled to `message` when there is one.
+    Reveal {
+        conversation: ConversationId,
+        message: Option<MessageId>,
+    },
+    /// Someone else sent a message, our own messages are not reported.
+    Received {
+        conversation: ConversationId,
+        message: MessageId,
+    },
+}
+
+struct GlobalChatStore(Entity<ChatStore>);
+
+impl Global for GlobalChatStore {}
+
+/// The time, sender and text of a line of a chat archive that starts a message.
+fn parse_archive_line(line: &str) -> Option<(DateTime<Local>, &str, &str)> {
+    let at = NaiveDateTime::parse_from_str(line.get(..16)?, "%Y-%m-%d %H:%M").ok()?;
+    let (sender, text) = line.get(16..)?.strip_prefix(' ')?.split_once(": ")?;
+    let at = at.and_local_timezone(Local).earliest()?;
+    Some((at, sender.trim(), text)).filter(|(_, sender, _)| !sender.is_empty())
+}
+
+/// All conversations known to this client.
+///
+/// Views observe the store and re-render when it is notified.
+pub struct ChatStore {
+    me: UserId,
+    next_message_id: u64,
+    conversations: HashMap<ConversationId, Vec<Message>>,
+    /// Display names of the users we know about.
+    names: HashMap<UserId, SharedString>,
+    /// Everyone in a conversation including us.
+    members: HashMap<ConversationId, Vec<UserId>>,
+    groups: HashMap<ConversationId, Group>,
+    unread: HashMap<ConversationId, Unread>,
+    /// The number of thread replies of each root message, by conversation.
+    reply_counts: HashMap<ConversationId, HashMap<MessageId, usize>>,
+    /// Edits that arrived before the revisions they follow, applied once those are in.
+    pending_edits: HashMap<(ConversationId, MessageId), Vec<Op>>,
+    index: SearchIndex,
+    attachments: AttachmentCache,
+    drafts: Drafts,
+    flags: SavedFlags,
+    /// How long after sending a message can still be recalled.
+    recall_window: Duration,
+    transport: Rc<dyn Transport>,
+}
+
+impl EventEmitter<ChatEvent> for ChatStore {}
+
+impl ChatStore {
+    pub fn init(cx: &mut App) {
+        let settings = SettingsStore::get(cx);
+        let mut store = Self::new(LOCAL_USER, Rc::new(LoopbackTransport::default()), &settings.storage);
+        store.set_recall_window(Duration::minutes(settings.chat.recall_minutes.into()));
+        let store = cx.new(|_| store);
+        cx.observe_global::<SettingsStore>({
+            let store = store.clone();
+            move |cx| {
+                let recall_window = Duration::minutes(SettingsStore::get(cx).chat.recall_minutes.into());
+                store.update(cx, |store, _| store.set_recall_window(recall_window));
+            }
+        })
+        .detach();
+        cx.set_global(GlobalChatStore(store));
+    }
+
+    pub fn global(cx: &App) -> Entity<Self> {
+        cx.global::<GlobalChatStore>().0.clone()
+    }
+
+    /// The files of the store are kept in the folders of `storage`.
+    pub fn new(me: UserId, transport: Rc<dyn Transport>, storage: &StorageSettings) -> Self {
+        Self {
+            me,
+            next_message_id: 1,
+            conversations: HashMap::new(),
+            names: HashMap::new(),
+            members: HashMap::new(),
+            groups: HashMap::new(),
+            unread: HashMap::new(),
+            reply_counts: HashMap::new(),
+            pending_edits: HashMap::new(),
+            index: SearchIndex::default(),
+            attachments: AttachmentCache::load(storage.cache_dir().join("attachments")),
+            drafts: Drafts::load(storage.data_dir().join("drafts")),
+            flags: SavedFlags::load(storage.data_dir().join("conversations.json")),
+            recall_window: Duration::minutes(2),
+            transport,
+        }
+    }
+
+    pub fn me(&self) -> UserId {
+        self.me
+    }
+
+    pub fn add_user(&mut self, id: UserId, name: impl Into<SharedString>) {
+        self.names.insert(id, name.into());
+    }
+
+    pub fn name(&self, id: UserId) -> SharedString {
+        self.names.get(&id).cloned().unwrap_or_else(|| "Unknown".into())
+    }
+
+    /// Everyone we know about except us, by name.
+    pub fn users(&self) -> Vec<UserId> {
+        let mut users = self
+            .names
+            .keys()
+            .copied()
+            .filter(|id| *id != self.me)
+            .collect::<Vec<_>>();
+        users.sort_by_key(|id| self.name(*id));
+        users
+    }
+
+    /// The name used in system messages, "You" for us.
+    fn display_name(&self, id: UserId) -> SharedString {
+        if id == self.me {
+            "You".into()
+        } else {
+            self.name(id)
+        }
+    }
+
+    /// The group title, or the name of the other user of a direct conversation.
+    pub fn title(&self, conversation: ConversationId) -> SharedString {
+        match self.groups.get(&conversation) {
+            Some(group) => group.title.clone(),
+            None => self.name(conversation.0),
+        }
+    }
+
+    /// The conversations we can send to, direct ones with every user and the groups we are in, by title.
+    pub fn conversations(&self) -> Vec<ConversationId> {
+        let mut conversations = self
+            .users()
+            .into_iter()
+            .map(ConversationId)
+            .chain(
+                self.groups
+                    .keys()
+                    .copied()
+                    .filter(|conversation| self.is_member(*conversation, self.me)),
+            )
+            .collect::<Vec<_>>();
+        conversations.sort_by_key(|conversation| self.title(*conversation));
+        conversations
+    }
+
+    /// The conversations whose title or the name of a member contains `query`, ignoring case, by title.
+    ///
+    /// The contact list and the forward sheet both find conversations this way.
+    pub fn find_conversations(&self, query: &str) -> Vec<ConversationId> {
+        let query = query.trim().to_lowercase();
+        let matches = |name: SharedString| name.to_lowercase().contains(&query);
+        self.conversations()
+            .into_iter()
+            .filter(|conversation| {
+                matches(self.title(*conversation))
+                    || self.members(*conversation).iter().any(|member| matches(self.name(*member)))
+            })
+            .collect()
+    }
+
+    /// Add a group we know about from elsewhere, like the users this sends nothing.
+    pub fn add_group(&mut self, conversation: ConversationId, group: Group, members: Vec<UserId>) {
+        self.groups.insert(conversation, group);
+        self.members.insert(conversation, members);
+    }
+
+    pub fn set_members(&mut self, conversation: ConversationId, members: Vec<UserId>) {
+        self.members.insert(conversation, members);
+    }
+
+    pub fn members(&self, conversation: ConversationId) -> &[UserId] {
+        self.members
+            .get(&conversation)
+            .map(|members| members.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn is_group(&self, conversation: ConversationId) -> bool {
+        self.groups.contains_key(&conversation)
+    }
+
+    pub fn group(&self, conversation: ConversationId) -> Option<&Group> {
+        self.groups.get(&conversation)
+    }
+
+    pub fn is_member(&self, conversation: ConversationId, user: UserId) -> bool {
+        self.members(conversation).contains(&user)
+    }
+
+    pub fn is_admin(&self, conversation: ConversationId, user: UserId) -> bool {
+        self.groups
+            .get(&conversation)
+            .is_some_and(|group| group.admins.contains(&user))
+    }
+
+    fn next_message_id(&mut self) -> MessageId {
+        let id = MessageId(self.next_message_id);
+        self.next_message_id += 1;
+        id
+    }
+
+    /// A new group id.
+    ///
+    /// Groups get negative ids so they never collide with the direct conversations, which are
+    /// identified by the other user. The ids are random rather than counted, drafts, flags and
+    /// windows are saved by id and must not pass to a group created in a later run or by
+    /// another client.
+    fn new_group_id(&self) -> ConversationId {
+        loop {
+            let (bits, _) = uuid::Uuid::new_v4().as_u64_pair();
+            let conversation = ConversationId(-((bits >> 1) as i64) - 1);
+            if !self.groups.contains_key(&conversation) && !self.conversations.contains_key(&conversation) {
+                return conversation;
+            }
+        }
+    }
+
+    /// Create a group with us as its only admin and open it.
+    pub fn create_group(
+        &mut self,
+        title: impl Into<SharedString>,
+        users: Vec<UserId>,
+        cx: &mut Context<Self>,
+    ) -> ConversationId {
+        let conversation = self.new_group_id();
+
+        let mut members = vec![self.me];
+        for user in users {
+            if !members.contains(&user) {
+                members.push(user);
+            }
+        }
+        let op = Op::CreateGroup {
+            conversation,
+            message: self.next_message_id(),
+            by: self.me,
+            title: title.into(),
+            members,
+            at: Local::now(),
+        };
+        self.apply(&op, cx);
+        self.transport.send(op);
+        conversation
+    }
+
+    pub fn add_members(
+        &mut self,
+        conversation: ConversationId,
+        users: Vec<UserId>,
+        cx: &mut Context<Self>,
+    ) {
+        let users = users
+            .into_iter()
+            .filter(|user| !self.is_member(conversation, *user))
+            .collect::<Vec<_>>();
+        if users.is_empty() || !self.is_admin(conversation, self.me) {
+            return;
+        }
+
+        let op = Op::AddMembers {
+            conversation,
+            message: self.next_message_id(),
+            by: self.me,
+            users,
+            at: Local::now(),
+        };
+        self.apply(&op, cx);
+        self.transport.send(op);
+    }
+
+    /// Remove someone from a group, only admins can remove others.
+    pub fn remove_member(&mut self, conversation: ConversationId, user: UserId, cx: &mut Context<Self>) {
+        if !self.is_member(conversation, user) || (user != self.me && !self.is_admin(conversation, self.me)) {
+            return;
+        }
+
+        let op = Op::RemoveMember {
+            conversation,
+            message: self.next_message_id(),
+            by: self.me,
+            user,
+            at: Local::now(),
+        };
+        self.apply(&op, cx);
+        self.transport.send(op);
+    }
+
+    pub fn leave_group(&mut self, conversation: ConversationId, cx: &mut Context<Self>) {
+        self.remove_member(conversation, self.me, cx);
+    }
+
+    pub fn set_admin(
+        &mut self,
+        conversation: ConversationId,
+        user: UserId,
+        admin: bool,
+        cx: &mut Context<Self>,
+    ) {
+        if !self.is_member(conversation, user)
+            || !self.is_admin(conversation, self.me)
+            || self.is_admin(conversation, user) == admin
+        {
+            return;
+        }
+
+        let op = Op::SetAdmin {
+            conversation,
+            message: self.next_message_id(),
+            by: self.me,
+            user,
+            admin,
+            at: Local::now(),
+        };
+        self.apply(&op, cx);
+        self.transport.send(op);
+    }
+
+    /// The members of a conversation with their names, for resolving `@name` tokens.
+    pub fn member_names(&self, conversation: ConversationId) -> Vec<(UserId, SharedString)> {
+        self.members(conversation)
+            .iter()
+            .map(|id| (*id, self.name(*id)))
+            .collect()
+    }
+
+    fn mentions_in(&self, conversation: ConversationId, text: &str) -> Vec<UserId> {
+        if !self.is_group(conversation) {
+            return vec![];
+        }
+        let mut mentions = find_mentions(text, &self.member_names(conversation))
+            .into_iter()
+            .map(|(_, user)| user)
+            .collect::<Vec<_>>();
+        mentions.sort();
+        mentions.dedup();
+        mentions
+    }
+
+    /// Import a chat log as a new group titled `title`, returns the group.
+    ///
+    /// A line starting with a time and a sender, like `2024-05-01 09:30 Alice: Hello`, starts a
+    /// message and the lines after it continue the message. Senders are matched to the users by
+    /// name, `Me` is us, unknown ones are added. The messages are history on this client only,
+    /// sending them would pass them off as new messages of their senders.
+    pub fn import_archive(
+        &mut self,
+        title: impl Into<SharedString>,
+        text: &str,
+        cx: &mut Context<Self>,
+    ) -> anyhow::Result<ConversationId> {
+        let mut entries: Vec<(DateTime<Local>, &str, String)> = vec![];
+        for line in text.lines() {
+            if let Some((at, sender, text)) = parse_archive_line(line) {
+                entries.push((at, sender, text.to_string()));
+            } else if let Some((_, _, text)) = entries.last_mut() {
+                text.push('\n');
+                text.push_str(line);
+            } else if !line.trim().is_empty() {
+                anyhow::bail!("not a chat archive, the first line has no time and sender");
+            }
+        }
+        anyhow::ensure!(!entries.is_empty(), "the archive has no messages");
+
+        entries.sort_by_key(|(at, _, _)| *at);
+
+        let mut senders = vec![];
+        for (_, name, _) in &entries {
+            let user = self.user_named(name);
+            if !senders.contains(&user) {
+                senders.push(user);
+            }
+        }
+        let conversation = self.create_group(title, senders, cx);
+
+        // History is neither unread nor announced.
+        for (at, name, text) in entries {
+            let text: SharedString = text.trim_end().to_string().into();
+            let op = Op::Send {
+                conversation,
+                message: self.next_message_id(),
+                sender: self.user_named(name),
+                at,
+                content: NewMessage {
+                    mentions: self.mentions_in(conversation, &text),
+                    text,
+                    ..Default::default()
+                },
+            };
+            self.apply(&op, cx);
+        }
+        self.unread.remove(&conversation);
+        cx.notify();
+        Ok(conversation)
+    }
+
+    /// The user with the name, added when there is none.
+    fn user_named(&mut self, name: &str) -> UserId {
+        if name == "Me" {
+            return self.me;
+        }
+        if let Some(user) = self.names.iter().find(|(_, known)| known.as_ref() == name).map(|(user, _)| *user) {
+            return user;
+        }
+        let user = self.names.keys().max().copied().unwrap_or(self.me).max(self.me) + 1;
+        self.add_user(user, name.to_string());
+        user
+    }
+
+    pub fn search_index(&self) -> &SearchIndex {
+        &self.index
+    }
+
+    pub fn all_messages(&self) -> impl Iterator<Item = &Message> {
+        self.conversations.values().flatten()
+    }
+
+    /// Ask the views to show a conversation, e.g. after picking a search result.
+    pub fn reveal(&mut self, conversation: ConversationId, message: Option<MessageId>, cx: &mut Context<Self>) {
+        cx.emit(ChatEvent::Reveal {
+            conversation,
+            message,
+        });
+    }
+
+    pub fn unread(&self, conversation: ConversationId) -> Unread {
+        self.unread.get(&conversation).copied().unwrap_or_default()
+    }
+
+    pub fn mark_read(&mut self, conversation: ConversationId, cx: &mut Context<Self>) {
+        if self.unread.remove(&conversation).is_some() {
+            cx.notify();
+        }
+    }
+
+    /// The unsent composer text of a conversation.
+    pub fn draft(&self, conversation: ConversationId) -> Option<&str> {
+        self.drafts.get(conversation)
+    }
+
+    pub fn set_draft(&mut self, conversation: ConversationId, text: &str, cx: &mut Context<Self>) {
+        if self.drafts.set(conversation, text) {
+            cx.notify();
+        }
+    }
+
+    pub fn flags(&self, conversation: ConversationId) -> ConversationFlags {
+        self.flags.get(conversation)
+    }
+
+    pub fn set_flags(&mut self, conversation: ConversationId, flags: ConversationFlags, cx: &mut Context<Self>) {
+        if self.flags(conversation) == flags {
+            return;
+        }
+
+        let op = Op::SetFlags {
+            conversation,
+            flags,
+            at: Local::now(),
+        };
+        self.apply(&op, cx);
+        self.transport.send(op);
+    }
+
+    pub fn attachments(&self) -> &AttachmentCache {
+        &self.attachments
+    }
+
+    /// Download the attachment of a message into the cache, returns its file.
+    pub fn download_attachment(&mut self, conversation: ConversationId, id: MessageId) -> Option<PathBuf> {
+        let attachment = self.message(conversation, id)?.attachment.clone()?;
+        self.attachments.download(&attachment)
+    }
+
+    pub fn recall_window(&self) -> Duration {
+        self.recall_window
+    }
+
+    pub fn set_recall_window(&mut self, window: Duration) {
+        self.recall_window = window;
+    }
+
+    pub fn can_recall(&self, message: &Message) -> bool {
+        message.sender == self.me
+            && !message.is_recalled()
+            && Local::now() - message.sent_at <= self.recall_window
+    }
+
+    /// Load the history of a conversation, until there is a server this fills it with fake messages.
+    pub fn open(&mut self, conversation: ConversationId) {
+        if self.conversations.contains_key(&conversation) {
+            return;
+        }
+
+        let others = match self.members.get(&conversation) {
+            Some(members) => members.iter().copied().filter(|id| *id != self.me).collect(),
+            None => vec![conversation.0],
+        };
+        let count = 5000;
+        let messages = (0..count)
+            .map(|i| Message::fake(self.next_message_id(), conversation, &others, i, count))
+            .collect::<Vec<_>>();
+        for message in &messages {
+            self.index.insert(message);
+        }
+        self.conversations.insert(conversation, messages);
+    }
+
+    pub fn messages(&self, conversation: ConversationId) -> &[Message] {
+        self.conversations
+            .get(&conversation)
+            .map(|messages| messages.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn message(&self, conversation: ConversationId, id: MessageId) -> Option<&Message> {
+        self.messages(conversation).iter().find(|message| message.id == id)
+    }
+
+    pub fn thread_replies(
+        &self,
+        conversation: ConversationId,
+        root: MessageId,
+    ) -> impl Iterator<Item = &Message> {
+        self.messages(conversation)
+            .iter()
+            .filter(move |message| message.thread == Some(root))
+    }
+
+    /// The number of replies in the thread of a message.
+    pub fn reply_count(&self, conversation: ConversationId, root: MessageId) -> usize {
+        self.reply_counts
+            .get(&conversation)
+            .and_then(|counts| counts.get(&root))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn message_mut(&mut self, conversation: ConversationId, id: MessageId) -> Option<&mut Message> {
+        self.conversations
+            .get_mut(&conversation)?
+            .iter_mut()
+            .find(|message| message.id == id)
+    }
+
+    pub fn send_message(
+        &mut self,
+        conversation: ConversationId,
+        mut message: NewMessage,
+        cx: &mut Context<Self>,
+    ) -> Option<MessageId> {
+        if message.text.trim().is_empty() {
+            return None;
+        }
+        if self.is_group(conversation) && !self.is_member(conversation, self.me) {
+            return None;
+        }
+        message.mentions = self.mentions_in(conversation, &message.text);
+
+        let id = self.next_message_id();
+        let op = Op::Send {
+            conversation,
+            message: id,
+            sender: self.me,
+            at: Local::now(),
+            content: message,
+        };
+        self.apply(&op, cx);
+        self.transport.send(op);
+        Some(id)
+    }
+
+    /// Send the text of messages from one conversation to another, in the order given.
+    ///
+    /// Attachments stay behind until the transport can carry them.
+    pub fn forward_messages(
+        &mut self,
+        from: ConversationId,
+        ids: &[MessageId],
+        to: ConversationId,
+        cx: &mut Context<Self>,
+    ) -> usize {
+        let texts = ids
+            .iter()
+            .filter_map(|id| self.message(from, *id))
+            .filter(|message| !message.is_recalled() && !message.is_system())
+            .map(|message| message.text.clone())
+            .collect::<Vec<_>>();
+        texts
+            .into_iter()
+            .filter_map(|text| {
+                let message = NewMessage {
+                    text,
+                    ..Default::default()
+                };
+                self.send_message(to, message, cx)
+            })
+            .count()
+    }
+
+    /// Replace the text of one of our own messages and send the new revision.
+    pub fn edit_message(
+        &mut self,
+        conversation: ConversationId,
+        id: MessageId,
+        text: impl Into<SharedString>,
+        cx: &mut Context<Self>,
+    ) {
+        let text: SharedString = text.into();
+        let Some(message) = self.message(conversation, id) else {
+            return;
+        };
+        if message.sender != self.me
+            || message.is_recalled()
+            || text.trim().is_empty()
+            || message.text == text
+        {
+            return;
+        }
+
+        let op = Op::Edit {
+            conversation,
+            message: id,
+            by: self.me,
+            revision: message.revisions.len() as u32 + 1,
+            mentions: self.mentions_in(conversation, &text),
+            text,
+            at: Local::now(),
+        };
+        self.apply(&op, cx);
+        self.transport.send(op);
+    }
+
+    /// Add our reaction with `emoji`, or take it back if we already reacted with it.
+    pub fn toggle_reaction(
+        &mut self,
+        conversation: ConversationId,
+        id: MessageId,
+        emoji: impl Into<SharedString>,
+        cx: &mut Context<Self>,
+    ) {
+        let emoji: SharedString = emoji.into();
+        let Some(message) = self.message(conversation, id) else {
+            return;
+        };
+        if message.is_recalled() {
+            return;
+        }
+
+        let op = if message.has_reacted(self.me, &emoji) {
+            Op::RemoveReaction {
+                conversation,
+                message: id,
+                user: self.me,
+                emoji,
+            }
+        } else {
+            Op::AddReaction {
+                conversation,
+                message: id,
+                user: self.me,
+                emoji,
+            }
+        };
+        self.apply(&op, cx);
+        self.transport.send(op);
+    }
+
+    /// Remove a message from this client only, the other participants still see it.
+    pub fn delete_message(
+        &mut self,
+        conversation: ConversationId,
+        id: MessageId,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(messages) = self.conversations.get_mut(&conversation) else {
+            return;
+        };
+        let Some(ix) = messages.iter().position(|message| message.id == id) else {
+            return;
+        };
+        let message = messages.remove(ix);
+        if let Some(root) = message.thread
+            && let Some(count) = self.reply_counts.get_mut(&conversation).and_then(|counts| counts.get_mut(&root))
+        {
+            *count = count.saturating_sub(1);
+        }
+        self.pending_edits.remove(&(conversation, id));
+        self.index.remove(conversation, id);
+        self.release_attachment(message.attachment);
+        cx.notify();
+    }
+
+    /// Recall one of our own messages for everyone, within the recall window.
+    pub fn recall_message(
+        &mut self,
+        conversation: ConversationId,
+        id: MessageId,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(message) = self.message(conversation, id) else {
+            return;
+        };
+        if !self.can_recall(message) {
+            return;
+        }
+
+        let op = Op::Recall {
+            conversation,
+            message: id,
+            by: self.me,
+        };
+        self.apply(&op, cx);
+        self.transport.send(op);
+    }
+
+    /// Purge a cached attachment once no message references it anymore.
+    fn release_attachment(&mut self, attachment: Option<Attachment>) {
+        let Some(attachment) = attachment else {
+            return;
+        };
+        let referenced = self
+            .conversations
+            .values()
+            .flatten()
+            .any(|message| message.attachment.as_ref().is_some_and(|a| a.id == attachment.id));
+        if !referenced {
+            self.attachments.purge(&attachment.id);
+        }
+    }
+
+    /// Apply an operation received from the server, a new message of someone else is announced.
+    pub fn receive(&mut self, op: Op, cx: &mut Context<Self>) {
+        let received = match &op {
+            Op::Send {
+                conversation,
+                message,
+                sender,
+                ..
+            } if *sender != self.me && self.message(*conversation, *message).is_none() => {
+                Some((*conversation, *message))
+            }
+            _ => None,
+        };
+        self.apply(&op, cx);
+        if let Some((conversation, message)) = received {
+            cx.emit(ChatEvent::Received { conversation, message });
+        }
+    }
+
+    fn apply(&mut self, op: &Op, cx: &mut Context<Self>) {
+        match op {
+            Op::Send {
+                conversation,
+                message,
+                sender,
+                at,
+                content,
+            } => {
+                if self.message(*conversation, *message).is_some() {
+                    return;
+                }
+                if *sender != self.me {
+                    let unread = self.unread.entry(*conversation).or_default();
+                    unread.messages += 1;
+                    if content.mentions.contains(&self.me) {
+                        unread.mentions += 1;
+                    }
+                }
+                self.next_message_id = self.next_message_id.max(message.0 + 1);
+                if let Some(root) = content.thread {
+                    *self.reply_counts.entry(*conversation).or_default().entry(root).or_default() += 1;
+                }
+                self.conversations
+                    .entry(*conversation)
+                    .or_default()
+                    .push(Message {
+                        id: *message,
+                        conversation: *conversation,
+                        sender: *sender,
+                        kind: MessageKind::Normal,
+                        text: content.text.clone(),
+                        attachment: None,
+                        reply_to: content.reply_to,
+                        thread: content.thread,
+                        sent_at: *at,
+                        edited_at: None,
+                        revisions: vec![],
+                        reactions: vec![],
+                        mentions: content.mentions.clone(),
+                    });
+                if let Some(message) = self.conversations.get(conversation).and_then(|messages| messages.last()) {
+                    self.index.insert(message);
+                }
+            }
+            Op::Edit {
+                conversation,
+                message,
+                by,
+                revision,
+                text,
+                mentions,
+                at,
+            } => {
+                let key = (*conversation, *message);
+                let Some(message) = self.message_mut(*conversation, *message) else {
+                    return;
+                };
+                if message.is_recalled() || message.sender != *by {
+                    return;
+                }
+                let next = message.revisions.len() as u32 + 1;
+                // Revisions we already have are duplicates, later ones wait for those before them.
+                if *revision < next {
+                    return;
+                }
+                if *revision > next {
+                    self.pending_edits.entry(key).or_default().push(op.clone());
+                    return;
+                }
+                message.revisions.push(Revision {
+                    text: message.text.clone(),
+                    at: message.edited_at.unwrap_or(message.sent_at),
+                });
+                message.text = text.clone();
+                message.mentions = mentions.clone();
+                message.edited_at = Some(*at);
+                let message = message.clone();
+                self.index.insert(&message);
+
+                let following = self.pending_edits.get_mut(&key).and_then(|ops| {
+                    let ix = ops
+                        .iter()
+                        .position(|op| matches!(op, Op::Edit { revision, .. } if *revision == next + 1))?;
+                    Some(ops.remove(ix))
+                });
+                if self.pending_edits.get(&key).is_some_and(Vec::is_empty) {
+                    self.pending_edits.remove(&key);
+                }
+                if let Some(op) = following {
+                    self.apply(&op, cx);
+                }
+            }
+            Op::Recall {
+                conversation,
+                message,
+                by,
+            } => {
+                let Some(message) = self.message_mut(*conversation, *message) else {
+                    return;
+                };
+                if message.is_recalled() || message.sender != *by {
+                    return;
+                }
+                message.kind = MessageKind::Recalled;
+                message.text = SharedString::default();
+                message.revisions.clear();
+                message.reactions.clear();
+                let attachment = message.attachment.take();
+                let (conversation, id) = (message.conversation, message.id);
+                self.pending_edits.remove(&(conversation, id));
+                self.index.remove(conversation, id);
+                self.release_attachment(attachment);
+            }
+            Op::AddReaction {
+                conversation,
+                message,
+                user,
+                emoji,
+            } => {
+                let Some(message) = self.message_mut(*conversation, *message) else {
+                    return;
+                };
+                if message.is_recalled() || message.has_reacted(*user, emoji) {
+                    return;
+                }
+                match message.reactions.iter_mut().find(|reaction| reaction.emoji == *emoji) {
+                    Some(reaction) => reaction.users.push(*user),
+                    None => message.reactions.push(Reaction {
+                        emoji: emoji.clone(),
+                        users: vec![*user],
+                    }),
+                }
+            }
+            Op::RemoveReaction {
+                conversation,
+                message,
+                user,
+                emoji,
+            } => {
+                let Some(message) = self.message_mut(*conversation, *message) else {
+                    return;
+                };
+                if !message.has_reacted(*user, emoji) {
+                    return;
+                }
+                for reaction in message.reactions.iter_mut().filter(|reaction| reaction.emoji == *emoji) {
+                    reaction.users.retain(|u| u != user);
+                }
+                message.reactions.retain(|reaction| !reaction.users.is_empty());
+            }
+            Op::CreateGroup {
+                conversation,
+                message,
+                by,
+                title,
+                members,
+                at,
+            } => {
+                if self.message(*conversation, *message).is_some() {
+                    return;
+                }
+                self.groups.insert(
+                    *conversation,
+                    Group {
+                        title: title.clone(),
+                        admins: vec![*by],
+                    },
+                );
+                self.members.insert(*conversation, members.clone());
+                let text = format!("{} created the group \"{}\"", self.display_name(*by), title);
+                self.push_system_message(*conversation, *message, *by, text, *at);
+            }
+            Op::AddMembers {
+                conversation,
+                message,
+                by,
+                users,
+                at,
+            } => {
+                if self.message(*conversation, *message).is_some() || !self.is_admin(*conversation, *by) {
+                    return;
+                }
+                let members = self.members.entry(*conversation).or_default();
+                for user in users {
+                    if !members.contains(user) {
+                        members.push(*user);
+                    }
+                }
+                let names = users
+                    .iter()
+                    .map(|user| self.display_name(*user))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let text = format!("{} added {}", self.display_name(*by), names);
+                self.push_system_message(*conversation, *message, *by, text, *at);
+            }
+            Op::RemoveMember {
+                conversation,
+                message,
+                by,
+                user,
+                at,
+            } => {
+                // Anyone can leave, only admins remove others.
+                if self.message(*conversation, *message).is_some() || (by != user && !self.is_admin(*conversation, *by)) {
+                    return;
+                }
+                if let Some(members) = self.members.get_mut(conversation) {
+                    members.retain(|member| member != user);
+                }
+                let members = self.members(*conversation).to_vec();
+                if let Some(group) = self.groups.get_mut(conversation) {
+                    group.admins.retain(|admin| admin != user);
+                    // A group is never left without an admin, the longest standing member takes over.
+                    if group.admins.is_empty() {
+                        group.admins.extend(members.first());
+                    }
+                }
+                let text = if by == user {
+                    format!("{} left", self.display_name(*user))
+                } else {
+                    format!("{} removed {}", self.display_name(*by), self.display_name(*user))
+                };
+                self.push_system_message(*conversation, *message, *by, text, *at);
+            }
+            Op::SetAdmin {
+                conversation,
+                message,
+                by,
+                user,
+                admin,
+                at,
+            } => {
+                if self.message(*conversation, *message).is_some() || !self.is_admin(*conversation, *by) {
+                    return;
+                }
+                let Some(group) = self.groups.get_mut(conversation) else {
+                    return;
+                };
+                group.admins.retain(|a| a != user);
+                if *admin {
+                    group.admins.push(*user);
+                }
+                let text = if *admin {
+                    format!("{} made {} an admin", self.display_name(*by), self.display_name(*user))
+                } else {
+                    format!("{} removed {} as admin", self.display_name(*by), self.display_name(*user))
+                };
+                self.push_system_message(*conversation, *message, *by, text, *at);
+            }
+            Op::SetFlags {
+                conversation,
+                flags,
+                at,
+            } => {
+                if !self.flags.set(*conversation, *flags, *at) {
+                    return;
+                }
+            }
+        }
+        cx.notify();
+    }
+
+    fn push_system_message(
+        &mut self,
+        conversation: ConversationId,
+        id: MessageId,
+        by: UserId,
+        text: String,
+        at: DateTime<Local>,
+    ) {
+        self.next_message_id = self.next_message_id.max(id.0 + 1);
+        self.conversations.entry(conversation).or_default().push(Message {
+            id,
+            conversation,
+            sender: by,
+            kind: MessageKind::System,
+            text: text.into(),
+            attachment: None,
+            reply_to: None,
+            thread: None,
+            sent_at: at,
+            edited_at: None,
+            revisions: vec![],
+            reactions: vec![],
+            mentions: vec![],
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gpui::TestAppContext;
+
+    use super::*;
+
+    const CONVERSATION: ConversationId = ConversationId(1);
+    const MESSAGE: MessageId = MessageId(1);
+
+    fn new_store(cx: &mut TestAppContext) -> (Entity<ChatStore>, Rc<LoopbackTransport>, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let (store, transport) = store_in(dir.path(), cx);
+        (store, transport, dir)
+    }
+
+    /// A store keeping its files in `dir`, a second one in the same folder is the next run.
+    fn store_in(dir: &Path, cx: &mut TestAppContext) -> (Entity<ChatStore>, Rc<LoopbackTransport>) {
+        let storage = StorageSettings {
+            data_dir: Some(dir.join("data")),
+            cache_dir: Some(dir.join("cache")),
+            ..Default::default()
+        };
+        let transport = Rc::new(LoopbackTransport::default());
+        let store = cx.new(|_| ChatStore::new(LOCAL_USER, transport.clone(), &storage));
+        (store, transport)
+    }
+
+    fn send(sender: UserId, text: &str) -> Op {
+        Op::Send {
+            conversation: CONVERSATION,
+            message: MESSAGE,
+            sender,
+            at: Local::now(),
+            content: NewMessage {
+                text: text.to_string().into(),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn edit(revision: u32, text: &str) -> Op {
+        edit_by(1, revision, text)
+    }
+
+    fn edit_by(by: UserId, revision: u32, text: &str) -> Op {
+        Op::Edit {
+            conversation: CONVERSATION,
+            message: MESSAGE,
+            by,
+            revision,
+            text: text.to_string().into(),
+            mentions: vec![],
+            at: Local::now(),
+        }
+    }
+
+    fn receive(store: &Entity<ChatStore>, ops: impl IntoIterator<Item = Op>, cx: &mut TestAppContext) {
+        store.update(cx, |store, cx| {
+            for op in ops {
+                store.receive(op, cx);
+            }
+        });
+    }
+
+    /// The text of the message and the texts it had before, oldest first.
+    fn texts(store: &Entity<ChatStore>, cx: &mut TestAppContext) -> (String, Vec<String>) {
+        store.read_with(cx, |store, _| {
+            let message = store.message(CONVERSATION, MESSAGE).unwrap();
+            let revisions = message.revisions.iter().map(|revision| revision.text.to_string()).collect();
+            (message.text.to_string(), revisions)
+        })
+    }
+
+    #[gpui::test]
+    fn duplicate_revisions_are_ignored(cx: &mut TestAppContext) {
+        let (store, _transport, _dir) = new_store(cx);
+        receive(&store, [send(1, "a"), edit(1, "b"), edit(1, "b"), edit(1, "c")], cx);
+
+        assert_eq!(texts(&store, cx), ("b".into(), vec!["a".into()]));
+    }
+
+    #[gpui::test]
+    fn gapped_revisions_wait_for_the_missing_ones(cx: &mut TestAppContext) {
+        let (store, _transport, _dir) = new_store(cx);
+        receive(&store, [send(1, "a"), edit(2, "c")], cx);
+        assert_eq!(texts(&store, cx), ("a".into(), vec![]));
+
+        receive(&store, [edit(1, "b")], cx);
+        assert_eq!(texts(&store, cx), ("c".into(), vec!["a".into(), "b".into()]));
+    }
+
+    #[gpui::test]
+    fn reordered_revisions_end_with_the_latest(cx: &mut TestAppContext) {
+        let (store, _transport, _dir) = new_store(cx);
+        receive(&store, [send(1, "a"), edit(3, "d"), edit(2, "c")], cx);
+        assert_eq!(texts(&store, cx), ("a".into(), vec![]));
+
+        receive(&store, [edit(1, "b")], cx);
+        assert_eq!(
+            texts(&store, cx),
+            ("d".into(), vec!["a".into(), "b".into(), "c".into()])
+        );
+
+        // A late duplicate of an older revision changes nothing.
+        receive(&store, [edit(2, "c")], cx);
+        assert_eq!(texts(&store, cx).0, "d");
+    }
+
+    #[gpui::test]
+    fn edit_message_sends_the_next_revision(cx: &mut TestAppContext) {
+        let (store, transport, _dir) = new_store(cx);
+        receive(&store, [send(LOCAL_USER, "a")], cx);
+        store.update(cx, |store, cx| {
+            store.edit_message(CONVERSATION, MESSAGE, "b", cx);
+            store.edit_message(CONVERSATION, MESSAGE, "b", cx);
+            store.edit_message(CONVERSATION, MESSAGE, "c", cx);
+        });
+
+        let revisions = transport
+            .sent()
+            .into_iter()
+            .filter_map(|op| match op {
+                Op::Edit { revision, .. } => Some(revision),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(revisions, vec![1, 2]);
+        assert_eq!(texts(&store, cx), ("c".into(), vec!["a".into(), "b".into()]));
+    }
+
+    #[gpui::test]
+    fn edits_of_someone_else_are_not_sent(cx: &mut TestAppContext) {
+        let (store, transport, _dir) = new_store(cx);
+        receive(&store, [send(1, "a")], cx);
+        store.update(cx, |store, cx| store.edit_message(CONVERSATION, MESSAGE, "b", cx));
+
+        assert!(transport.sent().is_empty());
+        assert_eq!(texts(&store, cx).0, "a");
+    }
+
+    fn recall(by: UserId) -> Op {
+        Op::Recall {
+            conversation: CONVERSATION,
+            message: MESSAGE,
+            by,
+        }
+    }
+
+    #[gpui::test]
+    fn only_the_sender_can_recall(cx: &mut TestAppContext) {
+        let (store, _transport, _dir) = new_store(cx);
+        receive(&store, [send(1, "a"), recall(2)], cx);
+        let recalled = |cx: &mut TestAppContext| {
+            store.read_with(cx, |store, _| store.message(CONVERSATION, MESSAGE).unwrap().is_recalled())
+        };
+        assert!(!recalled(cx));
+
+        receive(&store, [recall(1)], cx);
+        assert!(recalled(cx));
+    }
+
+    #[gpui::test]
+    fn only_the_sender_can_edit(cx: &mut TestAppContext) {
+        let (store, _transport, _dir) = new_store(cx);
+        receive(&store, [send(1, "a"), edit_by(2, 1, "b")], cx);
+        assert_eq!(texts(&store, cx), ("a".into(), vec![]));
+
+        receive(&store, [edit_by(1, 1, "c")], cx);
+        assert_eq!(texts(&store, cx), ("c".into(), vec!["a".into()]));
+    }
+
+    #[gpui::test]
+    fn deleted_messages_drop_their_pending_edits(cx: &mut TestAppContext) {
+        let (store, _transport, _dir) = new_store(cx);
+        receive(&store, [send(1, "a"), edit(2, "c")], cx);
+        store.update(cx, |store, cx| store.delete_message(CONVERSATION, MESSAGE, cx));
+
+        // The message arrives again, the edit that waited for the deleted one is gone.
+        receive(&store, [send(1, "a"), edit(1, "b")], cx);
+        assert_eq!(texts(&store, cx), ("b".into(), vec!["a".into()]));
+    }
+
+    #[gpui::test]
+    fn messages_are_recalled_within_the_recall_window(cx: &mut TestAppContext) {
+        let (store, transport, _dir) = new_store(cx);
+        let op = Op::Send {
+            conversation: CONVERSATION,
+            message: MESSAGE,
+            sender: LOCAL_USER,
+            at: Local::now() - Duration::minutes(3),
+            content: NewMessage {
+                text: "a".into(),
+                ..Default::default()
+            },
+        };
+        receive(&store, [op], cx);
+        let recall = |cx: &mut TestAppContext| {
+            store.update(cx, |store, cx| {
+                store.recall_message(CONVERSATION, MESSAGE, cx);
+                store.message(CONVERSATION, MESSAGE).unwrap().is_recalled()
+            })
+        };
+        assert!(!recall(cx));
+        assert!(transport.sent().is_empty());
+
+        store.update(cx, |store, _| store.set_recall_window(Duration::minutes(5)));
+        assert!(recall(cx));
+    }
+
+    fn react(add: bool, user: UserId, emoji: &str) -> Op {
+        let (conversation, message, emoji) = (CONVERSATION, MESSAGE, emoji.to_string().into());
+        if add {
+            Op::AddReaction {
+                conversation,
+                message,
+                user,
+                emoji,
+            }
+        } else {
+            Op::RemoveReaction {
+                conversation,
+                message,
+                user,
+                emoji,
+            }
+        }
+    }
+
+    /// The emojis of the message with the number of users of each.
+    fn reactions(store: &Entity<ChatStore>, cx: &mut TestAppContext) -> Vec<(String, usize)> {
+        store.read_with(cx, |store, _| {
+            let message = store.message(CONVERSATION, MESSAGE).unwrap();
+            message.reactions.iter().map(|reaction| (reaction.emoji.to_string(), reaction.users.len())).collect()
+        })
+    }
+
+    #[gpui::test]
+    fn adding_a_reaction_twice_counts_once(cx: &mut TestAppContext) {
+        let (store, _transport, _dir) = new_store(cx);
+        receive(&store, [send(1, "a"), react(true, 2, "👍"), react(true, 2, "👍")], cx);
+        assert_eq!(reactions(&store, cx), [("👍".to_string(), 1)]);
+
+        receive(&store, [react(true, 3, "👍"), react(true, 3, "🎉")], cx);
+        assert_eq!(reactions(&store, cx), [("👍".to_string(), 2), ("🎉".to_string(), 1)]);
+    }
+
+    #[gpui::test]
+    fn removing_a_missing_reaction_changes_nothing(cx: &mut TestAppContext) {
+        let (store, _transport, _dir) = new_store(cx);
+        receive(&store, [send(1, "a"), react(true, 2, "👍"), react(false, 3, "👍"), react(false, 2, "🎉")], cx);
+        assert_eq!(reactions(&store, cx), [("👍".to_string(), 1)]);
+
+        receive(&store, [react(false, 2, "👍"), react(false, 2, "👍")], cx);
+        assert!(reactions(&store, cx).is_empty());
+    }
+
+    fn reply(message: u64, sender: UserId) -> Op {
+        Op::Send {
+            conversation: CONVERSATION,
+            message: MessageId(message),
+            sender,
+            at: Local::now(),
+            content: NewMessage {
+                text: "reply".into(),
+                thread: Some(MESSAGE),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[gpui::test]
+    fn replies_are_counted_by_thread(cx: &mut TestAppContext) {
+        let (store, _transport, _dir) = new_store(cx);
+        let count = |cx: &mut TestAppContext| store.read_with(cx, |store, _| store.reply_count(CONVERSATION, MESSAGE));
+        receive(&store, [send(1, "a"), reply(2, 1), reply(3, LOCAL_USER), reply(2, 1)], cx);
+        assert_eq!(count(cx), 2);
+
+        store.update(cx, |store, cx| store.delete_message(CONVERSATION, MessageId(2), cx));
+        assert_eq!(count(cx), 1);
+
+        // A recalled reply stays in the thread as a tombstone.
+        store.update(cx, |store, cx| store.recall_message(CONVERSATION, MessageId(3), cx));
+        assert!(store.read_with(cx, |store, _| store.message(CONVERSATION, MessageId(3)).unwrap().is_recalled()));
+        assert_eq!(count(cx), 1);
+    }
+
+    /// Receive two messages sharing `attachment`.
+    fn receive_shared(store: &Entity<ChatStore>, attachment: &Attachment, cx: &mut TestAppContext) {
+        store.update(cx, |store, cx| {
+            for id in [MessageId(1), MessageId(2)] {
+                let op = Op::Send {
+                    conversation: CONVERSATION,
+                    message: id,
+                    sender: 1,
+                    at: Local::now(),
+                    content: NewMessage::default(),
+                };
+                store.receive(op, cx);
+                store.message_mut(CONVERSATION, id).unwrap().attachment = Some(attachment.clone());
+            }
+        });
+    }
+
+    #[gpui::test]
+    fn shared_attachments_are_purged_with_the_last_message(cx: &mut TestAppContext) {
+        let (store, _transport, dir) = new_store(cx);
+        let image = dir.path().join("photo.png");
+        std::fs::write(&image, "png").unwrap();
+        let attachment = Attachment {
+            id: "images/photo.png".into(),
+            source: image.as_path().into(),
+        };
+        receive_shared(&store, &attachment, cx);
+        let cached = store
+            .update(cx, |store, _| store.download_attachment(CONVERSATION, MessageId(1)))
+            .unwrap();
+        assert!(cached.starts_with(dir.path().join("cache")));
+
+        // The cache is saved, the next run purges the file as well.
+        let (store, _transport) = store_in(dir.path(), cx);
+        receive_shared(&store, &attachment, cx);
+        store.update(cx, |store, cx| store.delete_message(CONVERSATION, MessageId(1), cx));
+        assert!(cached.exists());
+
+        store.update(cx, |store, cx| store.delete_message(CONVERSATION, MessageId(2), cx));
+        assert!(!cached.exists());
+        assert!(image.exists());
+    }
+
+    #[gpui::test]
+    fn group_ids_differ_between_runs(cx: &mut TestAppContext) {
+        let (first, _transport, _dir) = new_store(cx);
+        let (second, _transport, _dir) = new_store(cx);
+        let create = |store: &Entity<ChatStore>, cx: &mut TestAppContext| {
+            store.update(cx, |store, cx| store.create_group("Team", vec![1, 2], cx))
+        };
+
+        let ids = [create(&first, cx), create(&first, cx), create(&second, cx)];
+        assert!(ids.iter().all(|id| id.0 < 0));
+        assert_ne!(ids[0], ids[1]);
+        assert_ne!(ids[0], ids[2]);
+    }
+
+    #[gpui::test]
+    fn added_groups_are_not_sent(cx: &mut TestAppContext) {
+        let (store, transport, _dir) = new_store(cx);
+        let group = ConversationId(-1);
+        store.update(cx, |store, _| {
+            let admins = vec![LOCAL_USER];
+            store.add_group(group, Group { title: "Team".into(), admins }, vec![LOCAL_USER, 1]);
+        });
+
+        assert!(transport.sent().is_empty());
+        store.read_with(cx, |store, _| {
+            assert_eq!(store.title(group), "Team");
+            assert!(store.is_admin(group, LOCAL_USER));
+            assert!(store.conversations().contains(&group));
+        });
+    }
+
+    #[gpui::test]
+    fn flags_and_drafts_are_back_in_the_next_run(cx: &mut TestAppContext) {
+        let (store, _transport, dir) = new_store(cx);
+        let group = ConversationId(-1);
+        let pinned = ConversationFlags {
+            pinned: true,
+            ..Default::default()
+        };
+        store.update(cx, |store, cx| {
+            store.set_flags(group, pinned, cx);
+            store.set_draft(ConversationId(1), "Hello", cx);
+        });
+
+        let (store, _transport) = store_in(dir.path(), cx);
+        store.read_with(cx, |store, _| {
+            assert_eq!(store.flags(group), pinned);
+            assert_eq!(store.draft(ConversationId(1)), Some("Hello"));
+        });
+    }
+
+    #[gpui::test]
+    fn group_members_are_listed_once(cx: &mut TestAppContext) {
+        let (store, _transport, _dir) = new_store(cx);
+        let group = store.update(cx, |store, cx| store.create_group("Team", vec![1, 2, 1, LOCAL_USER, 2], cx));
+
+        store.read_with(cx, |store, _| assert_eq!(store.members(group), &[LOCAL_USER, 1, 2]));
+    }
+
+    #[gpui::test]
+    fn only_admins_change_the_members(cx: &mut TestAppContext) {
+        let (store, _transport, _dir) = new_store(cx);
+        let group = store.update(cx, |store, cx| store.create_group("Team", vec![1, 2], cx));
+        let at = Local::now();
+        let add = |message, by| Op::AddMembers {
+            conversation: group,
+            message: MessageId(message),
+            by,
+            users: vec![3],
+            at,
+        };
+        let remove = |message, by, user| Op::RemoveMember {
+            conversation: group,
+            message: MessageId(message),
+            by,
+            user,
+            at,
+        };
+        let set_admin = |message, by, user| Op::SetAdmin {
+            conversation: group,
+            message: MessageId(message),
+            by,
+            user,
+            admin: true,
+            at,
+        };
+        let members = |cx: &mut TestAppContext| store.read_with(cx, |store, _| store.members(group).to_vec());
+        let is_admin = |user, cx: &mut TestAppContext| store.read_with(cx, |store, _| store.is_admin(group, user));
+
+        receive(&store, [add(100, 1), remove(101, 1, 2), set_admin(102, 1, 1)], cx);
+        assert_eq!(members(cx), [LOCAL_USER, 1, 2]);
+        assert!(!is_admin(1, cx));
+
+        receive(&store, [set_admin(103, LOCAL_USER, 1), add(104, 1), remove(105, 1, 2)], cx);
+        assert_eq!(members(cx), [LOCAL_USER, 1, 3]);
+        assert!(is_admin(1, cx));
+    }
+
+    #[gpui::test]
+    fn anyone_can_leave_a_group(cx: &mut TestAppContext) {
+        let (store, _transport, _dir) = new_store(cx);
+        let group = store.update(cx, |store, cx| store.create_group("Team", vec![1, 2], cx));
+        let leave = Op::RemoveMember {
+            conversation: group,
+            message: MessageId(100),
+            by: 2,
+            user: 2,
+            at: Local::now(),
+        };
+        receive(&store, [leave], cx);
+
+        store.read_with(cx, |store, _| assert_eq!(store.members(group), &[LOCAL_USER, 1]));
+    }
+
+    #[gpui::test]
+    fn conversations_are_found_by_title_and_member(cx: &mut TestAppContext) {
+        let (store, _transport, _dir) = new_store(cx);
+        let group = store.update(cx, |store, cx| {
+            store.add_user(1, "Jane Doe");
+            store.add_user(2, "John Smith");
+            store.create_group("Team", vec![1], cx)
+        });
+
+        store.read_with(cx, |store, _| {
+            assert_eq!(store.find_conversations("JOHN"), [ConversationId(2)]);
+            assert_eq!(store.find_conversations("team"), [group]);
+            assert_eq!(store.find_conversations("doe"), [ConversationId(1), group]);
+            assert_eq!(store.find_conversations(" ").len(), 3);
+        });
+    }
+
+    #[test]
+    fn archive_lines_start_with_a_time_and_a_sender() {
+        let (at, sender, text) = parse_archive_line("2024-05-01 09:30 Alice Smith: Hello: there").unwrap();
+        assert_eq!(at.format("%Y-%m-%d %H:%M").to_string(), "2024-05-01 09:30");
+        assert_eq!(sender, "Alice Smith");
+        assert_eq!(text, "Hello: there");
+
+        assert!(parse_archive_line("2024-05-01 09:30 : Hello").is_none());
+        assert!(parse_archive_line("2024-05-01 09:30 Alice Hello").is_none());
+        assert!(parse_archive_line("2024-13-01 09:30 Alice: Hello").is_none());
+        assert!(parse_archive_line("and more").is_none());
+    }
+
+    #[gpui::test]
+    fn archives_are_imported_as_local_history(cx: &mut TestAppContext) {
+        let (store, transport, _dir) = new_store(cx);
+        let archive = "\
+2024-05-01 09:31 Alice: See you
+at noon
+
+2024-05-01 09:30 Me: Lunch?
+";
+        let group = store
+            .update(cx, |store, cx| {
+                store.add_user(LOCAL_USER, "Me");
+                store.import_archive("Lunch", archive, cx)
+            })
+            .unwrap();
+
+        store.read_with(cx, |store, _| {
+            let texts = store
+                .messages(group)
+                .iter()
+                .filter(|message| message.kind == MessageKind::Normal)
+                .map(|message| (store.name(message.sender).to_string(), message.text.to_string()))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                texts,
+                [
+                    ("Me".to_string(), "Lunch?".to_string()),
+                    ("Alice".to_string(), "See you\nat noon".to_string()),
+                ]
+            );
+            assert_eq!(store.unread(group).messages, 0);
+        });
+        let sent = transport
+            .sent()
+            .into_iter()
+            .filter(|op| matches!(op, Op::Send { .. }))
+            .count();
+        assert_eq!(sent, 0);
+    }
+
+    #[gpui::test]
+    fn an_archive_must_start_with_a_message(cx: &mut TestAppContext) {
+        let (store, _transport, _dir) = new_store(cx);
+        let imported = store.update(cx, |store, cx| store.import_archive("Notes", "hello\n", cx));
+        assert!(imported.is_err());
+    }
+}
@@ -1 +1,3 @@
+use gpui::actions;
 
+actions!(contacts, [ToggleSidebar]);
@@ -0,0 +1,64 @@
+use gpui::{Action, App, Context, DismissEvent, Entity, Focusable as _, IntoElement, KeyBinding, ParentElement as _, SharedString, Subscription, Window, actions, anchored, deferred, px};
+use gpui_component::menu::PopupMenu;
+
+actions!(context_menu, [ShowContextMenu]);
+
+/// Open a web address, or a local file, with the default browser.
+#[derive(Action, Clone, PartialEq)]
+#[action(namespace = context_menu, no_json)]
+pub(crate) struct OpenInBrowser(pub(crate) SharedString);
+
+pub fn init(cx: &mut App) {
+    cx.bind_keys([
+        KeyBinding::new("menu", ShowContextMenu, None),
+        KeyBinding::new("shift-f10", ShowContextMenu, None),
+    ]);
+    cx.on_action(|action: &OpenInBrowser, cx| cx.open_url(&action.0));
+}
+
+/// A context menu opened with the keyboard, rendered by the item it belongs to.
+///
+/// Views build it with the same function as their right-click menu so both
+/// offer the same actions.
+pub struct KeyboardMenu<T> {
+    target: T,
+    menu: Entity<PopupMenu>,
+    _subscription: Subscription,
+}
+
+impl<T: 'static> KeyboardMenu<T> {
+    /// Build the menu and focus it, `slot` is where the view keeps it until it is dismissed.
+    pub fn new<V: 'static>(
+        target: T,
+        window: &mut Window,
+        cx: &mut Context<V>,
+        slot: fn(&mut V) -> &mut Option<Self>,
+        build: impl FnOnce(PopupMenu, &mut Window, &mut Context<PopupMenu>) -> PopupMenu,
+    ) -> Self {
+        let menu = PopupMenu::build(window, cx, build);
+        menu.focus_handle(cx).focus(window);
+        let _subscription = cx.subscribe_in(&menu, window, move |this, _, _: &DismissEvent, _, cx| {
+            *slot(this) = None;
+            cx.notify();
+        });
+
+        Self {
+            target,
+            menu,
+            _subscription,
+        }
+    }
+
+    pub fn target(&self) -> &T {
+        &self.target
+    }
+
+    pub fn render(&self) -> impl IntoElement {
+        deferred(
+            anchored()
+                .snap_to_window_with_margin(px(8.))
+                .child(self.menu.clone()),
+        )
+        .with_priority(1)
+    }
+}
@@ -0,0 +1,91 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::chat::ConversationId;
+
+/// Unsent composer text, one file per conversation so drafts survive a restart.
+pub struct Drafts {
+    root: PathBuf,
+    entries: HashMap<ConversationId, String>,
+}
+
+impl Drafts {
+    /// Load the drafts saved under `root`, unreadable files are skipped.
+    pub fn load(root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        let entries = std::fs::read_dir(&root)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|entry| {
+                let path = entry.path();
+                let id = path.file_stem()?.to_str()?.parse().ok()?;
+                let text = std::fs::read_to_string(&path).ok()?;
+                (!text.is_empty()).then_some((ConversationId(id), text))
+            })
+            .collect();
+
+        Self { root, entries }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn get(&self, conversation: ConversationId) -> Option<&str> {
+        self.entries.get(&conversation).map(|text| text.as_str())
+    }
+
+    /// Save the draft of a conversation, an empty text deletes it.
+    ///
+    /// Returns whether the draft changed.
+    pub fn set(&mut self, conversation: ConversationId, text: &str) -> bool {
+        if self.get(conversation).unwrap_or_default() == text {
+            return false;
+        }
+
+        let path = self.root.join(format!("{}.txt", conversation.0));
+        if text.is_empty() {
+            self.entries.remove(&conversation);
+            _ = std::fs::remove_file(&path);
+        } else {
+            self.entries.insert(conversation, text.to_string());
+            if std::fs::create_dir_all(&self.root).is_ok() {
+                _ = std::fs::write(&path, text);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drafts_load_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut drafts = Drafts::load(dir.path());
+        assert!(drafts.set(ConversationId(1), "Hello\nthere"));
+        assert!(drafts.set(ConversationId(-1), "Team"));
+        assert!(!drafts.set(ConversationId(-1), "Team"));
+
+        let drafts = Drafts::load(dir.path());
+        assert_eq!(drafts.get(ConversationId(1)), Some("Hello\nthere"));
+        assert_eq!(drafts.get(ConversationId(-1)), Some("Team"));
+        assert_eq!(drafts.get(ConversationId(2)), None);
+    }
+
+    #[test]
+    fn an_empty_draft_is_deleted() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut drafts = Drafts::load(dir.path());
+        drafts.set(ConversationId(1), "Hello");
+        assert!(drafts.set(ConversationId(1), ""));
+
+        assert_eq!(Drafts::load(dir.path()).get(ConversationId(1)), None);
+        assert!(!dir.path().join("1.txt").exists());
+    }
+}
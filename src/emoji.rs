@@ -0,0 +1,597 @@
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+use anyhow::Result;
+use gpui::{App, AppContext as _, Context, Entity, EventEmitter, Global, IntoElement, ParentElement as _, Render, Styled as _, Subscription, Task, Timer, Window, div, prelude::FluentBuilder as _, px};
+use gpui_component::{ActiveTheme as _, Rope, RopeExt as _, Selectable as _, Sizable as _, button::{Button, ButtonVariants as _}, h_flex, input::{CompletionProvider, Input, InputEvent, InputState}, label::Label, scroll::ScrollableElement as _, v_flex};
+use lsp_types::{CompletionContext, CompletionItem, CompletionResponse, CompletionTextEdit, TextEdit};
+use serde::{Deserialize, Serialize};
+
+use crate::{persist, settings::SettingsStore};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Category {
+    Smileys,
+    People,
+    Nature,
+    Food,
+    Activity,
+    Travel,
+    Objects,
+    Symbols,
+}
+
+impl Category {
+    pub const ALL: [Category; 8] = [
+        Category::Smileys,
+        Category::People,
+        Category::Nature,
+        Category::Food,
+        Category::Activity,
+        Category::Travel,
+        Category::Objects,
+        Category::Symbols,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Category::Smileys => "Smileys",
+            Category::People => "People",
+            Category::Nature => "Animals & Nature",
+            Category::Food => "Food & Drink",
+            Category::Activity => "Activity",
+            Category::Travel => "Travel & Places",
+            Category::Objects => "Objects",
+            Category::Symbols => "Symbols",
+        }
+    }
+
+    /// The emoji shown on the category tab.
+    pub fn icon(&self) -> &'static str {
+        match self {
+            Category::Smileys => "😀",
+            Category::People => "👋",
+            Category::Nature => "🐶",
+            Category::Food => "🍎",
+            Category::Activity => "⚽",
+            Category::Travel => "🚗",
+            Category::Objects => "💡",
+            Category::Symbols => "❤️",
+        }
+    }
+}
+
+pub struct Emoji {
+    pub emoji: &'static str,
+    pub shortcode: &'static str,
+    pub category: Category,
+}
+
+const fn e(emoji: &'static str, shortcode: &'static str, category: Category) -> Emoji {
+    Emoji {
+        emoji,
+        shortcode,
+        category,
+    }
+}
+
+pub static EMOJIS: &[Emoji] = &[
+    e("😀", "grinning", Category::Smileys),
+    e("😃", "smiley", Category::Smileys),
+    e("😄", "smile", Category::Smileys),
+    e("😁", "grin", Category::Smileys),
+    e("😆", "laughing", Category::Smileys),
+    e("😅", "sweat_smile", Category::Smileys),
+    e("😂", "joy", Category::Smileys),
+    e("🤣", "rofl", Category::Smileys),
+    e("🙂", "slightly_smiling_face", Category::Smileys),
+    e("😉", "wink", Category::Smileys),
+    e("😊", "blush", Category::Smileys),
+    e("😇", "innocent", Category::Smileys),
+    e("😍", "heart_eyes", Category::Smileys),
+    e("😘", "kissing_heart", Category::Smileys),
+    e("😋", "yum", Category::Smileys),
+    e("😛", "stuck_out_tongue", Category::Smileys),
+    e("🤔", "thinking", Category::Smileys),
+    e("🤐", "zipper_mouth_face", Category::Smileys),
+    e("😐", "neutral_face", Category::Smileys),
+    e("😏", "smirk", Category::Smileys),
+    e("😒", "unamused", Category::Smileys),
+    e("🙄", "roll_eyes", Category::Smileys),
+    e("😬", "grimacing", Category::Smileys),
+    e("😌", "relieved", Category::Smileys),
+    e("😔", "pensive", Category::Smileys),
+    e("😴", "sleeping", Category::Smileys),
+    e("😷", "mask", Category::Smileys),
+    e("🤯", "exploding_head", Category::Smileys),
+    e("😎", "sunglasses", Category::Smileys),
+    e("😕", "confused", Category::Smileys),
+    e("😮", "open_mouth", Category::Smileys),
+    e("😳", "flushed", Category::Smileys),
+    e("😢", "cry", Category::Smileys),
+    e("😭", "sob", Category::Smileys),
+    e("😱", "scream", Category::Smileys),
+    e("😡", "rage", Category::Smileys),
+    e("🥳", "partying_face", Category::Smileys),
+    e("👋", "wave", Category::People),
+    e("👌", "ok_hand", Category::People),
+    e("✌️", "v", Category::People),
+    e("🤞", "crossed_fingers", Category::People),
+    e("👍", "thumbsup", Category::People),
+    e("👎", "thumbsdown", Category::People),
+    e("👏", "clap", Category::People),
+    e("🙌", "raised_hands", Category::People),
+    e("🙏", "pray", Category::People),
+    e("💪", "muscle", Category::People),
+    e("👀", "eyes", Category::People),
+    e("🤷", "shrug", Category::People),
+    e("🤦", "facepalm", Category::People),
+    e("🐶", "dog", Category::Nature),
+    e("🐱", "cat", Category::Nature),
+    e("🐭", "mouse", Category::Nature),
+    e("🐼", "panda_face", Category::Nature),
+    e("🦊", "fox_face", Category::Nature),
+    e("🐸", "frog", Category::Nature),
+    e("🐵", "monkey_face", Category::Nature),
+    e("🐧", "penguin", Category::Nature),
+    e("🐢", "turtle", Category::Nature),
+    e("🌸", "cherry_blossom", Category::Nature),
+    e("🌻", "sunflower", Category::Nature),
+    e("🌲", "evergreen_tree", Category::Nature),
+    e("🌈", "rainbow", Category::Nature),
+    e("☀️", "sunny", Category::Nature),
+    e("🌙", "crescent_moon", Category::Nature),
+    e("🍎", "apple", Category::Food),
+    e("🍌", "banana", Category::Food),
+    e("🍉", "watermelon", Category::Food),
+    e("🍓", "strawberry", Category::Food),
+    e("🍕", "pizza", Category::Food),
+    e("🍔", "hamburger", Category::Food),
+    e("🍜", "ramen", Category::Food),
+    e("🍣", "sushi", Category::Food),
+    e("🍰", "cake", Category::Food),
+    e("☕", "coffee", Category::Food),
+    e("🍵", "tea", Category::Food),
+    e("🍺", "beer", Category::Food),
+    e("⚽", "soccer", Category::Activity),
+    e("🏀", "basketball", Category::Activity),
+    e("🎾", "tennis", Category::Activity),
+    e("🎮", "video_game", Category::Activity),
+    e("🎲", "game_die", Category::Activity),
+    e("🎸", "guitar", Category::Activity),
+    e("🎉", "tada", Category::Activity),
+    e("🎁", "gift", Category::Activity),
+    e("🏆", "trophy", Category::Activity),
+    e("🚗", "car", Category::Travel),
+    e("🚕", "taxi", Category::Travel),
+    e("🚲", "bike", Category::Travel),
+    e("🚀", "rocket", Category::Travel),
+    e("✈️", "airplane", Category::Travel),
+    e("🚆", "train", Category::Travel),
+    e("🏠", "house", Category::Travel),
+    e("🏖️", "beach_umbrella", Category::Travel),
+    e("🗺️", "world_map", Category::Travel),
+    e("💡", "bulb", Category::Objects),
+    e("💻", "computer", Category::Objects),
+    e("📱", "iphone", Category::Objects),
+    e("📷", "camera", Category::Objects),
+    e("📚", "books", Category::Objects),
+    e("📌", "pushpin", Category::Objects),
+    e("📎", "paperclip", Category::Objects),
+    e("🔒", "lock", Category::Objects),
+    e("🔑", "key", Category::Objects),
+    e("🔔", "bell", Category::Objects),
+    e("⏰", "alarm_clock", Category::Objects),
+    e("❤️", "heart", Category::Symbols),
+    e("💔", "broken_heart", Category::Symbols),
+    e("💯", "100", Category::Symbols),
+    e("🔥", "fire", Category::Symbols),
+    e("✨", "sparkles", Category::Symbols),
+    e("⭐", "star", Category::Symbols),
+    e("✅", "white_check_mark", Category::Symbols),
+    e("❌", "x", Category::Symbols),
+    e("❓", "question", Category::Symbols),
+    e("❗", "exclamation", Category::Symbols),
+    e("⚠️", "warning", Category::Symbols),
+    e("➕", "heavy_plus_sign", Category::Symbols),
+];
+
+pub fn find(shortcode: &str) -> Option<&'static Emoji> {
+    EMOJIS.iter().find(|emoji| emoji.shortcode == shortcode)
+}
+
+/// Emojis whose shortcode contains `query`, the ones starting with it come first.
+pub fn search(query: &str) -> Vec<&'static Emoji> {
+    let query = query.to_lowercase();
+    let mut results = EMOJIS
+        .iter()
+        .filter(|emoji| emoji.shortcode.contains(&query))
+        .collect::<Vec<_>>();
+    results.sort_by_key(|emoji| !emoji.shortcode.starts_with(&query));
+    results
+}
+
+/// Replace every complete `:shortcode:` in `text` by its emoji.
+pub fn replace_shortcodes(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(':') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find(':').and_then(|end| Some((end, find(&after[..end])?))) {
+            Some((end, emoji)) => {
+                result.push_str(emoji.emoji);
+                rest = &after[end + 1..];
+            }
+            None => {
+                result.push(':');
+                rest = after;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// The usage as saved, emojis no longer known are dropped when it is loaded.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SavedUsage {
+    counts: HashMap<String, usize>,
+    recent: Vec<String>,
+}
+
+/// How often and how recently each emoji was picked, saved so it survives a restart.
+pub struct EmojiUsage {
+    path: PathBuf,
+    counts: HashMap<&'static str, usize>,
+    /// Most recent first.
+    recent: Vec<&'static str>,
+    _save_task: Task<()>,
+}
+
+impl Global for EmojiUsage {}
+
+impl EmojiUsage {
+    const RECENT_LIMIT: usize = 24;
+    /// Picking a few emojis in a row is saved once.
+    const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+    /// Load the usage saved in `path`.
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let saved = persist::load::<SavedUsage>(&path);
+        let known = |emoji: &str| EMOJIS.iter().find(|e| e.emoji == emoji).map(|e| e.emoji);
+        Self {
+            counts: saved
+                .counts
+                .iter()
+                .filter_map(|(emoji, count)| Some((known(emoji)?, *count)))
+                .collect(),
+            recent: saved.recent.iter().filter_map(|emoji| known(emoji)).collect(),
+            path,
+            _save_task: Task::ready(()),
+        }
+    }
+
+    pub fn record(emoji: &'static str, cx: &mut App) {
+        let usage = cx.global_mut::<Self>();
+        *usage.counts.entry(emoji).or_default() += 1;
+        usage.recent.retain(|e| *e != emoji);
+        usage.recent.insert(0, emoji);
+        usage.recent.truncate(Self::RECENT_LIMIT);
+
+        let task = cx.spawn(async move |cx| {
+            Timer::after(Self::SAVE_DELAY).await;
+            _ = cx.update(|cx| cx.global::<Self>().save());
+        });
+        cx.global_mut::<Self>()._save_task = task;
+    }
+
+    fn save(&self) {
+        let saved = SavedUsage {
+            counts: self.counts.iter().map(|(emoji, count)| (emoji.to_string(), *count)).collect(),
+            recent: self.recent.iter().map(|emoji| emoji.to_string()).collect(),
+        };
+        persist::save(&self.path, &saved);
+    }
+
+    pub fn recent(&self) -> &[&'static str] {
+        &self.recent
+    }
+
+    pub fn frequent(&self, limit: usize) -> Vec<&'static str> {
+        let mut frequent = self.counts.iter().collect::<Vec<_>>();
+        frequent.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        frequent.into_iter().take(limit).map(|(emoji, _)| *emoji).collect()
+    }
+}
+
+pub fn init(cx: &mut App) {
+    let path = SettingsStore::get(cx).storage.data_dir().join("emoji.json");
+    cx.set_global(EmojiUsage::load(path));
+    // A pick may still be waiting for the save delay.
+    cx.on_app_quit(|cx| {
+        cx.global::<EmojiUsage>().save();
+        async {}
+    })
+    .detach();
+}
+
+pub struct EmojiPicked(pub &'static str);
+
+/// Emojis by category with a search box, recent and frequent ones are listed first.
+pub struct EmojiPicker {
+    search: Entity<InputState>,
+    /// `None` shows the recent and frequently used emojis.
+    category: Option<Category>,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl EventEmitter<EmojiPicked> for EmojiPicker {}
+
+impl EmojiPicker {
+    pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let search = cx.new(|cx| InputState::new(window, cx).placeholder("Search emoji"));
+        let _subscriptions = vec![cx.subscribe(&search, |_, _, event: &InputEvent, cx| {
+            if let InputEvent::Change = event {
+                cx.notify();
+            }
+        })];
+
+        Self {
+            search,
+            category: None,
+            _subscriptions,
+        }
+    }
+
+    fn pick(&mut self, emoji: &'static str, cx: &mut Context<Self>) {
+        EmojiUsage::record(emoji, cx);
+        cx.emit(EmojiPicked(emoji));
+        cx.notify();
+    }
+
+    fn render_grid(
+        &self,
+        id: &'static str,
+        emojis: impl IntoIterator<Item = &'static str>,
+        cx: &Context<Self>,
+    ) -> impl IntoElement {
+        h_flex()
+            .flex_wrap()
+            .gap_0p5()
+            .children(emojis.into_iter().enumerate().map(|(ix, emoji)| {
+                Button::new((id, ix))
+                    .label(emoji)
+                    .small()
+                    .ghost()
+                    .on_click(cx.listener(move |this, _, _, cx| this.pick(emoji, cx)))
+            }))
+    }
+
+    fn render_section(
+        &self,
+        id: &'static str,
+        title: &'static str,
+        emojis: Vec<&'static str>,
+        cx: &Context<Self>,
+    ) -> impl IntoElement {
+        v_flex()
+            .gap_1()
+            .child(
+                Label::new(title)
+                    .text_xs()
+                    .text_color(cx.theme().muted_foreground),
+            )
+            .map(|this| {
+                if emojis.is_empty() {
+                    this.child(
+                        Label::new("Nothing yet")
+                            .text_xs()
+                            .text_color(cx.theme().muted_foreground),
+                    )
+                } else {
+                    this.child(self.render_grid(id, emojis, cx))
+                }
+            })
+    }
+}
+
+impl Render for EmojiPicker {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let query = self.search.read(cx).value().trim().to_string();
+        let usage = cx.global::<EmojiUsage>();
+        let recent = usage.recent().to_vec();
+        let frequent = usage.frequent(16);
+
+        v_flex()
+            .w(px(320.))
+            .h(px(320.))
+            .gap_2()
+            .child(Input::new(&self.search).small().cleanable(true))
+            .child(
+                h_flex()
+                    .gap_0p5()
+                    .child(
+                        Button::new("emoji-category-recent")
+                            .label("🕘")
+                            .small()
+                            .ghost()
+                            .selected(self.category.is_none())
+                            .on_click(cx.listener(|this, _, _, cx| {
+                                this.category = None;
+                                cx.notify();
+                            })),
+                    )
+                    .children(Category::ALL.iter().enumerate().map(|(ix, category)| {
+                        let category = *category;
+                        Button::new(("emoji-category", ix))
+                            .label(category.icon())
+                            .small()
+                            .ghost()
+                            .selected(self.category == Some(category))
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                this.category = Some(category);
+                                cx.notify();
+                            }))
+                    })),
+            )
+            .child(
+                div()
+                    .flex_1()
+                    .overflow_y_scrollbar()
+                    .map(|this| {
+                        if !query.is_empty() {
+                            this.child(self.render_section(
+                                "emoji-search",
+                                "Search results",
+                                search(&query).into_iter().map(|emoji| emoji.emoji).collect(),
+                                cx,
+                            ))
+                        } else if let Some(category) = self.category {
+                            this.child(self.render_section(
+                                "emoji",
+                                category.label(),
+                                EMOJIS
+                                    .iter()
+                                    .filter(|emoji| emoji.category == category)
+                                    .map(|emoji| emoji.emoji)
+                                    .collect(),
+                                cx,
+                            ))
+                        } else {
+                            this.child(
+                                v_flex()
+                                    .gap_2()
+                                    .child(self.render_section("emoji-frequent", "Frequently used", frequent, cx))
+                                    .child(self.render_section("emoji-recent", "Recent", recent, cx)),
+                            )
+                        }
+                    }),
+            )
+    }
+}
+
+/// Completes `:shortcode` to emojis while typing.
+pub struct ShortcodeCompletionProvider;
+
+impl ShortcodeCompletionProvider {
+    const MIN_QUERY_LEN: usize = 2;
+
+    /// The byte offset of the `:` starting the shortcode being typed before `offset`, and the query after it.
+    pub fn query_at(text: &str, offset: usize) -> Option<(usize, &str)> {
+        let before = &text[..offset];
+        let start = before.rfind(':')?;
+        let query = &before[start + 1..];
+        let starts_word = before[..start]
+            .chars()
+            .next_back()
+            .is_none_or(|c| c.is_whitespace());
+        let valid = query
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '+' || c == '-');
+        (starts_word && valid && query.len() >= Self::MIN_QUERY_LEN).then_some((start, query))
+    }
+
+    pub fn completions_at(text: &Rope, offset: usize) -> Vec<CompletionItem> {
+        let content = text.to_string();
+        let Some((start, query)) = Self::query_at(&content, offset) else {
+            return vec![];
+        };
+        let range = lsp_types::Range::new(text.offset_to_position(start), text.offset_to_position(offset));
+
+        search(query)
+            .into_iter()
+            .take(10)
+            .map(|emoji| CompletionItem {
+                // The menu highlights as many bytes of the label as the filter text has,
+                // so the label starts with the shortcode and the emoji goes in the detail.
+                label: format!(":{}:", emoji.shortcode),
+                filter_text: Some(if emoji.shortcode.starts_with(query) {
+                    format!(":{}", query)
+                } else {
+                    String::new()
+                }),
+                detail: Some(emoji.emoji.to_string()),
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                    range,
+                    new_text: emoji.emoji.to_string(),
+                })),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    pub fn is_trigger(new_text: &str) -> bool {
+        !new_text.is_empty()
+            && new_text
+                .chars()
+                .all(|c| c == ':' || c.is_ascii_alphanumeric() || c == '_' || c == '+' || c == '-')
+    }
+}
+
+impl CompletionProvider for ShortcodeCompletionProvider {
+    fn completions(
+        &self,
+        text: &Rope,
+        offset: usize,
+        _: CompletionContext,
+        _: &mut Window,
+        _: &mut Context<InputState>,
+    ) -> Task<Result<CompletionResponse>> {
+        Task::ready(Ok(CompletionResponse::Array(Self::completions_at(text, offset))))
+    }
+
+    fn is_completion_trigger(&self, _: usize, new_text: &str, _: &mut Context<InputState>) -> bool {
+        Self::is_trigger(new_text)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(text: &str) -> Option<(usize, &str)> {
+        ShortcodeCompletionProvider::query_at(text, text.len())
+    }
+
+    #[test]
+    fn unknown_shortcodes_are_kept() {
+        assert_eq!(replace_shortcodes("a :nope: b :tada:"), "a :nope: b 🎉");
+        assert_eq!(replace_shortcodes("at 10:30 :smile:"), "at 10:30 😄");
+    }
+
+    #[test]
+    fn adjacent_shortcodes_are_replaced() {
+        assert_eq!(replace_shortcodes(":tada::heart:"), "🎉❤️");
+        assert_eq!(replace_shortcodes("::tada:"), ":🎉");
+    }
+
+    #[test]
+    fn a_colon_at_the_end_is_kept() {
+        assert_eq!(replace_shortcodes("note:"), "note:");
+        assert_eq!(replace_shortcodes(":tada: :"), "🎉 :");
+        assert_eq!(replace_shortcodes(":tada"), ":tada");
+    }
+
+    #[test]
+    fn a_query_starts_a_word() {
+        assert_eq!(query("hi :ta"), Some((3, "ta")));
+        assert_eq!(query(":thumbs"), Some((0, "thumbs")));
+        assert_eq!(query("hi :t"), None);
+        assert_eq!(query("10:30"), None);
+        assert_eq!(query("hi :ta da"), None);
+    }
+
+    #[test]
+    fn queries_after_multibyte_text_are_found_by_byte_offset() {
+        assert_eq!(query("日本 :tad"), Some((7, "tad")));
+        assert_eq!(query("😄 :smi"), Some((5, "smi")));
+        assert_eq!(query("日本:tad"), None);
+
+        let text = "héllo :smi and more";
+        let offset = text.find(" and").unwrap();
+        assert_eq!(ShortcodeCompletionProvider::query_at(text, offset), Some((7, "smi")));
+    }
+}
@@ -0,0 +1,133 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::{chat::ConversationId, persist};
+
+/// How a conversation is listed, set per conversation by the user.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConversationFlags {
+    /// Listed above the other conversations.
+    pub pinned: bool,
+    /// No notifications, the unread badge is grey.
+    pub muted: bool,
+    /// Only listed in the Archived view.
+    pub archived: bool,
+}
+
+/// The flags of a conversation as they are saved.
+#[derive(Serialize, Deserialize)]
+struct SavedEntry {
+    conversation: i64,
+    #[serde(flatten)]
+    flags: ConversationFlags,
+    changed_at: DateTime<Local>,
+}
+
+/// The flags of every conversation, saved to a single JSON file.
+///
+/// Every entry keeps the time it was changed so the latest change wins when
+/// our other clients sync theirs.
+pub struct SavedFlags {
+    path: PathBuf,
+    entries: HashMap<ConversationId, (ConversationFlags, DateTime<Local>)>,
+}
+
+impl SavedFlags {
+    /// Load the flags saved in `path`, a file that cannot be read is kept aside and no flags are set.
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let entries = persist::load::<Vec<SavedEntry>>(&path)
+            .into_iter()
+            .map(|entry| (ConversationId(entry.conversation), (entry.flags, entry.changed_at)))
+            .collect();
+
+        Self { path, entries }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get(&self, conversation: ConversationId) -> ConversationFlags {
+        self.entries
+            .get(&conversation)
+            .map(|(flags, _)| *flags)
+            .unwrap_or_default()
+    }
+
+    /// Set the flags of a conversation unless they were changed later than `at`.
+    ///
+    /// Returns whether the flags changed.
+    pub fn set(&mut self, conversation: ConversationId, flags: ConversationFlags, at: DateTime<Local>) -> bool {
+        if self
+            .entries
+            .get(&conversation)
+            .is_some_and(|(current, changed_at)| *current == flags || *changed_at > at)
+        {
+            return false;
+        }
+
+        self.entries.insert(conversation, (flags, at));
+        self.save();
+        true
+    }
+
+    fn save(&self) {
+        let mut entries = self
+            .entries
+            .iter()
+            .map(|(conversation, (flags, changed_at))| SavedEntry {
+                conversation: conversation.0,
+                flags: *flags,
+                changed_at: *changed_at,
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|entry| entry.conversation);
+        persist::save(&self.path, &entries);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    const CONVERSATION: ConversationId = ConversationId(1);
+
+    fn pinned() -> ConversationFlags {
+        ConversationFlags {
+            pinned: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn flags_load_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("conversations.json");
+        let mut flags = SavedFlags::load(&path);
+        assert!(flags.set(CONVERSATION, pinned(), Local::now()));
+
+        let flags = SavedFlags::load(&path);
+        assert_eq!(flags.get(CONVERSATION), pinned());
+        assert_eq!(flags.get(ConversationId(2)), ConversationFlags::default());
+    }
+
+    #[test]
+    fn an_older_change_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut flags = SavedFlags::load(dir.path().join("conversations.json"));
+        let now = Local::now();
+        assert!(flags.set(CONVERSATION, pinned(), now));
+
+        assert!(!flags.set(CONVERSATION, ConversationFlags::default(), now - Duration::minutes(1)));
+        assert_eq!(flags.get(CONVERSATION), pinned());
+    }
+}
@@ -0,0 +1,120 @@
+use gpui::{AppContext as _, Context, Entity, IntoElement, ParentElement as _, Render, Styled as _, Subscription, Window, prelude::FluentBuilder as _};
+use gpui_component::{ActiveTheme as _, Disableable as _, WindowExt as _, button::{Button, ButtonVariants as _}, checkbox::Checkbox, input::{Input, InputEvent, InputState}, label::Label, scroll::ScrollableElement as _, v_flex};
+
+use crate::chat::{ChatStore, ConversationId, MessageId};
+
+/// Picks the conversations to forward messages to, shown in a side sheet.
+pub struct ForwardView {
+    store: Entity<ChatStore>,
+    from: ConversationId,
+    messages: Vec<MessageId>,
+    search: Entity<InputState>,
+    /// The conversations picked so far, in the order they were picked.
+    targets: Vec<ConversationId>,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl ForwardView {
+    pub fn new(
+        from: ConversationId,
+        messages: Vec<MessageId>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let store = ChatStore::global(cx);
+        let search = cx.new(|cx| InputState::new(window, cx).placeholder("Search contacts"));
+        let _subscriptions = vec![
+            cx.observe(&store, |_, _, cx| cx.notify()),
+            cx.subscribe(&search, |_, _, event: &InputEvent, cx| {
+                if let InputEvent::Change = event {
+                    cx.notify();
+                }
+            }),
+        ];
+
+        Self {
+            store,
+            from,
+            messages,
+            search,
+            targets: vec![],
+            _subscriptions,
+        }
+    }
+
+    fn forward(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if self.targets.is_empty() {
+            return;
+        }
+
+        let from = self.from;
+        let messages = self.messages.clone();
+        let targets = std::mem::take(&mut self.targets);
+        let count = self.store.update(cx, |store, cx| {
+            targets
+                .iter()
+                .map(|to| store.forward_messages(from, &messages, *to, cx))
+                .max()
+                .unwrap_or_default()
+        });
+
+        window.close_sheet(cx);
+        let to = match targets.as_slice() {
+            [to] => self.store.read(cx).title(*to).to_string(),
+            targets => format!("{} conversations", targets.len()),
+        };
+        let text = match count {
+            0 => format!("Nothing to forward to {}", to),
+            1 => format!("Forwarded to {}", to),
+            count => format!("Forwarded {} messages to {}", count, to),
+        };
+        window.push_notification(text, cx);
+    }
+}
+
+impl Render for ForwardView {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let store = self.store.read(cx);
+        let conversations = store.find_conversations(&self.search.read(cx).value());
+
+        v_flex()
+            .size_full()
+            .gap_3()
+            .child(Input::new(&self.search).cleanable(true))
+            .child(
+                v_flex()
+                    .flex_1()
+                    .gap_1()
+                    .overflow_y_scrollbar()
+                    .when(conversations.is_empty(), |this| {
+                        this.child(
+                            Label::new("No matching contacts")
+                                .text_sm()
+                                .text_color(cx.theme().muted_foreground),
+                        )
+                    })
+                    .children(conversations.into_iter().map(|conversation| {
+                        Checkbox::new(("forward-to", conversation.0 as u64))
+                            .label(store.title(conversation))
+                            .checked(self.targets.contains(&conversation))
+                            .on_click(cx.listener(move |this, checked: &bool, _, cx| {
+                                this.targets.retain(|target| *target != conversation);
+                                if *checked {
+                                    this.targets.push(conversation);
+                                }
+                                cx.notify();
+                            }))
+                    })),
+            )
+            .child(
+                Button::new("forward")
+                    .label(match self.targets.len() {
+                        0 | 1 => "Forward".to_string(),
+                        count => format!("Forward to {}", count),
+                    })
+                    .primary()
+                    .disabled(self.targets.is_empty())
+                    .on_click(cx.listener(|this, _, window, cx| this.forward(window, cx))),
+            )
+    }
+}
@@ -1,76 +1,1397 @@
-use std::{path::Path, rc::Rc};
+use std::{collections::HashMap, ops::Range, path::PathBuf, rc::Rc, time::Duration};
 
-use gpui::{AppContext, Axis, Context, Edges, Entity, ImageSource, InteractiveElement as _, IntoElement, ParentElement as _, Pixels, Render, SharedString, StatefulInteractiveElement, Styled as _, Window, div, img, px};
-use gpui_component::{ActiveTheme as _, Icon, IconName, Sizable, StyledExt as _, VirtualListScrollHandle, accordion::Accordion, button::{Button, ButtonVariants}, h_flex, input::{Input, InputEvent, InputState}, resizable::{resizable_panel, v_resizable}, scroll::ScrollableElement, v_flex};
-use wry::cookie::time::format_description::modifier::Padding;
+use gpui::{Action, AnyElement, App, AppContext, ClipboardItem, Context, Corner, Edges, Entity, FocusHandle, Focusable, FontWeight, HighlightStyle, InteractiveElement as _, IntoElement, KeyBinding, ObjectFit, ParentElement as _, Pixels, Render, ScrollStrategy, SharedString, Size, StatefulInteractiveElement, Styled as _, StyledText, StyledImage as _, Subscription, Task, Timer, Window, actions, canvas, combine_highlights, div, img, prelude::FluentBuilder as _, px, rems, size};
+use gpui_component::{ActiveTheme as _, Disableable as _, IconName, Selectable as _, Sizable, StyledExt as _, VirtualListScrollHandle, WindowExt as _, button::{Button, ButtonVariants}, checkbox::Checkbox, h_flex, input::{Input, InputEvent, InputState, Search, SelectAll}, label::Label, menu::{ContextMenuExt as _, DropdownMenu as _, PopupMenu}, popover::Popover, resizable::{resizable_panel, v_resizable}, v_flex, v_virtual_list};
+use serde::{Deserialize, Serialize};
 
+use crate::{
+    context_menu::{KeyboardMenu, OpenInBrowser, ShowContextMenu},
+    forward::ForwardView,
+    chat::{ChatStore, ConversationId, Message, MessageId, NewMessage, UserId},
+    emoji::{EmojiPicked, EmojiPicker, replace_shortcodes},
+    mentions::{MentionCompletionProvider, find_mentions},
+    members::MembersView,
+    notifications::Notifications,
+    popout::PopOuts,
+    search::find_ignore_case,
+    settings::SettingsStore,
+    thread::ThreadView,
+    window_state::WindowState,
+};
 
+const CONTEXT: &str = "HistoryView";
+const FIND_CONTEXT: &str = "HistoryFind";
 
+const ROW_PADDING: Pixels = px(20.);
+const LINE_HEIGHT: Pixels = px(20.);
+const QUOTE_HEIGHT: Pixels = px(36.);
+const IMAGE_HEIGHT: Pixels = px(160.);
+const FOOTER_HEIGHT: Pixels = px(24.);
+const TOMBSTONE_HEIGHT: Pixels = px(32.);
+const EDITOR_HEIGHT: Pixels = px(200.);
+const REACTIONS_HEIGHT: Pixels = px(28.);
+/// The widest a message gets, its text wraps there.
+const MESSAGE_MAX_WIDTH: Pixels = px(600.);
+/// The horizontal padding of a row.
+const ROW_PADDING_X: Pixels = px(10.);
 
-pub struct History{
-    text: SharedString,
-    image: ImageSource,
-    height: Pixels,
-    hint: i32,
+/// How long typing has to pause before the draft is saved.
+const DRAFT_SAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// Offered when hovering a message.
+const QUICK_REACTIONS: [&str; 6] = ["👍", "❤️", "😂", "😮", "😢", "🙏"];
+
+#[derive(Action, Clone, PartialEq)]
+#[action(namespace = history, no_json)]
+pub(crate) struct EditMessage(pub(crate) MessageId);
+
+#[derive(Action, Clone, PartialEq)]
+#[action(namespace = history, no_json)]
+pub(crate) struct DeleteMessage(pub(crate) MessageId);
+
+#[derive(Action, Clone, PartialEq)]
+#[action(namespace = history, no_json)]
+pub(crate) struct RecallMessage(pub(crate) MessageId);
+
+#[derive(Action, Clone, PartialEq)]
+#[action(namespace = history, no_json)]
+pub(crate) struct ReplyToMessage(pub(crate) MessageId);
+
+#[derive(Action, Clone, PartialEq)]
+#[action(namespace = history, no_json)]
+pub(crate) struct OpenThread(pub(crate) MessageId);
+
+#[derive(Action, Clone, PartialEq)]
+#[action(namespace = history, no_json)]
+pub(crate) struct CopyMessage(pub(crate) MessageId);
+
+#[derive(Action, Clone, PartialEq)]
+#[action(namespace = history, no_json)]
+pub(crate) struct ForwardMessage(pub(crate) MessageId);
+
+#[derive(Action, Clone, PartialEq)]
+#[action(namespace = history, no_json)]
+pub(crate) struct SaveImage(pub(crate) MessageId);
+
+/// Open the image of a message in the app the system uses for it.
+#[derive(Action, Clone, PartialEq)]
+#[action(namespace = history, no_json)]
+pub(crate) struct OpenImage(pub(crate) MessageId);
+
+/// Start selecting messages, with this one selected.
+#[derive(Action, Clone, PartialEq)]
+#[action(namespace = history, no_json)]
+pub(crate) struct SelectMessage(pub(crate) MessageId);
+
+/// The key sending the message in a composer, the other one inserts a line break.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SendKey {
+    #[default]
+    Enter,
+    /// Ctrl+Enter, or Cmd+Enter on macOS.
+    SecondaryEnter,
 }
-const ITEM_HEIGHT: Pixels = px(30.);
-const ITEM_HEIGHTS: [gpui::Pixels; 7] = [px(20.), px(30.), px(50.), px(70.), px(110.),px(130.), px(170.)];
-impl History {
-    pub fn new(txt: impl Into<SharedString>, img: impl Into<ImageSource>)->Self{
-        Self { text: txt.into(), image: img.into(), height: ITEM_HEIGHT, hint: 0}
+
+impl SendKey {
+    pub fn global(cx: &App) -> Self {
+        SettingsStore::get(cx).chat.send_key
+    }
+
+    /// Whether an `InputEvent::PressEnter` sends the message.
+    pub fn is_send(&self, secondary: bool) -> bool {
+        secondary == (*self == SendKey::SecondaryEnter)
+    }
+
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            SendKey::Enter => "Enter to send",
+            SendKey::SecondaryEnter if cfg!(target_os = "macos") => "Cmd+Enter to send",
+            SendKey::SecondaryEnter => "Ctrl+Enter to send",
+        }
     }
-    pub fn newWithI(i: i32)->Self{
-        Self { text: format!("Item {}", i).into(), image: Path::new(&format!("G:/research/rustee/agpui/images/{}.png", i as usize%11)).into(), height: ITEM_HEIGHTS[i as usize%7], hint: i}
+}
+
+#[derive(Action, Clone, PartialEq)]
+#[action(namespace = history, no_json)]
+pub(crate) struct SetSendKey(pub(crate) SendKey);
+
+actions!(history, [CancelEdit, CancelSelection, FindNext, FindPrevious, CloseFind]);
+
+pub fn init(cx: &mut App) {
+    cx.on_action(|action: &SetSendKey, cx| {
+        SettingsStore::update(cx, |settings| settings.chat.send_key = action.0);
+    });
+    cx.bind_keys([
+        KeyBinding::new("escape", CancelEdit, Some(CONTEXT)),
+        KeyBinding::new("escape", CancelSelection, Some(CONTEXT)),
+        KeyBinding::new("secondary-f", Search, Some(CONTEXT)),
+        KeyBinding::new("f3", FindNext, Some(CONTEXT)),
+        KeyBinding::new("shift-f3", FindPrevious, Some(CONTEXT)),
+        KeyBinding::new("shift-enter", FindPrevious, Some(FIND_CONTEXT)),
+        KeyBinding::new("escape", CloseFind, Some(FIND_CONTEXT)),
+    ]);
+}
+
+/// The first web link in a message text.
+fn find_link(text: &str) -> Option<&str> {
+    text.split_whitespace()
+        .find(|word| word.starts_with("https://") || word.starts_with("http://"))
+        .map(|word| word.trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '"', '\'']))
+}
+
+/// Text of a multi-line input after Enter was pressed, without the line break the key inserted.
+pub(crate) fn submitted_text(state: &InputState) -> String {
+    let mut text = state.value().to_string();
+    let cursor = state.cursor();
+    if text[..cursor].ends_with('\n') {
+        text.remove(cursor - 1);
     }
+    text
+}
+
+/// The inline editor replacing a message bubble while it is being edited.
+struct MessageEditor {
+    message: MessageId,
+    input: Entity<InputState>,
+    _subscription: Subscription,
+}
+
+/// The find bar searching the text of the messages in the conversation.
+struct FindBar {
+    input: Entity<InputState>,
+    /// Every hit in list order.
+    matches: Vec<(MessageId, Range<usize>)>,
+    /// Index into `matches` of the hit scrolled to.
+    current: Option<usize>,
+    _subscription: Subscription,
 }
 
 pub struct HistoryView {
-    historys: Rc<Vec<History>>,
+    store: Entity<ChatStore>,
+    conversation: ConversationId,
     input: Entity<InputState>,
+    emoji_picker: Entity<EmojiPicker>,
+    editor: Option<MessageEditor>,
+    find: Option<FindBar>,
+    keyboard_menu: Option<KeyboardMenu<MessageId>>,
+    /// The messages picked in multi-select mode, `None` unless selecting.
+    selection: Option<Vec<MessageId>>,
+    /// The message the composer replies to.
+    reply_to: Option<MessageId>,
+    _draft_task: Task<()>,
 
+    /// Indices into the conversation messages of the rows in the list, thread replies are left out.
+    rows: Vec<usize>,
+    item_sizes: Rc<Vec<Size<Pixels>>>,
+    /// The width of the message list as last painted, the text of the messages wraps within it.
+    list_width: Pixels,
+    /// The number of wrapped lines of each message, with the text and width they were measured for.
+    text_lines: HashMap<MessageId, (SharedString, Pixels, usize)>,
+    /// The message jumped to, flashed for a moment.
+    highlighted: Option<MessageId>,
+    /// The message under the mouse, it shows the reaction picker.
+    hovered: Option<MessageId>,
+    scroll_to_bottom: bool,
+    /// A jump requested before the rows were laid out.
+    pending_jump: Option<MessageId>,
+
+    focus_handle: FocusHandle,
     scroll_handle: VirtualListScrollHandle,
+    _highlight_task: Task<()>,
+    _subscriptions: Vec<Subscription>,
 }
 
 impl HistoryView {
     pub fn new(
+        conversation: ConversationId,
         window: &mut Window,
         cx: &mut Context<Self>,
     ) -> Self {
-        let hs = (0..5000).map(|i| History::newWithI(i)).collect::<Vec<_>>();
+        let store = ChatStore::global(cx);
+        store.update(cx, |store, cx| {
+            store.open(conversation);
+            store.mark_read(conversation, cx);
+        });
+        Notifications::log(cx).update(cx, |log, cx| log.mark_read(Some(conversation), cx));
 
+        let draft = store.read(cx).draft(conversation).unwrap_or_default().to_string();
         let input = cx.new(|cx|
             InputState::new(window, cx)
                 .placeholder("Enter your name...")
-                .multi_line(true)
+                .auto_grow(1, 8)
+                .default_value(draft)
             );
-        let _ = cx.subscribe_in(&input, window, |view, state, event, window, cx| {
-            match event {
-                InputEvent::Change => {
-                    let text = state.read(cx).value();
-                    println!("Input changed: {}", text);
-                }
-                InputEvent::PressEnter { secondary } => {
-                    println!("Enter pressed, secondary: {}", secondary);
-                }
-                InputEvent::Focus => println!("Input focused"),
-                InputEvent::Blur => println!("Input blurred"),
-            }
+        input.update(cx, |input, _| {
+            input.lsp.completion_provider = Some(Rc::new(MentionCompletionProvider::new(conversation)));
         });
 
+        let emoji_picker = cx.new(|cx| EmojiPicker::new(window, cx));
+
+        let _subscriptions = vec![
+            cx.observe(&store, |this, store, cx| {
+                let conversation = this.conversation;
+                store.update(cx, |store, cx| store.mark_read(conversation, cx));
+                this.update_find_matches(cx);
+                cx.notify();
+            }),
+            cx.subscribe_in(&emoji_picker, window, |this, _, EmojiPicked(emoji), window, cx| {
+                this.input.update(cx, |input, cx| input.insert(*emoji, window, cx));
+            }),
+            cx.subscribe_in(&input, window, |this, state, event, window, cx| match event {
+                InputEvent::Change => this.schedule_draft_save(cx),
+                InputEvent::PressEnter { secondary } if SendKey::global(cx).is_send(*secondary) => {
+                    let text = submitted_text(state.read(cx));
+                    this.send(text, window, cx);
+                }
+                _ => {}
+            }),
+            // Switching conversations drops the view, the draft must not wait for the timer.
+            cx.on_release(|this, cx| this.save_draft(cx)),
+            // Nor when quitting, the view may outlive the app.
+            cx.on_app_quit(|this, cx| {
+                this.save_draft(cx);
+                async {}
+            }),
+        ];
+
         Self {
-            historys: hs.into(),
+            store,
+            conversation,
             input: input,
+            emoji_picker,
+            editor: None,
+            find: None,
+            keyboard_menu: None,
+            selection: None,
+            reply_to: None,
+            _draft_task: Task::ready(()),
+            rows: vec![],
+            item_sizes: Rc::new(vec![]),
+            list_width: px(0.),
+            text_lines: HashMap::new(),
+            highlighted: None,
+            hovered: None,
+            scroll_to_bottom: true,
+            pending_jump: None,
+            focus_handle: cx.focus_handle(),
             scroll_handle: VirtualListScrollHandle::new(),
+            _highlight_task: Task::ready(()),
+            _subscriptions,
+        }
+    }
+
+    pub fn conversation(&self) -> ConversationId {
+        self.conversation
+    }
+
+    pub fn focus_composer(&self, window: &mut Window, cx: &mut App) {
+        self.input.update(cx, |input, cx| input.focus(window, cx));
+    }
+
+    /// Rebuild the rows of the list and their heights from the store.
+    fn layout_rows(&mut self, window: &Window, cx: &App) {
+        let store = self.store.read(cx);
+        let messages = store.messages(self.conversation);
+
+        self.rows = messages
+            .iter()
+            .enumerate()
+            .filter(|(_, message)| message.thread.is_none())
+            .map(|(ix, _)| ix)
+            .collect();
+        let wrap_width = (self.list_width > px(0.))
+            .then(|| (self.list_width - ROW_PADDING_X * 2.).min(MESSAGE_MAX_WIDTH));
+        let mut text_lines = std::mem::take(&mut self.text_lines);
+        self.item_sizes = Rc::new(
+            self.rows
+                .iter()
+                .map(|ix| {
+                    let message = &messages[*ix];
+                    let editing = self.editor.as_ref().is_some_and(|editor| editor.message == message.id);
+                    let replies = store.reply_count(self.conversation, message.id);
+                    let lines = Self::text_lines(&mut text_lines, message, wrap_width, window);
+                    size(px(0.), Self::row_height(message, lines, replies, editing))
+                })
+                .collect(),
+        );
+        self.text_lines = text_lines;
+    }
+
+    /// The number of lines the text of a message wraps into, measured again only when
+    /// the text or the width changed.
+    fn text_lines(
+        cache: &mut HashMap<MessageId, (SharedString, Pixels, usize)>,
+        message: &Message,
+        wrap_width: Option<Pixels>,
+        window: &Window,
+    ) -> usize {
+        let width = wrap_width.unwrap_or_default();
+        if let Some((text, measured_width, lines)) = cache.get(&message.id)
+            && *text == message.text
+            && *measured_width == width
+        {
+            return *lines;
+        }
+
+        let style = window.text_style();
+        let font_size = rems(0.875).to_pixels(window.rem_size());
+        let runs = [style.to_run(message.text.len())];
+        let lines = window
+            .text_system()
+            .shape_text(message.text.clone(), font_size, &runs, wrap_width, None)
+            .map(|lines| lines.iter().map(|line| line.wrap_boundaries().len() + 1).sum())
+            .unwrap_or_else(|_| message.text.lines().count())
+            .max(1);
+        cache.insert(message.id, (message.text.clone(), width, lines));
+        lines
+    }
+
+    /// The list is virtualized, so every row has a fixed height computed from its content.
+    fn row_height(message: &Message, lines: usize, replies: usize, editing: bool) -> Pixels {
+        if message.is_recalled() || message.is_system() {
+            return TOMBSTONE_HEIGHT;
+        }
+        if editing {
+            return ROW_PADDING + EDITOR_HEIGHT;
+        }
+
+        let mut height = ROW_PADDING + LINE_HEIGHT * lines as f32;
+        if message.reply_to.is_some() {
+            height += QUOTE_HEIGHT;
+        }
+        if message.attachment.is_some() {
+            height += IMAGE_HEIGHT;
+        }
+        if !message.reactions.is_empty() {
+            height += REACTIONS_HEIGHT;
+        }
+        if message.is_edited() || replies > 0 {
+            height += FOOTER_HEIGHT;
         }
+        height
+    }
+
+    /// Scroll the list to a message and flash it.
+    ///
+    /// Thread replies are not listed, so their root message is shown instead.
+    pub fn jump_to_message(&mut self, id: MessageId, window: &mut Window, cx: &mut Context<Self>) {
+        if self.rows.is_empty() {
+            self.pending_jump = Some(id);
+            cx.notify();
+            return;
+        }
+
+        let id = self
+            .store
+            .read(cx)
+            .message(self.conversation, id)
+            .and_then(|message| message.thread)
+            .unwrap_or(id);
+        if !self.scroll_to_message(id, cx) {
+            return;
+        }
+
+        self.highlighted = Some(id);
+        self._highlight_task = cx.spawn_in(window, async move |this, window| {
+            Timer::after(Duration::from_millis(1500)).await;
+            _ = this.update_in(window, |this, _, cx| {
+                this.highlighted = None;
+                cx.notify();
+            });
+        });
+        cx.notify();
+    }
+
+    /// Scroll the list so a message is in the middle, it does not need to be rendered yet.
+    fn scroll_to_message(&mut self, id: MessageId, cx: &App) -> bool {
+        let messages = self.store.read(cx).messages(self.conversation);
+        let Some(row) = self.rows.iter().position(|ix| messages[*ix].id == id) else {
+            return false;
+        };
+        self.scroll_handle.scroll_to_item(row, ScrollStrategy::Center);
+        true
+    }
+
+    fn on_find(&mut self, _: &Search, window: &mut Window, cx: &mut Context<Self>) {
+        // The inputs of the view do not search themselves, so Ctrl+F in them opens the find bar too.
+        cx.stop_propagation();
+        if let Some(find) = self.find.as_ref() {
+            find.input.update(cx, |input, cx| input.focus(window, cx));
+            window.dispatch_action(Box::new(SelectAll), cx);
+            return;
+        }
+
+        let input = cx.new(|cx| InputState::new(window, cx).placeholder("Find in conversation"));
+        let _subscription = cx.subscribe_in(&input, window, |this, _, event, _, cx| match event {
+            InputEvent::Change => {
+                this.update_find_matches(cx);
+                let last = this.find.as_ref().and_then(|find| find.matches.len().checked_sub(1));
+                this.select_match(last, cx);
+            }
+            InputEvent::PressEnter { .. } => this.step_match(1, cx),
+            _ => {}
+        });
+        input.update(cx, |input, cx| input.focus(window, cx));
+        self.find = Some(FindBar {
+            input,
+            matches: vec![],
+            current: None,
+            _subscription,
+        });
+        cx.notify();
+    }
+
+    fn close_find(&mut self, _: &CloseFind, window: &mut Window, cx: &mut Context<Self>) {
+        if self.find.take().is_none() {
+            cx.propagate();
+            return;
+        }
+        window.focus(&self.focus_handle);
+        cx.notify();
+    }
+
+    fn find_next(&mut self, _: &FindNext, _: &mut Window, cx: &mut Context<Self>) {
+        self.step_match(1, cx);
+    }
+
+    fn find_previous(&mut self, _: &FindPrevious, _: &mut Window, cx: &mut Context<Self>) {
+        self.step_match(-1, cx);
+    }
+
+    /// Search the listed messages again, keeping the current hit when it is still there.
+    fn update_find_matches(&mut self, cx: &App) {
+        let Some(find) = self.find.as_mut() else {
+            return;
+        };
+        let query = find.input.read(cx).value().trim().to_string();
+        let current = find
+            .current
+            .and_then(|ix| find.matches.get(ix))
+            .cloned();
+
+        find.matches = if query.is_empty() {
+            vec![]
+        } else {
+            self.store
+                .read(cx)
+                .messages(self.conversation)
+                .iter()
+                .filter(|message| message.thread.is_none() && !message.is_recalled() && !message.is_system())
+                .flat_map(|message| {
+                    find_ignore_case(&message.text, &query)
+                        .into_iter()
+                        .map(|range| (message.id, range))
+                })
+                .collect()
+        };
+        find.current = current
+            .and_then(|current| find.matches.iter().position(|m| *m == current))
+            .or_else(|| find.matches.len().checked_sub(1));
+    }
+
+    fn select_match(&mut self, ix: Option<usize>, cx: &mut Context<Self>) {
+        let Some(find) = self.find.as_mut() else {
+            return;
+        };
+        find.current = ix;
+        if let Some((id, _)) = ix.and_then(|ix| find.matches.get(ix)) {
+            let id = *id;
+            self.scroll_to_message(id, cx);
+        }
+        cx.notify();
+    }
+
+    /// Move to the next hit below (`1`) or above (`-1`), wrapping around.
+    fn step_match(&mut self, step: isize, cx: &mut Context<Self>) {
+        let Some(find) = self.find.as_ref() else {
+            return;
+        };
+        let len = find.matches.len() as isize;
+        if len == 0 {
+            return;
+        }
+        let ix = match find.current {
+            Some(current) => (current as isize + step).rem_euclid(len),
+            None if step > 0 => 0,
+            None => len - 1,
+        };
+        self.select_match(Some(ix as usize), cx);
+    }
+
+    fn render_find_bar(&self, find: &FindBar, cx: &Context<Self>) -> impl IntoElement {
+        let status = match (find.current, find.matches.len()) {
+            (_, 0) if find.input.read(cx).value().trim().is_empty() => String::new(),
+            (_, 0) => "No results".to_string(),
+            (Some(current), len) => format!("{} of {}", current + 1, len),
+            (None, len) => format!("{} results", len),
+        };
+
+        h_flex()
+            .key_context(FIND_CONTEXT)
+            .px_4()
+            .py_2()
+            .gap_2()
+            .border_b_1()
+            .border_color(cx.theme().border)
+            .child(div().flex_1().child(Input::new(&find.input).small()))
+            .child(
+                Label::new(status)
+                    .text_xs()
+                    .text_color(cx.theme().muted_foreground),
+            )
+            .child(
+                Button::new("find-previous")
+                    .icon(IconName::ChevronUp)
+                    .xsmall()
+                    .ghost()
+                    .on_click(cx.listener(|this, _, _, cx| this.step_match(-1, cx))),
+            )
+            .child(
+                Button::new("find-next")
+                    .icon(IconName::ChevronDown)
+                    .xsmall()
+                    .ghost()
+                    .on_click(cx.listener(|this, _, _, cx| this.step_match(1, cx))),
+            )
+            .child(
+                Button::new("find-close")
+                    .icon(IconName::Close)
+                    .xsmall()
+                    .ghost()
+                    .on_click(cx.listener(|this, _, window, cx| this.close_find(&CloseFind, window, cx))),
+            )
+    }
+
+    fn save_draft(&mut self, cx: &mut App) {
+        self._draft_task = Task::ready(());
+        let conversation = self.conversation;
+        let text = self.input.read(cx).value().to_string();
+        let text = if text.trim().is_empty() { "" } else { text.as_str() };
+        self.store.update(cx, |store, cx| store.set_draft(conversation, text, cx));
+    }
+
+    fn schedule_draft_save(&mut self, cx: &mut Context<Self>) {
+        self._draft_task = cx.spawn(async move |this, cx| {
+            Timer::after(DRAFT_SAVE_DELAY).await;
+            _ = this.update(cx, |this, cx| this.save_draft(cx));
+        });
+    }
+
+    fn send(&mut self, text: String, window: &mut Window, cx: &mut Context<Self>) {
+        let text = replace_shortcodes(text.trim());
+        if text.is_empty() {
+            return;
+        }
+
+        let message = NewMessage {
+            text: text.into(),
+            reply_to: self.reply_to,
+            thread: None,
+            mentions: vec![],
+        };
+        let conversation = self.conversation;
+        let sent = self.store.update(cx, |store, cx| store.send_message(conversation, message, cx));
+        // Keep what was written so it is not lost when the message cannot be sent.
+        if sent.is_none() {
+            window.push_notification("Could not send the message.", cx);
+            return;
+        }
+        self.reply_to = None;
+        self.input.update(cx, |input, cx| input.set_value("", window, cx));
+        self.save_draft(cx);
+        self.scroll_to_bottom = true;
+        cx.notify();
+    }
+
+    fn toggle_reaction(&mut self, id: MessageId, emoji: &'static str, cx: &mut Context<Self>) {
+        let conversation = self.conversation;
+        self.store.update(cx, |store, cx| store.toggle_reaction(conversation, id, emoji, cx));
+    }
+
+    fn on_reply_to_message(&mut self, action: &ReplyToMessage, window: &mut Window, cx: &mut Context<Self>) {
+        self.reply_to = Some(action.0);
+        self.input.update(cx, |input, cx| input.focus(window, cx));
+        cx.notify();
+    }
+
+    fn on_copy_message(&mut self, action: &CopyMessage, _: &mut Window, cx: &mut Context<Self>) {
+        let Some(message) = self.store.read(cx).message(self.conversation, action.0) else {
+            return;
+        };
+        cx.write_to_clipboard(ClipboardItem::new_string(message.text.to_string()));
+    }
+
+    fn on_forward_message(&mut self, action: &ForwardMessage, window: &mut Window, cx: &mut Context<Self>) {
+        self.open_forward(vec![action.0], window, cx);
+    }
+
+    fn open_forward(&mut self, messages: Vec<MessageId>, window: &mut Window, cx: &mut Context<Self>) {
+        let conversation = self.conversation;
+        let forward = cx.new(|cx| ForwardView::new(conversation, messages, window, cx));
+        window.open_sheet(cx, move |sheet, _, _| {
+            sheet
+                .title("Forward to")
+                .size(px(360.))
+                .child(forward.clone())
+        });
+    }
+
+    /// Download the image of a message into the cache, returns its file.
+    fn download_image(&mut self, id: MessageId, cx: &mut Context<Self>) -> Option<PathBuf> {
+        let conversation = self.conversation;
+        self.store.update(cx, |store, _| store.download_attachment(conversation, id))
+    }
+
+    fn on_open_image(&mut self, action: &OpenImage, _: &mut Window, cx: &mut Context<Self>) {
+        if let Some(path) = self.download_image(action.0, cx) {
+            cx.open_with_system(&path);
+        }
+    }
+
+    fn on_save_image(&mut self, action: &SaveImage, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(path) = self.download_image(action.0, cx) else {
+            return;
+        };
+
+        let directory = SettingsStore::get(cx).storage.download_dir();
+        let name = path.file_name().and_then(|name| name.to_str()).map(|name| name.to_string());
+        let target = cx.prompt_for_new_path(&directory, name.as_deref());
+        cx.spawn_in(window, async move |_, cx| {
+            let Ok(Ok(Some(target))) = target.await else {
+                return;
+            };
+            if let Err(err) = std::fs::copy(&path, &target) {
+                _ = cx.update(|window, cx| {
+                    window.push_notification(format!("Could not save the image: {}", err), cx);
+                });
+            }
+        })
+        .detach();
+    }
+
+    fn on_select_message(&mut self, action: &SelectMessage, _: &mut Window, cx: &mut Context<Self>) {
+        let selection = self.selection.get_or_insert_default();
+        if !selection.contains(&action.0) {
+            selection.push(action.0);
+        }
+        self.editor = None;
+        cx.notify();
+    }
+
+    fn cancel_selection(&mut self, _: &CancelSelection, _: &mut Window, cx: &mut Context<Self>) {
+        if self.selection.take().is_none() {
+            cx.propagate();
+            return;
+        }
+        cx.notify();
+    }
+
+    fn toggle_selected(&mut self, id: MessageId, selected: bool, cx: &mut Context<Self>) {
+        let Some(selection) = self.selection.as_mut() else {
+            return;
+        };
+        selection.retain(|selected| *selected != id);
+        if selected {
+            selection.push(id);
+        }
+        cx.notify();
+    }
+
+    /// The selected messages in the order of the conversation, not the order they were picked.
+    fn selected_messages<'a>(&self, cx: &'a App) -> Vec<&'a Message> {
+        let selection = self.selection.as_deref().unwrap_or_default();
+        self.store
+            .read(cx)
+            .messages(self.conversation)
+            .iter()
+            .filter(|message| selection.contains(&message.id))
+            .collect()
+    }
+
+    fn copy_selected(&mut self, cx: &mut Context<Self>) {
+        let store = self.store.read(cx);
+        let text = self
+            .selected_messages(cx)
+            .into_iter()
+            .filter(|message| !message.is_recalled())
+            .map(|message| format!("{}: {}", store.name(message.sender), message.text))
+            .collect::<Vec<_>>()
+            .join("\n");
+        cx.write_to_clipboard(ClipboardItem::new_string(text));
+        self.selection = None;
+        cx.notify();
+    }
+
+    fn forward_selected(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let messages = self
+            .selected_messages(cx)
+            .into_iter()
+            .map(|message| message.id)
+            .collect::<Vec<_>>();
+        self.selection = None;
+        self.open_forward(messages, window, cx);
+        cx.notify();
+    }
+
+    fn delete_selected(&mut self, cx: &mut Context<Self>) {
+        let conversation = self.conversation;
+        let messages = self.selection.take().unwrap_or_default();
+        self.store.update(cx, |store, cx| {
+            for id in messages {
+                store.delete_message(conversation, id, cx);
+            }
+        });
+        cx.notify();
+    }
+
+    fn render_selection_bar(&self, selected: usize, cx: &Context<Self>) -> impl IntoElement {
+        h_flex()
+            .px_4()
+            .py_2()
+            .gap_2()
+            .border_b_1()
+            .border_color(cx.theme().border)
+            .child(
+                Label::new(format!("{} selected", selected))
+                    .flex_1()
+                    .text_sm(),
+            )
+            .child(
+                Button::new("copy-selected")
+                    .label("Copy")
+                    .xsmall()
+                    .outline()
+                    .disabled(selected == 0)
+                    .on_click(cx.listener(|this, _, _, cx| this.copy_selected(cx))),
+            )
+            .child(
+                Button::new("forward-selected")
+                    .label("Forward")
+                    .xsmall()
+                    .outline()
+                    .disabled(selected == 0)
+                    .on_click(cx.listener(|this, _, window, cx| this.forward_selected(window, cx))),
+            )
+            .child(
+                Button::new("delete-selected")
+                    .label("Delete for Me")
+                    .xsmall()
+                    .danger()
+                    .disabled(selected == 0)
+                    .on_click(cx.listener(|this, _, _, cx| this.delete_selected(cx))),
+            )
+            .child(
+                Button::new("cancel-selection")
+                    .icon(IconName::Close)
+                    .xsmall()
+                    .ghost()
+                    .on_click(cx.listener(|this, _, window, cx| this.cancel_selection(&CancelSelection, window, cx))),
+            )
+    }
+
+    /// The menu key opens the menu of the message under the mouse, or of the newest one.
+    fn on_show_context_menu(&mut self, _: &ShowContextMenu, window: &mut Window, cx: &mut Context<Self>) {
+        let store = self.store.read(cx);
+        let messages = store.messages(self.conversation);
+        let Some(id) = self.hovered.or_else(|| {
+            self.rows
+                .iter()
+                .rev()
+                .map(|ix| &messages[*ix])
+                .find(|message| !message.is_recalled() && !message.is_system())
+                .map(|message| message.id)
+        }) else {
+            return;
+        };
+
+        self.scroll_to_message(id, cx);
+        let conversation = self.conversation;
+        let focus_handle = self.focus_handle.clone();
+        self.keyboard_menu = Some(KeyboardMenu::new(
+            id,
+            window,
+            cx,
+            |this| &mut this.keyboard_menu,
+            move |menu, _, cx| Self::message_menu(menu, conversation, id, focus_handle, cx),
+        ));
+        cx.notify();
+    }
+
+    /// The context menu of a message, items that do not apply to it are disabled.
+    fn message_menu(
+        menu: PopupMenu,
+        conversation: ConversationId,
+        id: MessageId,
+        focus_handle: FocusHandle,
+        cx: &App,
+    ) -> PopupMenu {
+        let store = ChatStore::global(cx).read(cx);
+        let Some(message) = store.message(conversation, id) else {
+            return menu;
+        };
+        let outgoing = message.sender == store.me();
+        let can_send = !store.is_group(conversation) || store.is_member(conversation, store.me());
+        let image = message
+            .attachment
+            .as_ref()
+            .and_then(|attachment| attachment.local_path(store.attachments()));
+        let url = find_link(&message.text).map(|link| link.to_string());
+
+        let menu = menu
+            .action_context(focus_handle)
+            .menu("Copy", Box::new(CopyMessage(id)))
+            .menu_with_disabled("Reply", Box::new(ReplyToMessage(id)), !can_send)
+            .menu_with_disabled("Reply in Thread", Box::new(OpenThread(id)), !can_send)
+            .menu("Forward…", Box::new(ForwardMessage(id)))
+            .menu("Select", Box::new(SelectMessage(id)))
+            .separator()
+            .menu_with_disabled("Open Image", Box::new(OpenImage(id)), image.is_none())
+            .menu_with_disabled("Save Image…", Box::new(SaveImage(id)), image.is_none())
+            .menu_with_disabled(
+                "Open in Browser",
+                Box::new(OpenInBrowser(url.clone().unwrap_or_default().into())),
+                url.is_none(),
+            )
+            .separator();
+        let menu = if outgoing {
+            menu.menu("Edit", Box::new(EditMessage(id)))
+                .menu_with_disabled("Recall", Box::new(RecallMessage(id)), !store.can_recall(message))
+        } else {
+            menu
+        };
+        menu.menu("Delete for Me", Box::new(DeleteMessage(id)))
+    }
+
+    fn on_open_thread(&mut self, action: &OpenThread, window: &mut Window, cx: &mut Context<Self>) {
+        let thread = cx.new(|cx| ThreadView::new(self.conversation, action.0, window, cx));
+        window.open_sheet(cx, move |sheet, _, _| {
+            sheet
+                .title("Thread")
+                .size(px(420.))
+                .child(thread.clone())
+        });
+    }
+
+    fn show_members(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let conversation = self.conversation;
+        let members = cx.new(|cx| MembersView::new(conversation, cx));
+        window.open_sheet(cx, move |sheet, _, _| {
+            sheet
+                .title("Members")
+                .size(px(420.))
+                .child(members.clone())
+        });
+    }
+
+    fn on_edit_message(&mut self, action: &EditMessage, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(message) = self.store.read(cx).message(self.conversation, action.0) else {
+            return;
+        };
+        let text = message.text.clone();
+
+        let input = cx.new(|cx| {
+            InputState::new(window, cx)
+                .auto_grow(1, 6)
+                .default_value(text)
+        });
+        let conversation = self.conversation;
+        input.update(cx, |input, _| {
+            input.lsp.completion_provider = Some(Rc::new(MentionCompletionProvider::new(conversation)));
+        });
+        let _subscription = cx.subscribe_in(&input, window, |this, state, event, window, cx| {
+            if let InputEvent::PressEnter { secondary } = event
+                && SendKey::global(cx).is_send(*secondary)
+            {
+                let text = submitted_text(state.read(cx));
+                this.save_edit(text, window, cx);
+            }
+        });
+        input.update(cx, |input, cx| input.focus(window, cx));
+
+        self.editor = Some(MessageEditor {
+            message: action.0,
+            input,
+            _subscription,
+        });
+        cx.notify();
+    }
+
+    fn on_delete_message(&mut self, action: &DeleteMessage, _: &mut Window, cx: &mut Context<Self>) {
+        let conversation = self.conversation;
+        self.store.update(cx, |store, cx| store.delete_message(conversation, action.0, cx));
+    }
+
+    fn on_recall_message(&mut self, action: &RecallMessage, _: &mut Window, cx: &mut Context<Self>) {
+        let conversation = self.conversation;
+        self.store.update(cx, |store, cx| store.recall_message(conversation, action.0, cx));
+    }
+
+    fn save_edit(&mut self, text: String, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(editor) = self.editor.take() else {
+            return;
+        };
+        let conversation = self.conversation;
+        self.store.update(cx, |store, cx| {
+            store.edit_message(conversation, editor.message, text.trim_end().to_string(), cx)
+        });
+        window.focus(&self.focus_handle);
+        cx.notify();
+    }
+
+    fn cancel_edit(&mut self, _: &CancelEdit, window: &mut Window, cx: &mut Context<Self>) {
+        if self.editor.take().is_none() {
+            cx.propagate();
+            return;
+        }
+        window.focus(&self.focus_handle);
+        cx.notify();
+    }
+
+    fn render_editor(&self, editor: &MessageEditor, cx: &Context<Self>) -> impl IntoElement {
+        v_flex()
+            .w(px(400.))
+            .gap_1()
+            .child(Input::new(&editor.input))
+            .child(
+                h_flex()
+                    .gap_1()
+                    .justify_end()
+                    .child(
+                        Button::new("cancel-edit")
+                            .label("Cancel")
+                            .small()
+                            .ghost()
+                            .on_click(cx.listener(|this, _, window, cx| {
+                                this.cancel_edit(&CancelEdit, window, cx)
+                            })),
+                    )
+                    .child(
+                        Button::new("save-edit")
+                            .label("Save")
+                            .small()
+                            .primary()
+                            .on_click(cx.listener(|this, _, window, cx| {
+                                let Some(editor) = this.editor.as_ref() else {
+                                    return;
+                                };
+                                let text = editor.input.read(cx).value().to_string();
+                                this.save_edit(text, window, cx);
+                            })),
+                    ),
+            )
+    }
+
+    /// The "edited" marker, clicking it lists the earlier versions of the message.
+    fn render_edited_marker(message: &Message) -> impl IntoElement {
+        let revisions = message.revisions.clone();
+
+        Popover::new(("edited", message.id.0))
+            .anchor(gpui::Corner::TopRight)
+            .trigger(
+                Button::new(("edited-trigger", message.id.0))
+                    .label("edited")
+                    .xsmall()
+                    .ghost(),
+            )
+            .content(move |_, _, cx| {
+                v_flex()
+                    .gap_2()
+                    .max_w(px(360.))
+                    .children(revisions.iter().rev().map(|revision| {
+                        v_flex()
+                            .child(
+                                Label::new(revision.at.format("%Y-%m-%d %H:%M").to_string())
+                                    .text_xs()
+                                    .text_color(cx.theme().muted_foreground),
+                            )
+                            .child(revision.text.clone())
+                    }))
+            })
+    }
+
+    /// The quoted snippet above a reply, clicking it jumps to the original message.
+    fn render_quote(&self, message: &Message, reply_to: MessageId, cx: &Context<Self>) -> impl IntoElement {
+        let theme = cx.theme();
+        let snippet = self
+            .store
+            .read(cx)
+            .message(self.conversation, reply_to)
+            .map(|quoted| quoted.snippet())
+            .unwrap_or_else(|| "Message deleted".into());
+
+        div()
+            .id(("quote", message.id.0))
+            .h(QUOTE_HEIGHT - px(4.))
+            .mb_1()
+            .px_2()
+            .flex()
+            .items_center()
+            .border_l_2()
+            .border_color(theme.primary)
+            .bg(theme.muted)
+            .rounded(theme.radius)
+            .text_xs()
+            .text_color(theme.muted_foreground)
+            .cursor_pointer()
+            .child(snippet)
+            .on_click(cx.listener(move |this, _, window, cx| {
+                this.jump_to_message(reply_to, window, cx)
+            }))
+    }
+
+    /// Quick reactions floating over the hovered message, on the side away from the bubble.
+    fn render_reaction_picker(id: MessageId, outgoing: bool, cx: &Context<Self>) -> impl IntoElement {
+        let theme = cx.theme();
+
+        h_flex()
+            .absolute()
+            .top_1()
+            .when(outgoing, |this| this.left_2())
+            .when(!outgoing, |this| this.right_2())
+            .p_0p5()
+            .gap_0p5()
+            .bg(theme.popover)
+            .border_1()
+            .border_color(theme.border)
+            .rounded(theme.radius)
+            .shadow_md()
+            .children(QUICK_REACTIONS.iter().enumerate().map(|(ix, emoji)| {
+                Button::new(("quick-reaction", ix))
+                    .label(*emoji)
+                    .xsmall()
+                    .ghost()
+                    .on_click(cx.listener(move |this, _, _, cx| this.toggle_reaction(id, emoji, cx)))
+            }))
+    }
+
+    /// One chip per emoji with its count, our own reactions are highlighted.
+    fn render_reactions(message: &Message, me: UserId, cx: &Context<Self>) -> impl IntoElement {
+        let id = message.id;
+
+        h_flex()
+            .h(REACTIONS_HEIGHT)
+            .gap_1()
+            .children(message.reactions.iter().map(|reaction| {
+                let emoji = reaction.emoji.clone();
+                Button::new((SharedString::from(format!("reaction-{}", emoji)), id.0 as usize))
+                    .label(format!("{} {}", emoji, reaction.users.len()))
+                    .xsmall()
+                    .outline()
+                    .selected(reaction.users.contains(&me))
+                    .on_click(cx.listener(move |this, _, _, cx| {
+                        let conversation = this.conversation;
+                        let emoji = emoji.clone();
+                        this.store.update(cx, |store, cx| {
+                            store.toggle_reaction(conversation, id, emoji, cx)
+                        });
+                    }))
+            }))
+    }
+
+    /// The system line left in place of a recalled message.
+    fn render_tombstone(message: &Message, outgoing: bool, cx: &Context<Self>) -> impl IntoElement {
+        h_flex()
+            .id(("message", message.id.0))
+            .h(TOMBSTONE_HEIGHT)
+            .justify_center()
+            .text_xs()
+            .text_color(cx.theme().muted_foreground)
+            .child(if outgoing {
+                "You recalled a message"
+            } else {
+                "Message recalled"
+            })
+    }
+
+    /// A membership change, centered like a tombstone.
+    fn render_system_message(message: &Message, cx: &Context<Self>) -> impl IntoElement {
+        h_flex()
+            .id(("message", message.id.0))
+            .h(TOMBSTONE_HEIGHT)
+            .justify_center()
+            .text_xs()
+            .text_color(cx.theme().muted_foreground)
+            .child(message.text.clone())
+    }
+
+    /// The message text with the mentions and find hits highlighted, mentions of us stand out more.
+    fn render_text(&self, message: &Message, me: UserId, cx: &Context<Self>) -> impl IntoElement {
+        let find_highlights = self
+            .find
+            .iter()
+            .flat_map(|find| {
+                find.matches
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (id, _))| *id == message.id)
+                    .map(|(ix, (_, range))| {
+                        let color = if find.current == Some(ix) {
+                            cx.theme().warning
+                        } else {
+                            cx.theme().warning.opacity(0.3)
+                        };
+                        let style = HighlightStyle {
+                            background_color: Some(color),
+                            ..Default::default()
+                        };
+                        (range.clone(), style)
+                    })
+            })
+            .collect::<Vec<_>>();
+        if message.mentions.is_empty() {
+            return StyledText::new(message.text.clone()).with_highlights(find_highlights);
+        }
+
+        let store = self.store.read(cx);
+        let members = message
+            .mentions
+            .iter()
+            .map(|id| (*id, store.name(*id)))
+            .collect::<Vec<_>>();
+        let highlights = find_mentions(&message.text, &members)
+            .into_iter()
+            .map(|(range, user)| {
+                let style = HighlightStyle {
+                    color: Some(cx.theme().link),
+                    font_weight: Some(FontWeight::SEMIBOLD),
+                    background_color: (user == me).then(|| cx.theme().warning.opacity(0.3)),
+                    ..Default::default()
+                };
+                (range, style)
+            })
+            .collect::<Vec<_>>();
+        StyledText::new(message.text.clone())
+            .with_highlights(combine_highlights(highlights, find_highlights).collect::<Vec<_>>())
+    }
+
+    fn render_message(
+        &self,
+        message: &Message,
+        outgoing: bool,
+        replies: usize,
+        height: Pixels,
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        if message.is_recalled() {
+            return Self::render_tombstone(message, outgoing, cx).into_any_element();
+        }
+        if message.is_system() {
+            return Self::render_system_message(message, cx).into_any_element();
+        }
+
+        let store = self.store.read(cx);
+        let me = store.me();
+        let editor = self
+            .editor
+            .as_ref()
+            .filter(|editor| editor.message == message.id);
+        let focus_handle = self.focus_handle.clone();
+        let conversation = self.conversation;
+        let id = message.id;
+
+        div()
+            .id(("message", message.id.0))
+            .h(height)
+            .py_2()
+            .px(ROW_PADDING_X)
+            .flex()
+            .when(outgoing, |this| this.justify_end())
+            .relative()
+            .when(self.highlighted == Some(id), |this| this.bg(cx.theme().accent))
+            .on_hover(cx.listener(move |this, hovered: &bool, _, cx| {
+                if *hovered {
+                    this.hovered = Some(id);
+                } else if this.hovered == Some(id) {
+                    this.hovered = None;
+                }
+                cx.notify();
+            }))
+            .text_sm()
+            .when_some(self.selection.as_ref(), |this, selection| {
+                this.child(
+                    Checkbox::new(("select", id.0))
+                        .checked(selection.contains(&id))
+                        .mr_2()
+                        .on_click(cx.listener(move |this, checked: &bool, _, cx| {
+                            this.toggle_selected(id, *checked, cx)
+                        })),
+                )
+                // Keeps the checkbox on the left while our bubbles stay on the right.
+                .when(outgoing, |this| this.child(div().flex_1()))
+            })
+            .map(|this| match editor {
+                Some(editor) => this.child(self.render_editor(editor, cx)),
+                None => this.child(
+                    v_flex()
+                        .max_w(MESSAGE_MAX_WIDTH)
+                        .when_some(message.reply_to, |this, reply_to| {
+                            this.child(self.render_quote(message, reply_to, cx))
+                        })
+                        .child(self.render_text(message, me, cx))
+                        .children(message.attachment.as_ref().map(|attachment| {
+                            img(attachment.source.clone())
+                                .h(IMAGE_HEIGHT)
+                                .max_w(MESSAGE_MAX_WIDTH)
+                                .object_fit(ObjectFit::Contain)
+                        }))
+                        .when(!message.reactions.is_empty(), |this| {
+                            this.child(Self::render_reactions(message, me, cx))
+                        })
+                        .when(message.is_edited() || replies > 0, |this| {
+                            this.child(
+                                h_flex()
+                                    .h(FOOTER_HEIGHT)
+                                    .gap_1()
+                                    .when(replies > 0, |this| {
+                                        this.child(
+                                            Button::new(("thread", id.0))
+                                                .label(if replies == 1 {
+                                                    "1 reply".to_string()
+                                                } else {
+                                                    format!("{} replies", replies)
+                                                })
+                                                .xsmall()
+                                                .link()
+                                                .on_click(cx.listener(move |this, _, window, cx| {
+                                                    this.on_open_thread(&OpenThread(id), window, cx)
+                                                })),
+                                        )
+                                    })
+                                    .when(message.is_edited(), |this| {
+                                        this.child(Self::render_edited_marker(message))
+                                    }),
+                            )
+                        }),
+                ),
+            })
+            .when(self.hovered == Some(id) && editor.is_none(), |this| {
+                this.child(Self::render_reaction_picker(id, outgoing, cx))
+            })
+            .children(
+                self.keyboard_menu
+                    .as_ref()
+                    .filter(|menu| *menu.target() == id)
+                    .map(|menu| menu.render()),
+            )
+            .context_menu(move |menu, _, cx| {
+                Self::message_menu(menu, conversation, id, focus_handle.clone(), cx)
+            })
+            .into_any_element()
+    }
+
+    fn render_rows(&mut self, range: std::ops::Range<usize>, cx: &mut Context<Self>) -> Vec<AnyElement> {
+        let store = self.store.read(cx);
+        let me = store.me();
+        let messages = store.messages(self.conversation);
+
+        range
+            .filter_map(|row| {
+                let message = messages.get(*self.rows.get(row)?)?;
+                let replies = store.reply_count(self.conversation, message.id);
+                let height = self.item_sizes[row].height;
+                Some(self.render_message(message, message.sender == me, replies, height, cx))
+            })
+            .collect()
+    }
+
+    fn render_emoji_button(&self) -> impl IntoElement {
+        let picker = self.emoji_picker.clone();
+        Popover::new("emoji-picker")
+            .anchor(Corner::BottomRight)
+            .trigger(Button::new("emoji").label("😀").small().ghost())
+            .content(move |_, _, _| picker.clone())
+    }
+
+    fn render_send_key_button(&self, cx: &Context<Self>) -> impl IntoElement {
+        Button::new("send-key")
+            .icon(IconName::ChevronDown)
+            .ghost()
+            .tooltip(SendKey::global(cx).label())
+            .dropdown_menu_with_anchor(Corner::BottomRight, |menu, _, cx| {
+                let current = SendKey::global(cx);
+                [SendKey::Enter, SendKey::SecondaryEnter]
+                    .into_iter()
+                    .fold(menu, |menu, key| {
+                        menu.menu_with_check(key.label(), key == current, Box::new(SetSendKey(key)))
+                    })
+            })
+    }
+
+    /// "Replying to ..." above the composer.
+    fn render_reply_banner(&self, cx: &Context<Self>) -> Option<impl IntoElement> {
+        let reply_to = self.reply_to?;
+        let snippet = self
+            .store
+            .read(cx)
+            .message(self.conversation, reply_to)
+            .map(|message| message.snippet())
+            .unwrap_or_default();
+
+        Some(
+            h_flex()
+                .px(px(10.))
+                .gap_2()
+                .text_xs()
+                .text_color(cx.theme().muted_foreground)
+                .child(div().flex_1().child(format!("Replying to: {}", snippet)))
+                .child(
+                    Button::new("cancel-reply")
+                        .icon(IconName::Close)
+                        .xsmall()
+                        .ghost()
+                        .on_click(cx.listener(|this, _, _, cx| {
+                            this.reply_to = None;
+                            cx.notify();
+                        })),
+                ),
+        )
     }
 }
 
+impl Focusable for HistoryView {
+    fn focus_handle(&self, _: &App) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
 
 impl Render for HistoryView {
-    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        self.layout_rows(window, cx);
+        if let Some(id) = self.pending_jump.take().filter(|_| !self.rows.is_empty()) {
+            self.scroll_to_bottom = false;
+            self.jump_to_message(id, window, cx);
+        } else if self.scroll_to_bottom && !self.rows.is_empty() {
+            self.scroll_to_bottom = false;
+            self.scroll_handle.scroll_to_item(self.rows.len() - 1, ScrollStrategy::Top);
+        }
+
         let theme = cx.theme();
+        let store = self.store.read(cx);
+        let left_group = store.is_group(self.conversation) && !store.is_member(self.conversation, store.me());
+        let composer_height = WindowState::global(cx).composer_height.unwrap_or(px(255.));
 
         v_flex()
+            .id("history")
+            .key_context(CONTEXT)
+            .track_focus(&self.focus_handle)
+            .on_action(cx.listener(Self::on_edit_message))
+            .on_action(cx.listener(Self::on_delete_message))
+            .on_action(cx.listener(Self::on_recall_message))
+            .on_action(cx.listener(Self::on_reply_to_message))
+            .on_action(cx.listener(Self::on_open_thread))
+            .on_action(cx.listener(Self::on_copy_message))
+            .on_action(cx.listener(Self::on_forward_message))
+            .on_action(cx.listener(Self::on_open_image))
+            .on_action(cx.listener(Self::on_save_image))
+            .on_action(cx.listener(Self::on_show_context_menu))
+            .on_action(cx.listener(Self::on_select_message))
+            .on_action(cx.listener(Self::cancel_selection))
+            .on_action(cx.listener(Self::cancel_edit))
+            .capture_action(cx.listener(Self::on_find))
+            .on_action(cx.listener(Self::close_find))
+            .on_action(cx.listener(Self::find_next))
+            .on_action(cx.listener(Self::find_previous))
             .flex_1()
             .h_full()
             .overflow_x_hidden()
@@ -81,7 +1402,31 @@ impl Render for HistoryView {
                 .border_b_1()
                 .border_color(theme.border)
                 // .bg(theme.blue)
-                .child("title: hello for it"),
+                .gap_2()
+                .child(div().flex_1().child(store.title(self.conversation)))
+                .when(store.is_group(self.conversation), |this| {
+                    this.child(
+                        Button::new("members")
+                            .label(format!("{} members", store.members(self.conversation).len()))
+                            .small()
+                            .ghost()
+                            .on_click(cx.listener(|this, _, window, cx| this.show_members(window, cx))),
+                    )
+                })
+                .child(
+                    Button::new("pop-out")
+                        .icon(IconName::ExternalLink)
+                        .small()
+                        .ghost()
+                        .tooltip("Open in New Window")
+                        .on_click(cx.listener(|this, _, _, cx| PopOuts::open(this.conversation, cx))),
+                ),
+            )
+            .children(self.find.as_ref().map(|find| self.render_find_bar(find, cx)))
+            .children(
+                self.selection
+                    .as_ref()
+                    .map(|selection| self.render_selection_bar(selection.len(), cx)),
             )
             .child(
                 div()
@@ -92,44 +1437,50 @@ impl Render for HistoryView {
                 .border_color(theme.border)
                 .child(
                     v_resizable("history")
+                    .on_resize(|state, _, cx| {
+                        if let Some(height) = state.read(cx).sizes().get(1).copied() {
+                            WindowState::update(cx, |state| state.composer_height = Some(height));
+                        }
+                    })
                     .child(
                         div()
-                        .w_full()
+                        .size_full()
                         .flex()
                         .child(
                             div()
-                                .w_full()
+                                .size_full()
                                 .bg(theme.background)
+                                .paddings(Edges{ top: px(10.), right: px(20.), bottom: px(30.), left: px(40.) })
+                                .relative()
+                                .child({
+                                    // The rows are measured against the width of the list, lay them out again when it changes.
+                                    let view = cx.entity();
+                                    canvas(
+                                        move |bounds, _, cx| {
+                                            view.update(cx, |this, cx| {
+                                                if this.list_width != bounds.size.width {
+                                                    this.list_width = bounds.size.width;
+                                                    cx.notify();
+                                                }
+                                            })
+                                        },
+                                        |_, _, _, _| {},
+                                    )
+                                    .absolute()
+                                    .size_full()
+                                })
                                 .child(
-                                    v_flex()
-                                        .paddings(Edges{ top: px(10.), right: px(20.), bottom: px(30.), left: px(40.) })
-                                        .border_10()
-                                        .border_color(theme.yellow)
-                                        .overflow_y_scrollbar()//(&self.scroll_handle, Axis::Vertical)
-                                        .children(self.historys.iter().take(100).map(|item| {
-                                            if item.hint % 2 == 0{
-                                                div()
-                                                .margins(Edges::all(px(10.)))
-                                                .flex()
-                                                .justify_end()
-                                                .text_sm()
-                                                .child(item.text.clone())
-                                                .child(img(item.image.clone()).max_w(px(600.)))
-                                            }else{
-                                                div()
-                                                .margins(Edges::all(px(10.)))
-                                                .text_sm()
-                                                .child(item.text.clone())
-                                                .child(img(item.image.clone()).max_w(px(600.)))
-                                            }
-                                        })),
+                                    v_virtual_list(cx.entity(), "messages", self.item_sizes.clone(), |this, range, _, cx| {
+                                        this.render_rows(range, cx)
+                                    })
+                                    .track_scroll(&self.scroll_handle),
                                 )
                         )
                         .into_any_element()
                     )
                     .child(
                         resizable_panel()
-                        .size(px(255.))
+                        .size(composer_height.clamp(px(100.), px(500.)))
                         .size_range(px(100.)..px(500.))
                         .child(
                             v_flex()
@@ -137,23 +1488,44 @@ impl Render for HistoryView {
                             .child(
                                 "toolbox"
                             )
+                            .children(self.render_reply_banner(cx))
+                            .when(left_group, |this| {
+                                this.child(
+                                    Label::new("You are no longer a member of this group")
+                                        .px_2()
+                                        .text_xs()
+                                        .text_color(theme.muted_foreground),
+                                )
+                            })
                             // .border_10()
                             // .border_color(theme.border)
                             .child(
                                 h_flex()
                                 .paddings(Edges{ top: px(10.), right: px(10.), bottom: px(10.), left: px(10.) })
+                                .gap_2()
                                 .flex_1()
+                                .items_end()
                                 .child(
                                     Input::new(&self.input)
                                     // .bordered(false)
-                                    .size_full()
+                                    .flex_1()
                                     .border_color(theme.border)
-                                    .suffix(Button::new("btn").icon(IconName::Info))
-                                ).child("Send")
+                                    .suffix(self.render_emoji_button())
+                                )
+                                .child(
+                                    Button::new("send")
+                                        .label("Send")
+                                        .primary()
+                                        .on_click(cx.listener(|this, _, window, cx| {
+                                            let text = this.input.read(cx).value().to_string();
+                                            this.send(text, window, cx);
+                                        })),
+                                )
+                                .child(self.render_send_key_button(cx))
                             )
                         )
                     )
                 )
             )
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,376 @@
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use gpui::{Action, App, AppContext as _, Context, Global, IntoElement, KeyBinding, KeyBindingContextPredicate, NoAction, ParentElement as _, Render, SharedString, Styled as _, Window, actions, div, is_no_action, prelude::FluentBuilder as _, px};
+use gpui_component::{ActiveTheme as _, Sizable as _, StyledExt as _, WindowExt as _, alert::Alert, button::Button, h_flex, kbd::Kbd, label::Label, scroll::ScrollableElement as _, v_flex};
+
+use crate::{
+    contacts::ToggleSidebar,
+    settings::SettingsStore,
+    title_bar::{CloseWindow, Quit, Tab, TabPrev, ToggleSearch},
+    web_panel::ToggleWebPanel,
+};
+
+actions!(keymap, [FocusComposer, FocusContacts, ShowKeymap]);
+
+/// What a new keymap file says, every line is a comment.
+const KEYMAP_TEMPLATE: &str = "\
+# Keystrokes bound to action names, they take precedence over the default bindings.
+# An empty action name removes the binding of the keystrokes.
+#
+# \"ctrl-tab\" = \"Tab\"
+# \"secondary-q\" = \"\"
+#
+# A binding that only applies in part of the window names its key context:
+#
+# \"ctrl-g\" = { action = \"history::FindNext\", context = \"HistoryView\" }
+";
+
+/// The namespaces of the actions listed in the shortcuts dialog, with the title of their group.
+const SHORTCUT_GROUPS: &[(&str, &str)] = &[
+    ("", "General"),
+    ("keymap", "Navigation"),
+    ("contacts", "Contacts"),
+    ("history", "Conversation"),
+    ("preferences", "General"),
+    ("themes", "Appearance"),
+    ("web", "Web"),
+];
+
+/// The bindings of the actions declared without a keymap file.
+fn default_bindings() -> Vec<KeyBinding> {
+    vec![
+        KeyBinding::new("ctrl-tab", Tab, None),
+        KeyBinding::new("ctrl-shift-tab", TabPrev, None),
+        KeyBinding::new("secondary-1", FocusContacts, None),
+        KeyBinding::new("secondary-2", FocusComposer, None),
+        KeyBinding::new("secondary-b", ToggleSidebar, None),
+        KeyBinding::new("secondary-shift-f", ToggleSearch, None),
+        KeyBinding::new("secondary-shift-b", ToggleWebPanel, None),
+        KeyBinding::new("secondary-w", CloseWindow, None),
+        KeyBinding::new("secondary-q", Quit, None),
+        KeyBinding::new("secondary-/", ShowKeymap, None),
+    ]
+}
+
+/// The key bindings of the user, read from a TOML file of keystrokes and action names.
+pub struct Keymap {
+    path: PathBuf,
+    /// What was wrong with the keymap file, the entries in question are left out.
+    problems: Vec<String>,
+}
+
+impl Global for Keymap {}
+
+impl Keymap {
+    /// Bind the default keymap and then the one of the user, call it after the other
+    /// modules bound their keys so the user can override those too.
+    pub fn init(cx: &mut App) {
+        cx.bind_keys(default_bindings());
+
+        let path = Self::default_path(cx);
+        let (bindings, problems) = match std::fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text, cx),
+            Err(_) => (vec![], vec![]),
+        };
+        cx.bind_keys(bindings);
+        cx.set_global(Self { path, problems });
+
+        cx.on_action(|_: &ShowKeymap, cx| {
+            let Some(window) = cx.active_window() else {
+                return;
+            };
+            _ = window.update(cx, |_, window, cx| open_shortcuts(window, cx));
+        });
+    }
+
+    /// The keymap file next to the settings file.
+    pub fn default_path(cx: &App) -> PathBuf {
+        SettingsStore::global(cx).path().with_file_name("keymap.toml")
+    }
+
+    pub fn global(cx: &App) -> &Self {
+        cx.global::<Self>()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn problems(&self) -> &[String] {
+        &self.problems
+    }
+
+    /// Parse a keymap, an entry with an unknown action, keystroke or context is skipped.
+    ///
+    /// An entry is an action name, or a table of the action and the key context it applies in.
+    pub fn parse(text: &str, cx: &App) -> (Vec<KeyBinding>, Vec<String>) {
+        let table = match text.parse::<toml::Table>() {
+            Ok(table) => table,
+            Err(err) => return (vec![], vec![err.message().to_string()]),
+        };
+
+        let mut bindings = vec![];
+        let mut problems = vec![];
+        for (keystrokes, value) in table {
+            match Self::parse_binding(&keystrokes, &value, cx) {
+                Ok(binding) => bindings.push(binding),
+                Err(err) => problems.push(format!("\"{}\": {}", keystrokes, err)),
+            }
+        }
+        (bindings, problems)
+    }
+
+    fn parse_binding(keystrokes: &str, value: &toml::Value, cx: &App) -> Result<KeyBinding, String> {
+        let (name, context) = match value {
+            toml::Value::String(name) => (name.as_str(), None),
+            toml::Value::Table(table) => {
+                if let Some(key) = table.keys().find(|key| !matches!(key.as_str(), "action" | "context")) {
+                    return Err(format!("unknown field {}, expected action or context", key));
+                }
+                let name = table
+                    .get("action")
+                    .and_then(|name| name.as_str())
+                    .ok_or("the action must be an action name")?;
+                let context = match table.get("context") {
+                    None => None,
+                    Some(context) => Some(context.as_str().ok_or("the context must be a key context")?),
+                };
+                (name, context)
+            }
+            _ => return Err("must be bound to an action name".into()),
+        };
+
+        let action = if name.is_empty() {
+            NoAction.boxed_clone()
+        } else {
+            cx.build_action(name, None).map_err(|err| err.to_string())?
+        };
+        let context = context
+            .map(|context| KeyBindingContextPredicate::parse(context).map(Rc::new))
+            .transpose()
+            .map_err(|err| format!("invalid context: {}", err))?;
+        KeyBinding::load(keystrokes, action, context, false, None, cx.keyboard_mapper().as_ref())
+            .map_err(|err| err.to_string())
+    }
+
+    /// Open the keymap file with the system, creating it with a few examples first.
+    pub fn open_file(cx: &mut App) {
+        let path = Self::global(cx).path.clone();
+        if !path.exists()
+            && let Some(dir) = path.parent()
+            && std::fs::create_dir_all(dir).is_ok()
+        {
+            _ = std::fs::write(&path, KEYMAP_TEMPLATE);
+        }
+        cx.open_with_system(&path);
+    }
+}
+
+/// The group and title of an action name, `None` for actions left out of the shortcuts dialog.
+fn describe(name: &str) -> Option<(&'static str, String)> {
+    let (namespace, name) = name.rsplit_once("::").unwrap_or(("", name));
+    let group = SHORTCUT_GROUPS
+        .iter()
+        .find(|(group, _)| *group == namespace)
+        .map(|(_, title)| *title)?;
+
+    let mut title = String::new();
+    for c in name.chars() {
+        if c.is_uppercase() && !title.is_empty() {
+            title.push(' ');
+        }
+        title.push(c);
+    }
+    Some((group, title))
+}
+
+pub fn open_shortcuts(window: &mut Window, cx: &mut App) {
+    let shortcuts = cx.new(Shortcuts::new);
+    window.open_dialog(cx, move |dialog, _, _| {
+        dialog
+            .title("Keyboard Shortcuts")
+            .w(px(520.))
+            .child(shortcuts.clone())
+    });
+}
+
+/// A shortcut group's title with the titles and keystrokes of its actions.
+type ShortcutGroup = (&'static str, Vec<(String, Vec<Kbd>)>);
+
+/// Lists the key bindings in effect, those of the keymap file included.
+pub struct Shortcuts {
+    /// The groups in the order of [`SHORTCUT_GROUPS`], each with its titles and keystrokes.
+    groups: Vec<ShortcutGroup>,
+}
+
+impl Shortcuts {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        let keymap = cx.key_bindings();
+        let keymap = keymap.borrow();
+
+        // A binding is shadowed by a later one of the same keystrokes, so walk them backwards.
+        let mut seen: Vec<(String, Option<String>)> = vec![];
+        let mut rows = vec![];
+        for binding in keymap.bindings().rev() {
+            let keystrokes = binding
+                .keystrokes()
+                .iter()
+                .map(|keystroke| keystroke.inner().unparse())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let context = binding.predicate().map(|predicate| predicate.to_string());
+            let key = (keystrokes, context);
+            if seen.contains(&key) {
+                continue;
+            }
+            seen.push(key);
+
+            let action = binding.action();
+            if is_no_action(action) {
+                continue;
+            }
+            let Some((group, title)) = describe(action.name()) else {
+                continue;
+            };
+            let keys = binding
+                .keystrokes()
+                .iter()
+                .map(|keystroke| Kbd::new(keystroke.inner().clone()))
+                .collect::<Vec<_>>();
+            rows.push((group, title, keys));
+        }
+        rows.reverse();
+
+        let mut groups: Vec<ShortcutGroup> = vec![];
+        for (_, title) in SHORTCUT_GROUPS {
+            if groups.iter().any(|(group, _)| group == title) {
+                continue;
+            }
+            let items = rows
+                .iter()
+                .filter(|(group, _, _)| group == title)
+                .map(|(_, title, keys)| (title.clone(), keys.clone()))
+                .collect::<Vec<_>>();
+            if !items.is_empty() {
+                groups.push((title, items));
+            }
+        }
+
+        Self { groups }
+    }
+}
+
+impl Render for Shortcuts {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let problems = Keymap::global(cx).problems();
+
+        v_flex()
+            .gap_3()
+            .when(!problems.is_empty(), |this| {
+                this.child(
+                    Alert::warning("keymap-problems", problems.join("\n"))
+                        .title("Some entries of the keymap file were skipped"),
+                )
+            })
+            .child(
+                v_flex()
+                    .max_h(px(420.))
+                    .gap_4()
+                    .overflow_y_scrollbar()
+                    .children(self.groups.iter().map(|(group, items)| {
+                        v_flex()
+                            .gap_1()
+                            .child(Label::new(SharedString::from(*group)).text_sm().font_semibold())
+                            .children(items.iter().map(|(title, keys)| {
+                                h_flex()
+                                    .gap_2()
+                                    .py_0p5()
+                                    .child(Label::new(title.clone()).flex_1().text_sm())
+                                    .children(keys.iter().cloned())
+                            }))
+                    })),
+            )
+            .child(
+                h_flex()
+                    .gap_2()
+                    .child(
+                        div()
+                            .flex_1()
+                            .text_xs()
+                            .text_color(cx.theme().muted_foreground)
+                            .child("Changes to the keymap file apply on the next start."),
+                    )
+                    .child(
+                        Button::new("open-keymap-file")
+                            .label("Open Keymap File")
+                            .small()
+                            .outline()
+                            .on_click(|_, _, cx| Keymap::open_file(cx)),
+                    ),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gpui::TestAppContext;
+
+    use super::*;
+
+    #[gpui::test]
+    fn bindings_may_name_a_context(cx: &mut TestAppContext) {
+        let (bindings, problems) = cx.update(|cx| {
+            Keymap::parse(
+                r#"
+                "ctrl-tab" = "Tab"
+                "secondary-q" = ""
+                "ctrl-g" = { action = "history::FindNext", context = "HistoryView" }
+                "#,
+                cx,
+            )
+        });
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(bindings.len(), 3);
+
+        let contexts = bindings
+            .iter()
+            .map(|binding| binding.predicate().map(|predicate| predicate.to_string()))
+            .collect::<Vec<_>>();
+        assert!(contexts.contains(&Some("HistoryView".to_string())));
+        assert_eq!(contexts.iter().filter(|context| context.is_none()).count(), 2);
+    }
+
+    #[gpui::test]
+    fn bad_entries_are_skipped(cx: &mut TestAppContext) {
+        let (bindings, problems) = cx.update(|cx| {
+            Keymap::parse(
+                r#"
+                "ctrl-tab" = "NoSuchAction"
+                "ctrl-1" = 1
+                "ctrl-2" = { action = "Tab", when = "HistoryView" }
+                "ctrl-3" = { action = "Tab", context = "(" }
+                "ctrl-4" = { context = "HistoryView" }
+                "ctrl-5" = "Tab"
+                "#,
+                cx,
+            )
+        });
+        assert_eq!(bindings.len(), 1);
+        assert_eq!(problems.len(), 5);
+    }
+
+    #[test]
+    fn the_template_is_all_comments() {
+        assert!(KEYMAP_TEMPLATE.lines().all(|line| line.starts_with('#')));
+        let uncommented = KEYMAP_TEMPLATE
+            .lines()
+            .map(|line| line.trim_start_matches('#').trim())
+            .filter(|line| line.contains(" = "))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(uncommented.parse::<toml::Table>().map(|table| table.len()), Ok(3));
+    }
+}
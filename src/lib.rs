@@ -1,9 +1,70 @@
 mod title_bar;
 mod history;
 mod contacts;
+mod chat;
+mod attachments;
+mod transport;
+mod thread;
+mod emoji;
+mod mentions;
+mod members;
+mod search;
+mod drafts;
+mod flags;
+mod context_menu;
+mod forward;
+mod notifications;
+mod notification_center;
+mod appearance;
+mod settings;
+mod preferences;
+mod window_state;
+mod keymap;
+mod persist;
+mod popout;
+mod web_panel;
+mod bridge;
 // 显式引用 assets 包，确保图标资源被嵌入到二进制文件中
 use gpui_component_assets as _;
 
-pub use title_bar::AppTitleBar;
-pub use history::HistoryView;
-// pub use contacts::ContactsListDelegate;
\ No newline at end of file
+pub use title_bar::{AppTitleBar, Tab, TabPrev};
+pub use history::{HistoryView, SendKey};
+pub use attachments::{Attachment, AttachmentCache};
+pub use drafts::Drafts;
+pub use flags::{ConversationFlags, SavedFlags};
+pub use forward::ForwardView;
+pub use notifications::{DoNotDisturb, LoggedNotification, NotificationLog, Notifications, Notifier, PlatformNotifier, RecordingNotifier, SystemNotification};
+pub use notification_center::NotificationCenter;
+pub use appearance::{Appearance, ThemePicker};
+pub use preferences::{OpenPreferences, Preferences};
+pub use window_state::{WindowMode, WindowState};
+pub use popout::{ConversationWindow, PopOutConversation, PopOuts};
+pub use bridge::{Bridge, Command, Event as BridgeEvent};
+pub use web_panel::{ToggleWebPanel, WebPanel};
+pub use keymap::{FocusComposer, FocusContacts, Keymap, ShowKeymap, Shortcuts};
+pub use settings::{AppSettings, AppearanceMode, AppearanceSettings, ChatSettings, NetworkSettings, NotificationSettings, Proxy, SettingsStore, StorageSettings, WindowSettings};
+pub use context_menu::{KeyboardMenu, ShowContextMenu};
+pub use chat::{ChatEvent, ChatStore, ConversationId, Group, MessageId, NewMessage, Unread, UserId};
+pub use members::{MembersView, NewGroupForm};
+pub use search::{SearchHit, SearchQuery, SearchView, search};
+pub use transport::{LoopbackTransport, Op, Transport};
+// pub use contacts::ContactsListDelegate;
+pub use contacts::ToggleSidebar;
+
+pub fn init(cx: &mut gpui::App) {
+    SettingsStore::init(cx);
+    WindowState::init(cx);
+    ChatStore::init(cx);
+    Notifications::init(cx);
+    Appearance::init(cx);
+    context_menu::init(cx);
+    emoji::init(cx);
+    history::init(cx);
+    preferences::init(cx);
+    search::init(cx);
+    title_bar::init(cx);
+    popout::init(cx);
+    web_panel::init(cx);
+    Bridge::init(cx);
+    Keymap::init(cx);
+}
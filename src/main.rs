@@ -3,20 +3,18 @@
     windows_subsystem = "windows"
 )]
 
-use std::{path::Path, rc::Rc, time::Duration};
+use std::{path::Path, rc::Rc, time::{Duration, Instant}};
 
-use agpui::{AppTitleBar, HistoryView};
-use fake::Fake;
+use agpui::{AppTitleBar, ChatEvent, ChatStore, ConversationFlags, ConversationId, FocusComposer, Group, FocusContacts, HistoryView, KeyboardMenu, NewGroupForm, Notifications, PopOutConversation, PopOuts, ShowContextMenu, Tab, TabPrev, ToggleSidebar, Unread, WebPanel, WindowSettings, WindowState};
+use fake::{Fake, rand::{SeedableRng as _, rngs::StdRng}};
 use gpui::{
-    AnyView, App, AppContext, Application, Bounds, ClickEvent, Context, Edges, ElementId, Entity, FocusHandle, Focusable, Hsla, ImageSource, InteractiveElement, IntoElement, ParentElement, Pixels, Render, RenderOnce, ScrollStrategy, SharedString, Styled, Subscription, Task, Timer, Window, WindowBounds, WindowKind, WindowOptions, actions, div, prelude::FluentBuilder as _, px, size
+    Action, AnyElement, AnyView, App, AppContext, Application, ClickEvent, Context, Edges, ElementId, Entity, FocusHandle, Focusable, Hsla, ImageSource, InteractiveElement, IntoElement, KeyBinding, ParentElement, Pixels, Render, RenderOnce, ScrollStrategy, SharedString, Styled, StatefulInteractiveElement, Subscription, Task, Timer, Window, WindowKind, WindowOptions, actions, div, ease_in_out, prelude::FluentBuilder as _, px
 };
 
 use gpui_component::{
-    ActiveTheme, Icon, IconName, IndexPath, Root, Selectable, Sizable, StyledExt, TitleBar, WindowExt, accordion::Accordion, alert::Alert, avatar::{Avatar, AvatarGroup}, badge::Badge, button::Button, checkbox::Checkbox, h_flex, input::{Input, InputEvent, InputState}, label::Label, list::{List, ListDelegate, ListEvent, ListItem, ListState}, resizable::{h_resizable, resizable_panel}, v_flex, webview
+    ActiveTheme, Icon, IconName, IndexPath, Root, Selectable, Sizable, StyledExt, TitleBar, WindowExt, accordion::Accordion, alert::Alert, avatar::{Avatar, AvatarGroup}, badge::Badge, button::{Button, ButtonVariants as _}, checkbox::Checkbox, h_flex, input::{Input, InputEvent, InputState}, label::Label, list::{List, ListDelegate, ListEvent, ListItem, ListState}, menu::{ContextMenuExt as _, PopupMenu}, resizable::{h_resizable, resizable_panel}, scroll::ScrollableElement as _, tooltip::Tooltip, v_flex, webview
 };
 use gpui_component_assets::Assets;
-use gpui_component::webview::WebView;
-use gpui_component::wry;
 
 pub struct ChatContact {
     pub name: SharedString,
@@ -43,21 +41,29 @@ struct Contact {
     last_done_str: SharedString,
     prev_close_str: SharedString,
     // description: String,
+    is_group: bool,
 }
 
-fn random_contact() -> Contact {
-    let last_done = (0.0..999.0).fake();
-    let prev_close = last_done * (-0.1..0.1).fake::<f64>();
+fn avatar_source(id: i64) -> ImageSource {
+    Path::new(&format!("G:/research/rustee/agpui/images/{}.png", id as usize%11)).into()
+}
+
+/// The fake contact at `ix`, the same in every run so that drafts, flags and pop-outs saved
+/// by conversation find it again.
+fn fake_contact(ix: usize) -> Contact {
+    let rng = &mut StdRng::seed_from_u64(ix as u64);
+    let last_done = (0.0..999.0).fake_with_rng(rng);
+    let prev_close = last_done * (-0.1..0.1).fake_with_rng::<f64, _>(rng);
 
-    let id = (0..9999999999).fake();
+    let id = (1..9999999999).fake_with_rng(rng);
 
     Contact {
         id: id,
-        avatar: Some(Path::new(&format!("G:/research/rustee/agpui/images/{}.png", id as usize%11)).into()),
+        avatar: Some(avatar_source(id)),
         name: fake::faker::name::en::Name()
-            .fake::<String>()
+            .fake_with_rng::<String, _>(rng)
             .into(),
-        description: fake::faker::company::en::Industry().fake::<String>().into(),
+        description: fake::faker::company::en::Industry().fake_with_rng::<String, _>(rng).into(),
         last_done,
         prev_close,
         ..Default::default()
@@ -69,23 +75,51 @@ struct ContactListItem {
     base: ListItem,
     ix: IndexPath,
     contact: Rc<Contact>,
+    unread: Unread,
+    flags: ConversationFlags,
     selected: bool,
+    /// The menu opened with the keyboard when it is for this contact.
+    keyboard_menu: Option<AnyElement>,
 }
 
 impl ContactListItem {
     pub fn new(
         id: impl Into<ElementId>,
         contact: Rc<Contact>,
+        unread: Unread,
+        flags: ConversationFlags,
         ix: IndexPath,
         selected: bool,
     ) -> Self {
         ContactListItem {
             contact,
+            unread,
+            flags,
             ix,
             base: ListItem::new(id),
             selected,
+            keyboard_menu: None,
         }
     }
+
+    fn keyboard_menu(mut self, menu: Option<AnyElement>) -> Self {
+        self.keyboard_menu = menu;
+        self
+    }
+}
+
+/// The context menu of a contact, items that do not apply to it are disabled.
+fn contact_menu(menu: PopupMenu, conversation: ConversationId, cx: &App) -> PopupMenu {
+    let store = ChatStore::global(cx).read(cx);
+    let flags = store.flags(conversation);
+    let unread = store.unread(conversation);
+    menu.menu_with_disabled("Mark as Read", Box::new(MarkRead(conversation)), unread.messages == 0)
+        .separator()
+        .menu(if flags.pinned { "Unpin" } else { "Pin to Top" }, Box::new(TogglePinned(conversation)))
+        .menu(if flags.muted { "Unmute" } else { "Mute" }, Box::new(ToggleMuted(conversation)))
+        .menu(if flags.archived { "Unarchive" } else { "Archive" }, Box::new(ToggleArchived(conversation)))
+        .separator()
+        .menu("Open in New Window", Box::new(PopOutConversation(conversation)))
 }
 
 impl Selectable for ContactListItem {
@@ -110,10 +144,23 @@ impl RenderOnce for ContactListItem {
             background = theme.list_even
         }
 
-        let mut img = Avatar::new().name(self.contact.name.clone());
-        if let Some(avatar) = &self.contact.avatar {
-            img = img.src(avatar.clone())
-        }
+        let store = ChatStore::global(cx).read(cx);
+        let conversation = ConversationId(self.contact.id);
+        let (img, description) = if self.contact.is_group {
+            let members = store.members(conversation);
+            let img = AvatarGroup::new()
+                .limit(3)
+                .children(members.iter().map(|id| Avatar::new().name(store.name(*id)).src(avatar_source(*id))))
+                .into_any_element();
+            (img, SharedString::from(format!("{} members", members.len())))
+        } else {
+            let mut img = Avatar::new().name(self.contact.name.clone());
+            if let Some(avatar) = &self.contact.avatar {
+                img = img.src(avatar.clone())
+            }
+            (img.into_any_element(), self.contact.description.clone())
+        };
+        let flags = self.flags;
         self.base
             .rounded(theme.radius)
             .bg(background)
@@ -122,20 +169,54 @@ impl RenderOnce for ContactListItem {
             .child(
                 h_flex()
                 .gap_1()
-                .child(img)
+                .child(
+                    Badge::new()
+                        .count(self.unread.messages)
+                        .max(99)
+                        .when(flags.muted, |this| this.color(theme.muted_foreground))
+                        .child(img)
+                )
                 .child(
                     v_flex()
+                    .flex_1()
                     .gap_1()
                     .child(Label::new(self.contact.name.clone()))
-                    .child(Label::new(self.contact.description.clone()))
+                    .child(match store.draft(conversation) {
+                        Some(draft) => h_flex()
+                            .gap_1()
+                            .child(Label::new("Draft:").text_color(theme.danger))
+                            .child(Label::new(draft.lines().next().unwrap_or_default().to_string()).truncate())
+                            .into_any_element(),
+                        None => Label::new(description).into_any_element(),
+                    })
                 )
+                // Mentions of us are counted apart from the other unread messages.
+                .when(self.unread.mentions > 0, |this| {
+                    this.child(
+                        div()
+                        .px_1()
+                        .rounded(theme.radius)
+                        .bg(theme.danger)
+                        .text_color(theme.danger_foreground)
+                        .text_xs()
+                        .child(format!("@{}", self.unread.mentions))
+                    )
+                })
             )
+            .children(self.keyboard_menu)
+            .context_menu(move |menu, _, cx| contact_menu(menu, conversation, cx))
     }
 }
 
 struct ContactsListDelegate {
     // industries: Vec<SharedString>,
     contacts: Vec<Rc<Contact>>,
+    /// The number of fake contacts loaded so far.
+    fake_contacts: usize,
+    /// The pinned contacts and the others, only the archived ones in the Archived view.
+    sections: [Vec<Rc<Contact>>; 2],
+    show_archived: bool,
+    keyboard_menu: Option<KeyboardMenu<ConversationId>>,
     // matched_companies: Vec<Vec<Rc<Company>>>,
     selected_index: Option<IndexPath>,
     // confirmed_index: Option<IndexPath>,
@@ -146,14 +227,108 @@ struct ContactsListDelegate {
 }
 actions!(contacts, [SelectedContact]);
 
+#[derive(Action, Clone, PartialEq)]
+#[action(namespace = contacts, no_json)]
+struct MarkRead(ConversationId);
+
+#[derive(Action, Clone, PartialEq)]
+#[action(namespace = contacts, no_json)]
+struct TogglePinned(ConversationId);
+
+#[derive(Action, Clone, PartialEq)]
+#[action(namespace = contacts, no_json)]
+struct ToggleMuted(ConversationId);
+
+#[derive(Action, Clone, PartialEq)]
+#[action(namespace = contacts, no_json)]
+struct ToggleArchived(ConversationId);
+
+const PINNED_SECTION: usize = 0;
+
+/// The group seeded on top of the contacts, groups have negative ids.
+const TEAM: ConversationId = ConversationId(-1);
+
+const CONTACTS_MIN_WIDTH: Pixels = px(160.);
+const CONTACTS_MAX_WIDTH: Pixels = px(360.);
+/// The width of the contacts panel collapsed to a rail of avatars.
+const RAIL_WIDTH: Pixels = px(64.);
+/// How long the contacts panel takes to collapse or expand.
+const SIDEBAR_DURATION: Duration = Duration::from_millis(180);
+
+/// The contacts panel on its way from one width to another.
+struct SidebarAnimation {
+    started: Instant,
+    from: Pixels,
+    to: Pixels,
+}
+
+impl SidebarAnimation {
+    fn progress(&self) -> f32 {
+        (self.started.elapsed().as_secs_f32() / SIDEBAR_DURATION.as_secs_f32()).min(1.)
+    }
+
+    fn width(&self) -> Pixels {
+        self.from + (self.to - self.from) * ease_in_out(self.progress())
+    }
+}
+
 impl ContactsListDelegate {
+    /// Sort the contacts into the sections by their flags, the selection follows its contact.
+    fn refresh(&mut self, cx: &App) {
+        let selected = self.selected_index.and_then(|ix| self.contact(ix)).map(|contact| contact.id);
+        let store = ChatStore::global(cx).read(cx);
+        let found = (!self.query.trim().is_empty()).then(|| store.find_conversations(&self.query));
+
+        self.sections = Default::default();
+        for contact in &self.contacts {
+            let conversation = ConversationId(contact.id);
+            let flags = store.flags(conversation);
+            if flags.archived != self.show_archived
+                || found.as_ref().is_some_and(|found| !found.contains(&conversation))
+            {
+                continue;
+            }
+            let section = if flags.pinned && !self.show_archived { PINNED_SECTION } else { 1 };
+            self.sections[section].push(contact.clone());
+        }
+
+        self.selected_index = selected.and_then(|id| {
+            self.sections.iter().enumerate().find_map(|(section, contacts)| {
+                let row = contacts.iter().position(|contact| contact.id == id)?;
+                Some(IndexPath::new(row).section(section))
+            })
+        });
+    }
+
+    fn contact(&self, ix: IndexPath) -> Option<&Rc<Contact>> {
+        self.sections.get(ix.section)?.get(ix.row)
+    }
+
+    /// Every row of the sections in list order.
+    fn rows(&self) -> impl Iterator<Item = IndexPath> + '_ {
+        self.sections
+            .iter()
+            .enumerate()
+            .flat_map(|(section, contacts)| (0..contacts.len()).map(move |row| IndexPath::new(row).section(section)))
+    }
+
+    fn archived_count(&self, cx: &App) -> usize {
+        let store = ChatStore::global(cx).read(cx);
+        self.contacts
+            .iter()
+            .filter(|contact| store.flags(ConversationId(contact.id)).archived)
+            .count()
+    }
+
     fn prepare(&mut self, query: impl Into<SharedString>) {
         self.query = query.into();
     }
 
     fn extend_more(&mut self, len: usize) {
+        let start = self.fake_contacts;
+        self.fake_contacts += len;
         self.contacts
-            .extend((0..len).map(|_| Rc::new(random_contact())));
+            .extend((start..self.fake_contacts).map(|ix| Rc::new(fake_contact(ix))));
         self.prepare(self.query.clone());
     }
 }
@@ -162,20 +337,21 @@ impl ListDelegate for ContactsListDelegate {
     type Item = ContactListItem;
 
     fn sections_count(&self, _: &App) -> usize {
-        1
+        self.sections.len()
     }
 
     fn items_count(&self, section: usize, _: &App) -> usize {
-        self.contacts.len()
+        self.sections[section].len()
     }
 
     fn perform_search(
         &mut self,
         query: &str,
         _: &mut Window,
-        _: &mut Context<ListState<Self>>,
+        cx: &mut Context<ListState<Self>>,
     ) -> Task<()> {
         self.prepare(query.to_owned());
+        self.refresh(cx);
         Task::ready(())
     }
 
@@ -192,31 +368,31 @@ impl ListDelegate for ContactsListDelegate {
     ) {
         self.selected_index = ix;
         cx.notify();
-
-
-        win.open_dialog(cx, |dialog, _, _| {
-                dialog
-                .title("Welcome")
-                .child("This is a dialog dialog.")
-            })
     }
 
-    // fn render_section_header(
-    //     &self,
-    //     section: usize,
-    //     _: &mut Window,
-    //     cx: &mut App,
-    // ) -> Option<impl IntoElement> {
-    //     Some(
-    //         h_flex()
-    //             .pb_1()
-    //             .px_2()
-    //             .gap_2()
-    //             .text_sm()
-    //             .text_color(cx.theme().muted_foreground)
-    //             .child(Icon::new(IconName::Folder))
-    //     )
-    // }
+    fn render_section_header(
+        &mut self,
+        section: usize,
+        _: &mut Window,
+        cx: &mut Context<ListState<Self>>,
+    ) -> Option<impl IntoElement> {
+        // Without pinned contacts there is nothing to tell apart.
+        let title = match section {
+            PINNED_SECTION if !self.sections[PINNED_SECTION].is_empty() => "Pinned",
+            PINNED_SECTION => return None,
+            _ if self.show_archived => "Archived",
+            _ if self.sections[PINNED_SECTION].is_empty() => return None,
+            _ => "Chats",
+        };
+        Some(
+            h_flex()
+                .pb_1()
+                .px_2()
+                .text_sm()
+                .text_color(cx.theme().muted_foreground)
+                .child(title)
+        )
+    }
     // fn render_section_footer(
     //     &self,
     //     section: usize,
@@ -226,10 +402,18 @@ impl ListDelegate for ContactsListDelegate {
     //     Option<Element>::None
     // }
 
-    fn render_item(&mut self, ix: IndexPath, _: &mut Window, _: &mut Context<ListState<Self>>,) -> Option<Self::Item> {
+    fn render_item(&mut self, ix: IndexPath, _: &mut Window, cx: &mut Context<ListState<Self>>,) -> Option<Self::Item> {
         let selected = Some(ix) == self.selected_index;
-        if let Some(contact) = self.contacts.get(ix.row) {
-            return Some(ContactListItem::new(ix, contact.clone(), ix, selected));
+        if let Some(contact) = self.contact(ix) {
+            let store = ChatStore::global(cx).read(cx);
+            let conversation = ConversationId(contact.id);
+            let (unread, flags) = (store.unread(conversation), store.flags(conversation));
+            let keyboard_menu = self
+                .keyboard_menu
+                .as_ref()
+                .filter(|menu| *menu.target() == conversation)
+                .map(|menu| menu.render().into_any_element());
+            return Some(ContactListItem::new(ix, contact.clone(), unread, flags, ix, selected).keyboard_menu(keyboard_menu));
         }
         None
     }
@@ -258,6 +442,7 @@ impl ListDelegate for ContactsListDelegate {
             _ = view.update_in(window, move |view, window, cx| {
                 let query = view.delegate().query.clone();
                 view.delegate_mut().extend_more(200);
+                view.delegate_mut().refresh(cx);
                 _ = view.delegate_mut().perform_search(&query, window, cx);
                 view.delegate_mut().eof = view.delegate().contacts.len() >= 6000;
             });
@@ -269,12 +454,18 @@ impl ListDelegate for ContactsListDelegate {
 
 pub struct MainView{
     name: SharedString,
+    /// Whether the contacts panel shows only the avatars.
     collapsed: bool,
+    sidebar_animation: Option<SidebarAnimation>,
+    /// Bumped when the contacts panel is expanded, the panel group then starts over
+    /// from the saved width instead of the width it measured while collapsed.
+    sidebar_generation: usize,
     // contacts: Vec<(&'static str, Vec<ChatContact>)>,
 
     history: Entity<HistoryView>,
 
     contacts: Entity<gpui_component::list::ListState<ContactsListDelegate>>,
+    _subscriptions: Vec<Subscription>,
 }
 
 impl MainView {
@@ -288,6 +479,10 @@ impl MainView {
             // industries: vec![],
             // matched_companies: vec![vec![]],
             contacts: vec![],
+            fake_contacts: 0,
+            sections: Default::default(),
+            show_archived: false,
+            keyboard_menu: None,
             // selected_index: Some(IndexPath::default()),
             // confirmed_index: None,
             // query: "".into(),
@@ -316,46 +511,455 @@ impl MainView {
         // ];
 
 
-        let history = cx.new(|cx| HistoryView::new(window, cx));
+        // Until there is a server the contacts are only known here, a group with
+        // the first few of them is added on top.
+        let store = ChatStore::global(cx);
+        store.update(cx, |store, _| {
+            let me = store.me();
+            store.add_user(me, "Me");
+            for contact in &delegate.contacts {
+                store.add_user(contact.id, contact.name.clone());
+                store.set_members(ConversationId(contact.id), vec![me, contact.id]);
+            }
+            let members = [me].into_iter().chain(delegate.contacts.iter().take(5).map(|contact| contact.id)).collect();
+            let group = Group {
+                title: "Team".into(),
+                admins: vec![me],
+            };
+            store.add_group(TEAM, group, members);
+        });
+        delegate.contacts.insert(0, Rc::new(group_contact(TEAM, "Team")));
+        delegate.refresh(cx);
+
+        let conversation = ConversationId(delegate.contacts[0].id);
+        let history = cx.new(|cx| HistoryView::new(conversation, window, cx));
         let contacts = cx.new(|cx| ListState::new(delegate, window, cx));
+        let _subscriptions = vec![
+            cx.observe(&store, |this, _, cx| {
+                this.contacts.update(cx, |contacts, cx| {
+                    contacts.delegate_mut().refresh(cx);
+                    cx.notify();
+                });
+            }),
+            cx.subscribe_in(&store, window, |this, _, event: &ChatEvent, window, cx| match event {
+                ChatEvent::Reveal { conversation, message } => {
+                    this.add_contact(*conversation, cx);
+                    if this.history.read(cx).conversation() != *conversation {
+                        this.open_conversation(*conversation, window, cx);
+                    }
+                    if let Some(message) = message {
+                        this.history.update(cx, |history, cx| history.jump_to_message(*message, window, cx));
+                    }
+                }
+                ChatEvent::Received { conversation, message } => {
+                    // An active pop-out shows the toast, or nothing when it shows the conversation.
+                    if let Some((popout, open)) = PopOuts::active(cx) {
+                        _ = popout.update(cx, |_, window, cx| {
+                            Notifications::notify_message(*conversation, *message, Some(open), window, cx);
+                        });
+                        return;
+                    }
+                    let open = this.history.read(cx).conversation();
+                    Notifications::notify_message(*conversation, *message, Some(open), window, cx);
+                }
+            }),
+            cx.subscribe_in(&contacts, window, |this, contacts, event: &ListEvent, window, cx| {
+                if let ListEvent::Confirm(ix) = event {
+                    let Some(contact) = contacts.read(cx).delegate().contact(*ix) else {
+                        return;
+                    };
+                    let conversation = ConversationId(contact.id);
+                    this.open_conversation(conversation, window, cx);
+                }
+            }),
+        ];
         Self { 
             name: SharedString::default(),
-            collapsed: false,
+            collapsed: WindowState::global(cx).contacts_collapsed,
+            sidebar_animation: None,
+            sidebar_generation: 0,
             contacts: contacts,
             history: history,
+            _subscriptions,
         }
     }
 }
 
+fn group_contact(conversation: ConversationId, title: impl Into<SharedString>) -> Contact {
+    Contact {
+        id: conversation.0,
+        name: title.into(),
+        is_group: true,
+        ..Default::default()
+    }
+}
+
+impl MainView {
+    fn open_conversation(&mut self, conversation: ConversationId, window: &mut Window, cx: &mut Context<Self>) {
+        self.history = cx.new(|cx| HistoryView::new(conversation, window, cx));
+        cx.notify();
+    }
+
+    /// List a conversation the contacts do not have yet, like an imported one.
+    fn add_contact(&mut self, conversation: ConversationId, cx: &mut Context<Self>) {
+        self.contacts.update(cx, |contacts, cx| {
+            let delegate = contacts.delegate_mut();
+            if delegate.contacts.iter().any(|contact| contact.id == conversation.0) {
+                return;
+            }
+            let store = ChatStore::global(cx).read(cx);
+            let title = store.title(conversation);
+            let contact = if store.is_group(conversation) {
+                group_contact(conversation, title)
+            } else {
+                Contact {
+                    id: conversation.0,
+                    name: title,
+                    avatar: Some(avatar_source(conversation.0)),
+                    ..Default::default()
+                }
+            };
+            delegate.contacts.insert(0, Rc::new(contact));
+            delegate.refresh(cx);
+            cx.notify();
+        });
+    }
+
+    fn new_group(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let form = cx.new(|cx| NewGroupForm::new(window, cx));
+        let view = cx.entity().downgrade();
+        window.open_dialog(cx, move |dialog, _, _| {
+            let form = form.clone();
+            let view = view.clone();
+            dialog
+                .title("New Group")
+                .child(form.clone())
+                .confirm()
+                .on_ok(move |_, window, cx| {
+                    let Some(conversation) = form.update(cx, |form, cx| form.create(cx)) else {
+                        return false;
+                    };
+                    let title = ChatStore::global(cx).read(cx).title(conversation);
+                    _ = view.update(cx, |this, cx| {
+                        this.contacts.update(cx, |contacts, cx| {
+                            contacts.delegate_mut().contacts.insert(0, Rc::new(group_contact(conversation, title)));
+                            contacts.delegate_mut().refresh(cx);
+                            cx.notify();
+                        });
+                        this.open_conversation(conversation, window, cx);
+                    });
+                    true
+                })
+        });
+    }
+}
+
+impl MainView {
+    fn update_flags(
+        &mut self,
+        conversation: ConversationId,
+        update: impl FnOnce(&mut ConversationFlags),
+        cx: &mut Context<Self>,
+    ) {
+        let store = ChatStore::global(cx);
+        store.update(cx, |store, cx| {
+            let mut flags = store.flags(conversation);
+            update(&mut flags);
+            store.set_flags(conversation, flags, cx);
+        });
+    }
+
+    fn mark_read(&mut self, action: &MarkRead, _: &mut Window, cx: &mut Context<Self>) {
+        let store = ChatStore::global(cx);
+        store.update(cx, |store, cx| store.mark_read(action.0, cx));
+    }
+
+    /// The menu key opens the menu of the selected contact.
+    fn show_context_menu(&mut self, _: &ShowContextMenu, window: &mut Window, cx: &mut Context<Self>) {
+        self.contacts.update(cx, |contacts, cx| {
+            let delegate = contacts.delegate();
+            let Some(conversation) = delegate
+                .selected_index
+                .and_then(|ix| delegate.contact(ix))
+                .map(|contact| ConversationId(contact.id))
+            else {
+                return;
+            };
+            let focus_handle = contacts.focus_handle(cx);
+            let menu = KeyboardMenu::new(
+                conversation,
+                window,
+                cx,
+                |contacts| &mut contacts.delegate_mut().keyboard_menu,
+                move |menu, _, cx| contact_menu(menu.action_context(focus_handle), conversation, cx),
+            );
+            contacts.delegate_mut().keyboard_menu = Some(menu);
+            cx.notify();
+        });
+    }
+
+    fn toggle_pinned(&mut self, action: &TogglePinned, _: &mut Window, cx: &mut Context<Self>) {
+        self.update_flags(action.0, |flags| flags.pinned = !flags.pinned, cx);
+    }
+
+    fn toggle_muted(&mut self, action: &ToggleMuted, _: &mut Window, cx: &mut Context<Self>) {
+        self.update_flags(action.0, |flags| flags.muted = !flags.muted, cx);
+    }
+
+    fn toggle_archived(&mut self, action: &ToggleArchived, _: &mut Window, cx: &mut Context<Self>) {
+        self.update_flags(action.0, |flags| flags.archived = !flags.archived, cx);
+    }
+
+    /// The width of the expanded contacts panel.
+    fn contacts_width(cx: &App) -> Pixels {
+        WindowState::global(cx)
+            .contacts_width
+            .unwrap_or(px(255.))
+            .clamp(CONTACTS_MIN_WIDTH, CONTACTS_MAX_WIDTH)
+    }
+
+    fn toggle_sidebar(&mut self, _: &ToggleSidebar, _: &mut Window, cx: &mut Context<Self>) {
+        let expanded = Self::contacts_width(cx);
+        let (from, to) = if self.collapsed { (RAIL_WIDTH, expanded) } else { (expanded, RAIL_WIDTH) };
+        // Toggling again halfway turns around where the panel is now.
+        let from = self.sidebar_animation.as_ref().map_or(from, |animation| animation.width());
+        self.sidebar_animation = Some(SidebarAnimation { started: Instant::now(), from, to });
+        self.collapsed = !self.collapsed;
+        let collapsed = self.collapsed;
+        WindowState::update(cx, |state| state.contacts_collapsed = collapsed);
+        cx.notify();
+    }
+
+    fn select_contact(&mut self, ix: IndexPath, conversation: ConversationId, window: &mut Window, cx: &mut Context<Self>) {
+        self.contacts.update(cx, |contacts, cx| {
+            contacts.set_selected_index(Some(ix), window, cx);
+            cx.notify();
+        });
+        self.open_conversation(conversation, window, cx);
+    }
+
+    /// Open the contact `offset` rows away from the selected one, wrapping around the list.
+    fn select_adjacent(&mut self, offset: isize, window: &mut Window, cx: &mut Context<Self>) {
+        let delegate = self.contacts.read(cx).delegate();
+        let rows = delegate.rows().collect::<Vec<_>>();
+        if rows.is_empty() {
+            return;
+        }
+        let next = match delegate.selected_index.and_then(|ix| rows.iter().position(|row| *row == ix)) {
+            Some(current) => (current as isize + offset).rem_euclid(rows.len() as isize) as usize,
+            None if offset > 0 => 0,
+            None => rows.len() - 1,
+        };
+        let ix = rows[next];
+        let Some(conversation) = delegate.contact(ix).map(|contact| ConversationId(contact.id)) else {
+            return;
+        };
+        self.contacts.update(cx, |contacts, cx| contacts.scroll_to_item(ix, ScrollStrategy::Center, window, cx));
+        self.select_contact(ix, conversation, window, cx);
+    }
+
+    fn next_conversation(&mut self, _: &Tab, window: &mut Window, cx: &mut Context<Self>) {
+        self.select_adjacent(1, window, cx);
+    }
+
+    fn previous_conversation(&mut self, _: &TabPrev, window: &mut Window, cx: &mut Context<Self>) {
+        self.select_adjacent(-1, window, cx);
+    }
+
+    fn focus_composer(&mut self, _: &FocusComposer, window: &mut Window, cx: &mut Context<Self>) {
+        self.history.update(cx, |history, cx| history.focus_composer(window, cx));
+    }
+
+    fn focus_contacts(&mut self, _: &FocusContacts, window: &mut Window, cx: &mut Context<Self>) {
+        // The rail has no list to focus.
+        if self.collapsed {
+            self.toggle_sidebar(&ToggleSidebar, window, cx);
+        }
+        window.focus(&self.contacts.focus_handle(cx));
+    }
+
+    /// The collapsed contacts panel, the avatars with a dot for unread messages.
+    fn render_rail(&self, cx: &Context<Self>) -> impl IntoElement {
+        let delegate = self.contacts.read(cx).delegate();
+        let store = ChatStore::global(cx).read(cx);
+        let items = delegate
+            .rows()
+            .filter_map(|ix| Some((ix, delegate.contact(ix)?)))
+            .map(|(ix, contact)| {
+                let conversation = ConversationId(contact.id);
+                let (unread, flags) = (store.unread(conversation), store.flags(conversation));
+                let name = contact.name.clone();
+                div()
+                    .id(("contact-rail", contact.id as u64))
+                    .p_1()
+                    .rounded(cx.theme().radius)
+                    .when(delegate.selected_index == Some(ix), |this| this.bg(cx.theme().list_active))
+                    .hover(|this| this.bg(cx.theme().list_hover))
+                    .child(
+                        Badge::new()
+                            .when(unread.messages > 0, |this| this.dot())
+                            .when(flags.muted, |this| this.color(cx.theme().muted_foreground))
+                            .child(
+                                Avatar::new()
+                                    .name(contact.name.clone())
+                                    .when_some(contact.avatar.clone(), |this, avatar| this.src(avatar)),
+                            ),
+                    )
+                    .tooltip(move |window, cx| Tooltip::new(name.clone()).build(window, cx))
+                    .on_click(cx.listener(move |this, _, window, cx| this.select_contact(ix, conversation, window, cx)))
+            })
+            .collect::<Vec<_>>();
+
+        v_flex()
+            .size_full()
+            .items_center()
+            .gap_2()
+            .child(
+                Button::new("expand-contacts")
+                    .icon(IconName::PanelLeftOpen)
+                    .small()
+                    .ghost()
+                    .tooltip_with_action("Expand Contacts", &ToggleSidebar, None)
+                    .on_click(cx.listener(|this, _, window, cx| this.toggle_sidebar(&ToggleSidebar, window, cx))),
+            )
+            .child(
+                v_flex()
+                    .flex_1()
+                    .w_full()
+                    .items_center()
+                    .gap_1()
+                    .overflow_y_scrollbar()
+                    .children(items),
+            )
+    }
+
+    fn toggle_archived_view(&mut self, cx: &mut Context<Self>) {
+        self.contacts.update(cx, |contacts, cx| {
+            let delegate = contacts.delegate_mut();
+            delegate.show_archived = !delegate.show_archived;
+            delegate.refresh(cx);
+            cx.notify();
+        });
+        cx.notify();
+    }
+}
+
 impl Render for MainView {
     fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
-        
-        h_resizable("gallery-container")
+        let delegate = self.contacts.read(cx).delegate();
+        let (show_archived, archived_count) = (delegate.show_archived, delegate.archived_count(cx));
+
+        let animating = self
+            .sidebar_animation
+            .as_ref()
+            .filter(|animation| animation.progress() < 1.)
+            .map(|animation| animation.width());
+        let width_range = match animating {
+            Some(width) => {
+                window.request_animation_frame();
+                width..width
+            }
+            None => {
+                if self.sidebar_animation.take().is_some() && !self.collapsed {
+                    self.sidebar_generation += 1;
+                }
+                if self.collapsed {
+                    RAIL_WIDTH..RAIL_WIDTH
+                } else {
+                    CONTACTS_MIN_WIDTH..CONTACTS_MAX_WIDTH
+                }
+            }
+        };
+        let collapsed = self.collapsed;
+
+        div()
+            .size_full()
+            .on_action(cx.listener(Self::toggle_sidebar))
+            .on_action(cx.listener(Self::next_conversation))
+            .on_action(cx.listener(Self::previous_conversation))
+            .on_action(cx.listener(Self::focus_composer))
+            .on_action(cx.listener(Self::focus_contacts))
             .child(
-                resizable_panel()
-                    .size(px(255.))
-                    .size_range(px(160.)..px(360.))
-                    .child(
-                        List::new(&self.contacts)
-                            .p(px(8.))
-                            .flex_1()
-                            .w_full()
-                            .border_1()
-                            .border_color(cx.theme().border)
-                            .rounded(cx.theme().radius),
+            h_resizable(("gallery-container", self.sidebar_generation))
+                .on_resize(move |state, _, cx| {
+                    // The rail is not a width to come back to.
+                    if collapsed {
+                        return;
+                    }
+                    if let Some(width) = state.read(cx).sizes().first().copied() {
+                        WindowState::update(cx, |state| state.contacts_width = Some(width));
+                    }
+                })
+                .child(
+                    resizable_panel()
+                        .size(Self::contacts_width(cx))
+                        .size_range(width_range)
+                        .when(collapsed, |this| this.child(self.render_rail(cx)))
+                        .when(!collapsed, |this| this.child(
+                            v_flex()
+                            .size_full()
+                            .gap_2()
+                            .on_action(cx.listener(Self::mark_read))
+                            .on_action(cx.listener(Self::show_context_menu))
+                            .on_action(cx.listener(Self::toggle_pinned))
+                            .on_action(cx.listener(Self::toggle_muted))
+                            .on_action(cx.listener(Self::toggle_archived))
+                            .child(
+                                h_flex()
+                                .gap_2()
+                                .child(
+                                    Button::new("collapse-contacts")
+                                        .icon(IconName::PanelLeftClose)
+                                        .small()
+                                        .ghost()
+                                        .tooltip_with_action("Collapse Contacts", &ToggleSidebar, None)
+                                        .on_click(cx.listener(|this, _, window, cx| this.toggle_sidebar(&ToggleSidebar, window, cx))),
+                                )
+                                .child(
+                                    Button::new("new-group")
+                                        .label("New Group")
+                                        .small()
+                                        .on_click(cx.listener(|this, _, window, cx| this.new_group(window, cx))),
+                                )
+                                .child(
+                                    Button::new("archived")
+                                        .label(if show_archived {
+                                            "Back to Chats".to_string()
+                                        } else {
+                                            format!("Archived ({})", archived_count)
+                                        })
+                                        .small()
+                                        .ghost()
+                                        .on_click(cx.listener(|this, _, _, cx| this.toggle_archived_view(cx))),
+                                )
+                            )
+                            .child(
+                                List::new(&self.contacts)
+                                    .p(px(8.))
+                                    .flex_1()
+                                    .w_full()
+                                    .border_1()
+                                    .border_color(cx.theme().border)
+                                    .rounded(cx.theme().radius),
+                            )
+                        ))
                     )
+                .child(
+                    self.history.clone().into_any_element(),
                 )
-            .child(
-                self.history.clone().into_any_element(),
             )
         // div().bg(theme.blue)
     }
 }
 
+/// The narrowest the web panel gets, pages are rarely usable below it.
+const WEB_PANEL_MIN_WIDTH: Pixels = px(320.);
+
 pub struct MainWindow{
     title_bar: Entity<AppTitleBar>,
     view: AnyView,
-    webview: Entity<WebView>,
+    web_panel: Entity<WebPanel>,
+    _subscriptions: Vec<Subscription>,
 }
 
 impl MainWindow {
@@ -366,102 +970,55 @@ impl MainWindow {
     ) -> Self {
         let title_bar = cx.new(|cx| AppTitleBar::new(title, window, cx));
         let view = cx.new(|cx| MainView::new(window, cx));
+        let web_panel = cx.new(|cx| WebPanel::new(window, cx));
 
-        let webview = cx.new(|cx| {
-            let builder = wry::WebViewBuilder::new().with_url("https://www.baidu.com");
-            #[cfg(any(debug_assertions, feature = "inspector"))]
-            let builder = builder.with_devtools(true);
-
-            #[cfg(not(any(
-                target_os = "windows",
-                target_os = "macos",
-                target_os = "ios",
-                target_os = "android"
-            )))]
-            let webview = {
-                use gtk::prelude::*;
-                use wry::WebViewBuilderExtUnix;
-                // borrowed from https://github.com/tauri-apps/wry/blob/dev/examples/gtk_multiwebview.rs
-                // doesn't work yet
-                // TODO: How to initialize this fixed?
-                let fixed = gtk::Fixed::builder().build();
-                fixed.show_all();
-                builder.build_gtk(&fixed).unwrap()
-            };
-            #[cfg(any(
-                target_os = "windows",
-                target_os = "macos",
-                target_os = "ios",
-                target_os = "android"
-            ))]
-            let webview = {
-                use raw_window_handle::HasWindowHandle;
-
-                let window_handle = window.window_handle().expect("No window handle");
-                builder.build_as_child(&window_handle).unwrap()
-            };
-
-            WebView::new(webview, window, cx)
-        });
+        let _subscriptions = vec![
+            WindowState::observe_window(window, cx),
+            // The web panel is opened and closed through the window state.
+            cx.observe_global::<WindowState>(|_, cx| cx.notify()),
+        ];
 
         Self {
             title_bar,
             view: view.into(),
-            webview: webview
+            web_panel,
+            _subscriptions,
         }
     }
 }
 
-const HTML_CONTENT: &str = r#"
-<!DOCTYPE html>
-<html>
-<head>
-    <title>Local Content</title>
-    <style>
-        body { font-family: Arial, sans-serif; padding: 20px; }
-        .highlight { background-color: yellow; }
-    </style>
-</head>
-<body>
-    <h1>Hello from Local HTML</h1>
-    <p class="highlight">This content is loaded locally!</p>
-    <script>
-        console.log('Local HTML loaded successfully');
-    </script>
-</body>
-</html>
-"#;
-
 impl Render for MainWindow {
     fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         let dialog_layer = Root::render_dialog_layer(window, cx);
         let sheet_layer = Root::render_sheet_layer(window, cx);
         let notification_layer = Root::render_notification_layer(window, cx);
-        
+        let state = WindowState::global(cx);
+        let web_panel_width = state.web_panel_width.unwrap_or(px(480.)).max(WEB_PANEL_MIN_WIDTH);
+
         div()
             .size_full()
             .child(
                 v_flex()
                     .size_full()
                     .child(self.title_bar.clone())
-                    .child(div().flex_1().overflow_hidden().child(self.view.clone()))
-                    // .child(div().flex_1().overflow_hidden().child("Hello, World!"))
-                    //webview
-                    .child(Button::new("go").label("go web").on_click(cx.listener(|this, _, _, cx| {
-                        // let webview = this.webview.clone();
-                        this.webview.update(cx, |view, _| {
-                            view.show();
-                            view.load_html(HTML_CONTENT).unwrap();
-                        });
-                    })))
                     .child(
-                        div()
-                        .flex_1()
-                        .size_full()
-                        .bg(cx.theme().red)
-                        .child(self.webview.clone())
-                    )
-                     
+                        div().flex_1().overflow_hidden().child(
+                            h_resizable("web-container")
+                                .on_resize(|state, _, cx| {
+                                    if let Some(width) = state.read(cx).sizes().get(1).copied() {
+                                        WindowState::update(cx, |state| state.web_panel_width = Some(width));
+                                    }
+                                })
+                                .child(resizable_panel().child(self.view.clone()))
+                                .child(
+                                    resizable_panel()
+                                        .visible(state.web_panel_open)
+                                        .size(web_panel_width)
+                                        .size_range(WEB_PANEL_MIN_WIDTH..Pixels::MAX)
+                                        .child(self.web_panel.clone()),
+                                ),
+                        ),
+                    ),
             )
             .children(dialog_layer)
             .children(sheet_layer)
@@ -469,9 +1026,6 @@ impl Render for MainWindow {
     }
 }
 
-
-
-
 fn main() {
     // Required this for Windows to render the WebView.
     #[cfg(target_os = "windows")]
@@ -484,23 +1038,18 @@ fn main() {
     app.run(move |cx| {
         // This must be called before using any GPUI Component features.
         gpui_component::init(cx);
+        agpui::init(cx);
 
-        let window_size: Option<gpui::Size<Pixels>> = None;
-            let mut window_size = window_size.unwrap_or(size(px(1600.0), px(1200.0)));
-            if let Some(display) = cx.primary_display() {
-                let display_size = display.bounds().size;
-                window_size.width = window_size.width.min(display_size.width * 0.85);
-                window_size.height = window_size.height.min(display_size.height * 0.85);
-            }
-        let window_bounds = Bounds::centered(None, window_size, cx);
+        let (window_bounds, display_id) = WindowState::global(cx).window_bounds(cx);
 
         cx.spawn(async move |cx| {
             let options = WindowOptions {
-                window_bounds: Some(WindowBounds::Windowed(window_bounds)),
+                window_bounds: Some(window_bounds),
+                display_id,
                 titlebar: Some(TitleBar::title_bar_options()),
                 window_min_size: Some(gpui::Size {
-                    width: px(480.),
-                    height: px(320.),
+                    width: px(WindowSettings::MIN_WIDTH),
+                    height: px(WindowSettings::MIN_HEIGHT),
                 }),
                 kind: WindowKind::Normal,
                 #[cfg(target_os = "linux")]
@@ -527,6 +1076,9 @@ fn main() {
             })
             .expect("failed to update window");
 
+            // The contacts are known once the main window is open.
+            cx.update(PopOuts::restore)?;
+
             Ok::<_, anyhow::Error>(())
         })
         .detach();
@@ -0,0 +1,278 @@
+use gpui::{AppContext as _, Context, Entity, IntoElement, ParentElement as _, Render, Styled as _, Subscription, Window, prelude::FluentBuilder as _, px};
+use gpui_component::{ActiveTheme as _, Disableable as _, Sizable as _, button::{Button, ButtonVariants as _}, checkbox::Checkbox, h_flex, input::{Input, InputState}, label::Label, scroll::ScrollableElement as _, v_flex};
+
+use crate::chat::{ChatStore, ConversationId, UserId};
+
+/// Groups need at least this many members besides us.
+const MIN_GROUP_USERS: usize = 2;
+
+fn toggle(selected: &mut Vec<UserId>, user: UserId, checked: bool) {
+    selected.retain(|id| *id != user);
+    if checked {
+        selected.push(user);
+    }
+}
+
+/// The members of a group, shown in a side sheet.
+///
+/// Admins can add and remove members and hand out the admin role, everyone can leave.
+pub struct MembersView {
+    store: Entity<ChatStore>,
+    conversation: ConversationId,
+    /// The users picked to be added, `None` unless adding members.
+    adding: Option<Vec<UserId>>,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl MembersView {
+    pub fn new(conversation: ConversationId, cx: &mut Context<Self>) -> Self {
+        let store = ChatStore::global(cx);
+        let _subscriptions = vec![cx.observe(&store, |_, _, cx| cx.notify())];
+
+        Self {
+            store,
+            conversation,
+            adding: None,
+            _subscriptions,
+        }
+    }
+
+    fn add_selected(&mut self, cx: &mut Context<Self>) {
+        let Some(users) = self.adding.take() else {
+            return;
+        };
+        let conversation = self.conversation;
+        self.store.update(cx, |store, cx| store.add_members(conversation, users, cx));
+        cx.notify();
+    }
+
+    fn render_candidates(&self, selected: &[UserId], cx: &Context<Self>) -> impl IntoElement {
+        let store = self.store.read(cx);
+        let candidates = store
+            .users()
+            .into_iter()
+            .filter(|user| !store.is_member(self.conversation, *user))
+            .collect::<Vec<_>>();
+
+        v_flex()
+            .gap_2()
+            .p_2()
+            .rounded(cx.theme().radius)
+            .border_1()
+            .border_color(cx.theme().border)
+            .child(
+                v_flex()
+                    .max_h(px(240.))
+                    .gap_1()
+                    .overflow_y_scrollbar()
+                    .children(candidates.into_iter().map(|user| {
+                        Checkbox::new(("add-member", user as u64))
+                            .label(store.name(user))
+                            .checked(selected.contains(&user))
+                            .on_click(cx.listener(move |this, checked: &bool, _, cx| {
+                                if let Some(adding) = this.adding.as_mut() {
+                                    toggle(adding, user, *checked);
+                                }
+                                cx.notify();
+                            }))
+                    })),
+            )
+            .child(
+                h_flex()
+                    .gap_2()
+                    .justify_end()
+                    .child(
+                        Button::new("cancel-add-members")
+                            .label("Cancel")
+                            .small()
+                            .ghost()
+                            .on_click(cx.listener(|this, _, _, cx| {
+                                this.adding = None;
+                                cx.notify();
+                            })),
+                    )
+                    .child(
+                        Button::new("add-members")
+                            .label("Add")
+                            .small()
+                            .primary()
+                            .disabled(selected.is_empty())
+                            .on_click(cx.listener(|this, _, _, cx| this.add_selected(cx))),
+                    ),
+            )
+    }
+
+    fn render_member(&self, user: UserId, me: UserId, i_am_admin: bool, cx: &Context<Self>) -> impl IntoElement {
+        let store = self.store.read(cx);
+        let admin = store.is_admin(self.conversation, user);
+        let name = if user == me {
+            format!("{} (You)", store.name(user))
+        } else {
+            store.name(user).to_string()
+        };
+
+        h_flex()
+            .gap_2()
+            .py_1()
+            .child(Label::new(name).flex_1().text_sm())
+            .when(admin, |this| {
+                this.child(
+                    Label::new("Admin")
+                        .text_xs()
+                        .text_color(cx.theme().muted_foreground),
+                )
+            })
+            .when(i_am_admin && user != me, |this| {
+                this.child(
+                    Button::new(("toggle-admin", user as u64))
+                        .label(if admin { "Remove Admin" } else { "Make Admin" })
+                        .xsmall()
+                        .ghost()
+                        .on_click(cx.listener(move |this, _, _, cx| {
+                            let conversation = this.conversation;
+                            this.store
+                                .update(cx, |store, cx| store.set_admin(conversation, user, !admin, cx));
+                        })),
+                )
+                .child(
+                    Button::new(("remove-member", user as u64))
+                        .label("Remove")
+                        .xsmall()
+                        .danger()
+                        .on_click(cx.listener(move |this, _, _, cx| {
+                            let conversation = this.conversation;
+                            this.store
+                                .update(cx, |store, cx| store.remove_member(conversation, user, cx));
+                        })),
+                )
+            })
+    }
+}
+
+impl Render for MembersView {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let store = self.store.read(cx);
+        let me = store.me();
+        let members = store.members(self.conversation).to_vec();
+        let i_am_member = store.is_member(self.conversation, me);
+        let i_am_admin = store.is_admin(self.conversation, me);
+
+        v_flex()
+            .size_full()
+            .gap_3()
+            .child(
+                h_flex()
+                    .gap_2()
+                    .child(
+                        Label::new(format!("{} members", members.len()))
+                            .flex_1()
+                            .text_sm()
+                            .text_color(cx.theme().muted_foreground),
+                    )
+                    .when(i_am_admin && self.adding.is_none(), |this| {
+                        this.child(
+                            Button::new("start-add-members")
+                                .label("Add Members")
+                                .small()
+                                .outline()
+                                .on_click(cx.listener(|this, _, _, cx| {
+                                    this.adding = Some(vec![]);
+                                    cx.notify();
+                                })),
+                        )
+                    }),
+            )
+            .children(
+                self.adding
+                    .as_ref()
+                    .map(|selected| self.render_candidates(selected, cx)),
+            )
+            .child(
+                v_flex()
+                    .flex_1()
+                    .overflow_y_scrollbar()
+                    .children(
+                        members
+                            .into_iter()
+                            .map(|user| self.render_member(user, me, i_am_admin, cx)),
+                    ),
+            )
+            .when(i_am_member, |this| {
+                this.child(
+                    Button::new("leave-group")
+                        .label("Leave Group")
+                        .danger()
+                        .on_click(cx.listener(|this, _, _, cx| {
+                            let conversation = this.conversation;
+                            this.store.update(cx, |store, cx| store.leave_group(conversation, cx));
+                        })),
+                )
+            })
+    }
+}
+
+/// The form of the "New Group" dialog: a title and the contacts to start the group with.
+pub struct NewGroupForm {
+    title: Entity<InputState>,
+    selected: Vec<UserId>,
+}
+
+impl NewGroupForm {
+    pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let title = cx.new(|cx| InputState::new(window, cx).placeholder("Group name"));
+        Self {
+            title,
+            selected: vec![],
+        }
+    }
+
+    fn is_valid(&self, cx: &Context<Self>) -> bool {
+        !self.title.read(cx).value().trim().is_empty() && self.selected.len() >= MIN_GROUP_USERS
+    }
+
+    /// Create the group, `None` when the form is not complete yet.
+    pub fn create(&mut self, cx: &mut Context<Self>) -> Option<ConversationId> {
+        if !self.is_valid(cx) {
+            return None;
+        }
+
+        let title = self.title.read(cx).value().trim().to_string();
+        let users = self.selected.clone();
+        let store = ChatStore::global(cx);
+        Some(store.update(cx, |store, cx| store.create_group(title, users, cx)))
+    }
+}
+
+impl Render for NewGroupForm {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let store = ChatStore::global(cx).read(cx);
+
+        v_flex()
+            .gap_3()
+            .child(Input::new(&self.title))
+            .child(
+                Label::new(format!(
+                    "Pick at least {} contacts, {} selected",
+                    MIN_GROUP_USERS,
+                    self.selected.len()
+                ))
+                .text_xs()
+                .text_color(cx.theme().muted_foreground),
+            )
+            .child(
+                v_flex()
+                    .max_h(px(320.))
+                    .gap_1()
+                    .overflow_y_scrollbar()
+                    .children(store.users().into_iter().map(|user| {
+                        Checkbox::new(("new-group-member", user as u64))
+                            .label(store.name(user))
+                            .checked(self.selected.contains(&user))
+                            .on_click(cx.listener(move |this, checked: &bool, _, cx| {
+                                toggle(&mut this.selected, user, *checked);
+                                cx.notify();
+                            }))
+                    })),
+            )
+    }
+}
@@ -0,0 +1,194 @@
+use std::ops::Range;
+
+use anyhow::Result;
+use gpui::{Context, SharedString, Task, Window};
+use gpui_component::{Rope, RopeExt as _, input::{CompletionProvider, InputState}};
+use lsp_types::{CompletionContext, CompletionItem, CompletionResponse, CompletionTextEdit, TextEdit};
+
+use crate::{
+    chat::{ChatStore, ConversationId, UserId},
+    emoji::ShortcodeCompletionProvider,
+};
+
+fn starts_token(text: &str, ix: usize) -> bool {
+    text[..ix].chars().next_back().is_none_or(|c| c.is_whitespace())
+}
+
+fn ends_token(text: &str, ix: usize) -> bool {
+    text[ix..].chars().next().is_none_or(|c| !c.is_alphanumeric() && c != '_')
+}
+
+/// The `@name` tokens in `text` naming one of `members`, with their byte ranges.
+///
+/// Mentions are kept as plain `@name` text so they survive editing, the
+/// longest matching name wins when one name is a prefix of another.
+pub fn find_mentions(text: &str, members: &[(UserId, SharedString)]) -> Vec<(Range<usize>, UserId)> {
+    let mut mentions = vec![];
+    let mut cursor = 0;
+    while let Some(at) = text[cursor..].find('@').map(|ix| cursor + ix) {
+        cursor = at + 1;
+        if !starts_token(text, at) {
+            continue;
+        }
+        let rest = &text[at + 1..];
+        let found = members
+            .iter()
+            .filter(|(_, name)| !name.is_empty() && rest.starts_with(name.as_ref()))
+            .filter(|(_, name)| ends_token(text, at + 1 + name.len()))
+            .max_by_key(|(_, name)| name.len());
+        if let Some((user, name)) = found {
+            let end = at + 1 + name.len();
+            mentions.push((at..end, *user));
+            cursor = end;
+        }
+    }
+    mentions
+}
+
+/// Completes `@name` to the members of a group conversation while typing.
+///
+/// An input has a single completion provider, so this one also completes emoji shortcodes.
+pub struct MentionCompletionProvider {
+    conversation: ConversationId,
+}
+
+impl MentionCompletionProvider {
+    pub fn new(conversation: ConversationId) -> Self {
+        Self { conversation }
+    }
+
+    /// The byte offset of the `@` starting the mention being typed before `offset`, and the query after it.
+    ///
+    /// The query may hold spaces while it still starts the name of one of `members`, so
+    /// full names like "John Smith" can be completed.
+    fn query_at<'a>(text: &'a str, offset: usize, members: &[(UserId, SharedString)]) -> Option<(usize, &'a str)> {
+        let before = &text[..offset];
+        let start = before.rfind('@')?;
+        let query = &before[start + 1..];
+        if !starts_token(before, start) || query.contains('\n') {
+            return None;
+        }
+        if !query.contains(char::is_whitespace) {
+            return Some((start, query));
+        }
+        let lowercase = query.to_lowercase();
+        members
+            .iter()
+            .any(|(_, name)| name.to_lowercase().starts_with(&lowercase))
+            .then_some((start, query))
+    }
+
+    fn completions_at(
+        text: &Rope,
+        offset: usize,
+        members: Vec<(UserId, SharedString)>,
+    ) -> Option<Vec<CompletionItem>> {
+        let content = text.to_string();
+        let (start, query) = Self::query_at(&content, offset, &members)?;
+        let range = lsp_types::Range::new(text.offset_to_position(start), text.offset_to_position(offset));
+        let query = query.to_lowercase();
+
+        let mut members = members
+            .into_iter()
+            .filter(|(_, name)| name.to_lowercase().contains(&query))
+            .collect::<Vec<_>>();
+        members.sort_by_key(|(_, name)| !name.to_lowercase().starts_with(&query));
+
+        Some(
+            members
+                .into_iter()
+                .take(10)
+                .map(|(_, name)| {
+                    let label = format!("@{}", name);
+                    // The menu highlights as many bytes of the label as the filter text has.
+                    let matched = query.len() + 1;
+                    let filter_text = if label.to_lowercase().starts_with(&format!("@{}", query))
+                        && label.is_char_boundary(matched)
+                    {
+                        label[..matched].to_string()
+                    } else {
+                        String::new()
+                    };
+                    CompletionItem {
+                        filter_text: Some(filter_text),
+                        text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                            range,
+                            new_text: format!("{} ", label),
+                        })),
+                        label,
+                        ..Default::default()
+                    }
+                })
+                .collect(),
+        )
+    }
+}
+
+impl CompletionProvider for MentionCompletionProvider {
+    fn completions(
+        &self,
+        text: &Rope,
+        offset: usize,
+        _: CompletionContext,
+        _: &mut Window,
+        cx: &mut Context<InputState>,
+    ) -> Task<Result<CompletionResponse>> {
+        let store = ChatStore::global(cx).read(cx);
+        let items = if store.is_group(self.conversation) {
+            let me = store.me();
+            let members = store
+                .member_names(self.conversation)
+                .into_iter()
+                .filter(|(id, _)| *id != me)
+                .collect();
+            Self::completions_at(text, offset, members)
+        } else {
+            None
+        };
+        let items = items.unwrap_or_else(|| ShortcodeCompletionProvider::completions_at(text, offset));
+        Task::ready(Ok(CompletionResponse::Array(items)))
+    }
+
+    /// A space may continue a full name, whether it does is up to [`Self::completions`].
+    fn is_completion_trigger(&self, _: usize, new_text: &str, _: &mut Context<InputState>) -> bool {
+        !new_text.is_empty() && !new_text.contains('\n')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn members() -> Vec<(UserId, SharedString)> {
+        vec![(1, "John Smith".into()), (2, "Jane Doe".into())]
+    }
+
+    fn query(text: &str) -> Option<(usize, &str)> {
+        MentionCompletionProvider::query_at(text, text.len(), &members())
+    }
+
+    #[test]
+    fn a_query_may_continue_a_full_name() {
+        assert_eq!(query("hi @john"), Some((3, "john")));
+        assert_eq!(query("hi @John S"), Some((3, "John S")));
+        assert_eq!(query("hi @jane doe"), Some((3, "jane doe")));
+    }
+
+    #[test]
+    fn a_space_ends_a_query_matching_no_name() {
+        assert_eq!(query("hi @John X"), None);
+        assert_eq!(query("hi @John Smith "), None);
+        assert_eq!(query("@John\nS"), None);
+    }
+
+    #[test]
+    fn a_query_starts_a_token() {
+        assert_eq!(query("mail@john"), None);
+    }
+
+    #[test]
+    fn full_names_are_found_in_text() {
+        let mentions = find_mentions("ask @John Smith and @Jane", &members());
+        assert_eq!(mentions, vec![(4..15, 1)]);
+    }
+}
@@ -0,0 +1,156 @@
+use gpui::{Context, Entity, InteractiveElement as _, IntoElement, ParentElement as _, Render, StatefulInteractiveElement as _, Styled as _, Subscription, Window, div, prelude::FluentBuilder as _};
+use gpui_component::{ActiveTheme as _, Disableable as _, Sizable as _, StyledExt as _, WindowExt as _, button::{Button, ButtonVariants as _}, h_flex, label::Label, scroll::ScrollableElement as _, v_flex};
+
+use crate::{
+    chat::{ChatStore, ConversationId},
+    notifications::{LoggedNotification, NotificationLog, Notifications},
+};
+
+/// Past notifications grouped by conversation, shown in a side sheet behind the bell.
+pub struct NotificationCenter {
+    log: Entity<NotificationLog>,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl NotificationCenter {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        let log = Notifications::log(cx);
+        let _subscriptions = vec![cx.observe(&log, |_, _, cx| cx.notify())];
+
+        Self { log, _subscriptions }
+    }
+
+    fn open_source(&mut self, entry: &LoggedNotification, window: &mut Window, cx: &mut Context<Self>) {
+        let (id, conversation, message) = (entry.id, entry.conversation, entry.message);
+        self.log.update(cx, |log, cx| log.mark_notification_read(id, cx));
+        window.close_sheet(cx);
+        ChatStore::global(cx).update(cx, |store, cx| store.reveal(conversation, Some(message), cx));
+    }
+
+    /// The conversations with notifications, the one notified last first.
+    fn groups(entries: &[LoggedNotification]) -> Vec<(ConversationId, Vec<&LoggedNotification>)> {
+        let mut groups: Vec<(ConversationId, Vec<&LoggedNotification>)> = vec![];
+        for entry in entries.iter().rev() {
+            match groups.iter_mut().find(|(conversation, _)| *conversation == entry.conversation) {
+                Some((_, group)) => group.push(entry),
+                None => groups.push((entry.conversation, vec![entry])),
+            }
+        }
+        groups
+    }
+
+    fn render_group(
+        &self,
+        conversation: ConversationId,
+        entries: Vec<&LoggedNotification>,
+        cx: &Context<Self>,
+    ) -> impl IntoElement {
+        let title = ChatStore::global(cx).read(cx).title(conversation);
+        let unread = entries.iter().any(|entry| !entry.read);
+
+        v_flex()
+            .gap_1()
+            .child(
+                h_flex()
+                    .gap_2()
+                    .child(Label::new(title).flex_1().text_sm().font_semibold())
+                    .child(
+                        Button::new(("mark-read", conversation.0 as u64))
+                            .label("Mark Read")
+                            .xsmall()
+                            .ghost()
+                            .disabled(!unread)
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                this.log.update(cx, |log, cx| log.mark_read(Some(conversation), cx));
+                            })),
+                    )
+                    .child(
+                        Button::new(("clear", conversation.0 as u64))
+                            .label("Clear")
+                            .xsmall()
+                            .ghost()
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                this.log.update(cx, |log, cx| log.clear(Some(conversation), cx));
+                            })),
+                    ),
+            )
+            .children(entries.into_iter().map(|entry| {
+                let entry = entry.clone();
+                v_flex()
+                    .id(("notification", entry.id))
+                    .px_2()
+                    .py_1()
+                    .rounded(cx.theme().radius)
+                    .when(!entry.read, |this| this.bg(cx.theme().accent))
+                    .hover(|this| this.bg(cx.theme().list_hover))
+                    .child(
+                        h_flex()
+                            .gap_2()
+                            .child(Label::new(entry.title.clone()).flex_1().text_xs())
+                            .child(
+                                Label::new(entry.at.format("%m-%d %H:%M").to_string())
+                                    .text_xs()
+                                    .text_color(cx.theme().muted_foreground),
+                            ),
+                    )
+                    .child(div().text_sm().child(entry.body.clone()))
+                    .on_click(cx.listener(move |this, _, window, cx| this.open_source(&entry, window, cx)))
+            }))
+    }
+}
+
+impl Render for NotificationCenter {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let log = self.log.read(cx);
+        let unread = log.unread_count() > 0;
+        let groups = Self::groups(log.entries());
+        let empty = groups.is_empty();
+
+        v_flex()
+            .size_full()
+            .gap_3()
+            .child(
+                h_flex()
+                    .gap_2()
+                    .justify_end()
+                    .child(
+                        Button::new("mark-all-read")
+                            .label("Mark All Read")
+                            .small()
+                            .outline()
+                            .disabled(!unread)
+                            .on_click(cx.listener(|this, _, _, cx| {
+                                this.log.update(cx, |log, cx| log.mark_read(None, cx));
+                            })),
+                    )
+                    .child(
+                        Button::new("clear-all")
+                            .label("Clear All")
+                            .small()
+                            .outline()
+                            .disabled(empty)
+                            .on_click(cx.listener(|this, _, _, cx| {
+                                this.log.update(cx, |log, cx| log.clear(None, cx));
+                            })),
+                    ),
+            )
+            .child(
+                v_flex()
+                    .flex_1()
+                    .gap_4()
+                    .overflow_y_scrollbar()
+                    .when(empty, |this| {
+                        this.child(
+                            Label::new("No notifications")
+                                .text_sm()
+                                .text_color(cx.theme().muted_foreground),
+                        )
+                    })
+                    .children(
+                        groups
+                            .into_iter()
+                            .map(|(conversation, entries)| self.render_group(conversation, entries, cx)),
+                    ),
+            )
+    }
+}
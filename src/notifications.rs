@@ -0,0 +1,579 @@
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    process::Command,
+    rc::Rc,
+    time::Duration,
+};
+
+use chrono::{DateTime, Local, NaiveTime};
+use gpui::{App, AppContext as _, Context, Entity, Global, SharedString, Task, Timer, Window};
+use gpui_component::{WindowExt as _, notification::Notification};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chat::{ChatStore, ConversationId, MessageId},
+    persist,
+    settings::SettingsStore,
+};
+
+/// A notification shown by the operating system, outside the app window.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SystemNotification {
+    pub title: SharedString,
+    pub body: SharedString,
+}
+
+/// Shows notifications outside the app window, replaced by a [`RecordingNotifier`] in tests.
+///
+/// Clicks are only reported on Linux, where `notify-send` tells which action was taken.
+/// On macOS and Windows a click brings the app forward without telling it, so nothing
+/// is revealed.
+pub trait Notifier {
+    /// Show a notification, the task resolves to whether the user clicked it.
+    fn show(&self, notification: SystemNotification, cx: &App) -> Task<bool>;
+}
+
+/// Shows notifications with the notification tool of the platform.
+#[derive(Default)]
+pub struct PlatformNotifier;
+
+impl Notifier for PlatformNotifier {
+    fn show(&self, notification: SystemNotification, cx: &App) -> Task<bool> {
+        // notify-send waits until the notification is closed, which may take as long as the
+        // user leaves it, so the tool runs on a thread of its own instead of a background worker.
+        let (clicked, receiver) = futures::channel::oneshot::channel();
+        std::thread::spawn(move || {
+            _ = clicked.send(show_platform_notification(&notification));
+        });
+        cx.background_spawn(async move { receiver.await.unwrap_or(false) })
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn show_platform_notification(notification: &SystemNotification) -> bool {
+    // Passing the texts as arguments spares quoting them inside the script.
+    _ = Command::new("osascript")
+        .args([
+            "-e",
+            "on run argv",
+            "-e",
+            "display notification (item 2 of argv) with title (item 1 of argv)",
+            "-e",
+            "end run",
+        ])
+        .arg(notification.title.as_ref())
+        .arg(notification.body.as_ref())
+        .status();
+    false
+}
+
+#[cfg(target_os = "windows")]
+fn show_platform_notification(notification: &SystemNotification) -> bool {
+    const SCRIPT: &str = r#"
+        [Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime] > $null
+        $xml = [Windows.UI.Notifications.ToastNotificationManager]::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02)
+        $texts = $xml.GetElementsByTagName('text')
+        $texts[0].AppendChild($xml.CreateTextNode($env:AGPUI_TITLE)) > $null
+        $texts[1].AppendChild($xml.CreateTextNode($env:AGPUI_BODY)) > $null
+        $toast = [Windows.UI.Notifications.ToastNotification]::new($xml)
+        [Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier('agpui').Show($toast)
+    "#;
+    _ = Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+        .env("AGPUI_TITLE", notification.title.as_ref())
+        .env("AGPUI_BODY", notification.body.as_ref())
+        .status();
+    false
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn show_platform_notification(notification: &SystemNotification) -> bool {
+    // With an action notify-send waits until the notification is closed and prints the action clicked.
+    Command::new("notify-send")
+        .args(["--app-name", "agpui", "--action", "default=Open"])
+        .arg(notification.title.as_ref())
+        .arg(notification.body.as_ref())
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).trim() == "default")
+}
+
+/// A notifier that keeps every notification in memory instead of showing it.
+#[derive(Default)]
+pub struct RecordingNotifier {
+    shown: RefCell<Vec<SystemNotification>>,
+}
+
+impl RecordingNotifier {
+    pub fn shown(&self) -> Vec<SystemNotification> {
+        self.shown.borrow().clone()
+    }
+}
+
+impl Notifier for RecordingNotifier {
+    fn show(&self, notification: SystemNotification, _: &App) -> Task<bool> {
+        self.shown.borrow_mut().push(notification);
+        Task::ready(false)
+    }
+}
+
+/// A daily period without notifications, it may run over midnight.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DoNotDisturb {
+    pub enabled: bool,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl Default for DoNotDisturb {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start: NaiveTime::from_hms_opt(22, 0, 0).unwrap_or_default(),
+            end: NaiveTime::from_hms_opt(7, 0, 0).unwrap_or_default(),
+        }
+    }
+}
+
+impl DoNotDisturb {
+    pub fn is_active(&self, now: NaiveTime) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        if self.start <= self.end {
+            self.start <= now && now < self.end
+        } else {
+            now >= self.start || now < self.end
+        }
+    }
+}
+
+/// The most notifications kept in the log, older ones are dropped.
+const LOG_LIMIT: usize = 500;
+
+/// Messages may arrive in bursts, the log is saved once they settle.
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// A notification shown earlier, kept for the notification center.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LoggedNotification {
+    pub id: u64,
+    pub conversation: ConversationId,
+    pub message: MessageId,
+    pub title: SharedString,
+    pub body: SharedString,
+    pub at: DateTime<Local>,
+    pub read: bool,
+}
+
+/// Every notification shown, oldest first, saved to a JSON file shortly after every change.
+pub struct NotificationLog {
+    path: PathBuf,
+    entries: Vec<LoggedNotification>,
+    next_id: u64,
+    _save_task: Task<()>,
+}
+
+impl NotificationLog {
+    /// Load the notifications saved in `path`, a file that cannot be read is kept aside.
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let entries = persist::load::<Vec<LoggedNotification>>(&path);
+        let next_id = entries.iter().map(|entry| entry.id + 1).max().unwrap_or(1);
+
+        Self {
+            path,
+            entries,
+            next_id,
+            _save_task: Task::ready(()),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn entries(&self) -> &[LoggedNotification] {
+        &self.entries
+    }
+
+    pub fn unread_count(&self) -> usize {
+        self.entries.iter().filter(|entry| !entry.read).count()
+    }
+
+    fn push(
+        &mut self,
+        conversation: ConversationId,
+        message: MessageId,
+        title: SharedString,
+        body: SharedString,
+        cx: &mut Context<Self>,
+    ) {
+        self.entries.push(LoggedNotification {
+            id: self.next_id,
+            conversation,
+            message,
+            title,
+            body,
+            at: Local::now(),
+            read: false,
+        });
+        self.next_id += 1;
+        if self.entries.len() > LOG_LIMIT {
+            self.entries.drain(..self.entries.len() - LOG_LIMIT);
+        }
+        self.changed(cx);
+    }
+
+    /// Mark the notifications of a conversation read, or all of them without one.
+    pub fn mark_read(&mut self, conversation: Option<ConversationId>, cx: &mut Context<Self>) {
+        let mut changed = false;
+        for entry in &mut self.entries {
+            if !entry.read && conversation.is_none_or(|conversation| entry.conversation == conversation) {
+                entry.read = true;
+                changed = true;
+            }
+        }
+        if changed {
+            self.changed(cx);
+        }
+    }
+
+    pub fn mark_notification_read(&mut self, id: u64, cx: &mut Context<Self>) {
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.id == id && !entry.read) {
+            entry.read = true;
+            self.changed(cx);
+        }
+    }
+
+    /// Remove the notifications of a conversation, or all of them without one.
+    pub fn clear(&mut self, conversation: Option<ConversationId>, cx: &mut Context<Self>) {
+        let len = self.entries.len();
+        self.entries
+            .retain(|entry| conversation.is_some_and(|conversation| entry.conversation != conversation));
+        if self.entries.len() != len {
+            self.changed(cx);
+        }
+    }
+
+    /// Notify the observers and save the log once the changes settle.
+    fn changed(&mut self, cx: &mut Context<Self>) {
+        cx.notify();
+        self._save_task = cx.spawn(async move |this, cx| {
+            Timer::after(SAVE_DELAY).await;
+            _ = this.read_with(cx, |this, _| this.save());
+        });
+    }
+
+    fn save(&self) {
+        persist::save(&self.path, &self.entries);
+    }
+}
+
+/// Toasts for a conversation replace each other instead of piling up.
+struct MessageNotification;
+
+/// How incoming messages are announced.
+pub struct Notifications {
+    notifier: Rc<dyn Notifier>,
+    log: Entity<NotificationLog>,
+}
+
+impl Global for Notifications {}
+
+impl Notifications {
+    pub fn init(cx: &mut App) {
+        let path = SettingsStore::get(cx).storage.data_dir().join("notifications.json");
+        let log = cx.new(|_| NotificationLog::load(path));
+        cx.on_app_quit({
+            let log = log.clone();
+            move |cx| {
+                log.read(cx).save();
+                async {}
+            }
+        })
+        .detach();
+        cx.set_global(Self {
+            notifier: Rc::new(PlatformNotifier),
+            log,
+        });
+    }
+
+    /// The notifications shown so far.
+    pub fn log(cx: &App) -> Entity<NotificationLog> {
+        Self::global(cx).log.clone()
+    }
+
+    pub fn global(cx: &App) -> &Self {
+        cx.global::<Self>()
+    }
+
+    pub fn global_mut(cx: &mut App) -> &mut Self {
+        cx.global_mut::<Self>()
+    }
+
+    pub fn set_notifier(&mut self, notifier: Rc<dyn Notifier>) {
+        self.notifier = notifier;
+    }
+
+    /// Announce a message received in `conversation`, `open` is the conversation shown in `window`.
+    ///
+    /// A toast is shown while the window is active and the message is in another
+    /// conversation, otherwise the operating system shows the notification, as far as
+    /// the notification settings allow. Clicking either one reveals the message.
+    pub fn notify_message(
+        conversation: ConversationId,
+        id: MessageId,
+        open: Option<ConversationId>,
+        window: &mut Window,
+        cx: &mut App,
+    ) {
+        let store = ChatStore::global(cx);
+        let (title, body) = {
+            let store = store.read(cx);
+            let Some(message) = store.message(conversation, id) else {
+                return;
+            };
+            let settings = &SettingsStore::get(cx).notifications;
+            if store.flags(conversation).muted
+                || settings.do_not_disturb().is_active(Local::now().time())
+            {
+                return;
+            }
+            let sender = store.name(message.sender);
+            let title: SharedString = if store.is_group(conversation) {
+                format!("{} in {}", sender, store.title(conversation)).into()
+            } else {
+                sender
+            };
+            (title, message.snippet())
+        };
+
+        if window.is_window_active() && open == Some(conversation) {
+            return;
+        }
+        Self::log(cx).update(cx, |log, cx| {
+            log.push(conversation, id, title.clone(), body.clone(), cx);
+        });
+
+        let settings = &SettingsStore::get(cx).notifications;
+        if window.is_window_active() {
+            if !settings.toasts {
+                return;
+            }
+            let notification = Notification::new()
+                .id1::<MessageNotification>(("conversation", conversation.0 as u64))
+                .title(title)
+                .message(body)
+                .on_click(move |_, _, cx| {
+                    ChatStore::global(cx).update(cx, |store, cx| store.reveal(conversation, Some(id), cx));
+                });
+            window.push_notification(notification, cx);
+            return;
+        }
+        if !settings.system {
+            return;
+        }
+
+        let clicked = Self::global(cx).notifier.show(SystemNotification { title, body }, cx);
+        let handle = window.window_handle();
+        cx.spawn(async move |cx| {
+            if !clicked.await {
+                return;
+            }
+            _ = handle.update(cx, |_, window, cx| {
+                window.activate_window();
+                store.update(cx, |store, cx| store.reveal(conversation, Some(id), cx));
+            });
+        })
+        .detach();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use gpui::{Empty, TestAppContext, VisualTestContext};
+    use gpui_component::Root;
+
+    use super::*;
+    use crate::{
+        chat::NewMessage,
+        flags::ConversationFlags,
+        transport::Op,
+    };
+
+    const CONVERSATION: ConversationId = ConversationId(1);
+    const OTHER: ConversationId = ConversationId(2);
+
+    struct Setup {
+        notifier: Rc<RecordingNotifier>,
+        _dir: tempfile::TempDir,
+    }
+
+    /// The app with its data in a temporary folder and one window, which is active.
+    fn setup(cx: &mut TestAppContext) -> (Setup, &mut VisualTestContext) {
+        let dir = tempfile::tempdir().unwrap();
+        let notifier = Rc::new(RecordingNotifier::default());
+        cx.update(|cx| {
+            gpui_component::init(cx);
+            SettingsStore::init_in(dir.path(), cx);
+            ChatStore::init(cx);
+            Notifications::init(cx);
+            Notifications::global_mut(cx).set_notifier(notifier.clone());
+        });
+
+        let window = cx.add_window(|window, cx| Root::new(cx.new(|_| Empty), window, cx));
+        let cx = VisualTestContext::from_window(window.into(), cx).into_mut();
+        cx.update(|window, _| window.activate_window());
+        cx.run_until_parked();
+        (Setup { notifier, _dir: dir }, cx)
+    }
+
+    /// Receive message `id` from user 1 in `conversation` and announce it with `open` shown.
+    fn notify(conversation: ConversationId, id: u64, open: Option<ConversationId>, cx: &mut VisualTestContext) {
+        cx.update(|window, cx| {
+            ChatStore::global(cx).update(cx, |store, cx| {
+                let op = Op::Send {
+                    conversation,
+                    message: MessageId(id),
+                    sender: 1,
+                    at: Local::now(),
+                    content: NewMessage {
+                        text: "Hello".into(),
+                        ..Default::default()
+                    },
+                };
+                store.receive(op, cx);
+            });
+            Notifications::notify_message(conversation, MessageId(id), open, window, cx);
+        });
+        cx.run_until_parked();
+    }
+
+    fn logged(cx: &mut VisualTestContext) -> usize {
+        cx.update(|_, cx| Notifications::log(cx).read(cx).entries().len())
+    }
+
+    #[gpui::test]
+    fn a_muted_conversation_is_not_announced(cx: &mut TestAppContext) {
+        let (setup, cx) = setup(cx);
+        cx.update(|_, cx| {
+            ChatStore::global(cx).update(cx, |store, cx| {
+                let flags = ConversationFlags {
+                    muted: true,
+                    ..Default::default()
+                };
+                store.set_flags(CONVERSATION, flags, cx);
+            })
+        });
+
+        cx.deactivate_window();
+        notify(CONVERSATION, 1, None, cx);
+        assert!(setup.notifier.shown().is_empty());
+        assert_eq!(logged(cx), 0);
+    }
+
+    #[gpui::test]
+    fn nothing_is_announced_during_do_not_disturb(cx: &mut TestAppContext) {
+        let (setup, cx) = setup(cx);
+        let now = Local::now().time();
+        cx.update(|_, cx| {
+            SettingsStore::update(cx, |settings| {
+                let notifications = &mut settings.notifications;
+                notifications.do_not_disturb = true;
+                notifications.quiet_start = (now - Duration::hours(1)).format("%H:%M").to_string();
+                notifications.quiet_end = (now + Duration::hours(1)).format("%H:%M").to_string();
+            })
+        });
+
+        cx.deactivate_window();
+        notify(CONVERSATION, 1, None, cx);
+        assert!(setup.notifier.shown().is_empty());
+        assert_eq!(logged(cx), 0);
+    }
+
+    #[test]
+    fn do_not_disturb_may_run_over_midnight() {
+        let time = |hour| NaiveTime::from_hms_opt(hour, 0, 0).unwrap();
+        let night = DoNotDisturb {
+            enabled: true,
+            start: time(22),
+            end: time(7),
+        };
+        assert!(night.is_active(time(23)));
+        assert!(night.is_active(time(0)));
+        assert!(night.is_active(time(6)));
+        assert!(!night.is_active(time(7)));
+        assert!(!night.is_active(time(12)));
+
+        let day = DoNotDisturb {
+            start: time(9),
+            end: time(17),
+            ..night
+        };
+        assert!(day.is_active(time(9)));
+        assert!(!day.is_active(time(17)));
+        assert!(!day.is_active(time(23)));
+
+        let disabled = DoNotDisturb {
+            enabled: false,
+            ..night
+        };
+        assert!(!disabled.is_active(time(23)));
+    }
+
+    #[gpui::test]
+    fn the_open_conversation_of_the_active_window_is_not_announced(cx: &mut TestAppContext) {
+        let (setup, cx) = setup(cx);
+
+        notify(CONVERSATION, 1, Some(CONVERSATION), cx);
+        assert!(setup.notifier.shown().is_empty());
+        assert_eq!(logged(cx), 0);
+    }
+
+    #[gpui::test]
+    fn another_conversation_gets_a_toast_in_the_active_window(cx: &mut TestAppContext) {
+        let (setup, cx) = setup(cx);
+
+        notify(CONVERSATION, 1, Some(OTHER), cx);
+        assert!(setup.notifier.shown().is_empty());
+        assert_eq!(logged(cx), 1);
+    }
+
+    #[gpui::test]
+    fn a_background_window_shows_a_system_notification(cx: &mut TestAppContext) {
+        let (setup, cx) = setup(cx);
+        cx.update(|_, cx| ChatStore::global(cx).update(cx, |store, _| store.add_user(1, "Jane Doe")));
+
+        cx.deactivate_window();
+        notify(CONVERSATION, 1, Some(CONVERSATION), cx);
+        notify(OTHER, 2, Some(CONVERSATION), cx);
+        let shown = SystemNotification {
+            title: "Jane Doe".into(),
+            body: "Hello".into(),
+        };
+        assert_eq!(setup.notifier.shown(), [shown.clone(), shown]);
+        assert_eq!(logged(cx), 2);
+    }
+
+    #[gpui::test]
+    fn the_log_is_saved_once_changes_settle(cx: &mut TestAppContext) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notifications.json");
+        let log = cx.new(|_| NotificationLog::load(&path));
+        log.update(cx, |log, cx| {
+            log.push(CONVERSATION, MessageId(1), "Jane Doe".into(), "Hello".into(), cx);
+            log.push(OTHER, MessageId(2), "John Smith".into(), "Hi".into(), cx);
+            log.mark_read(Some(CONVERSATION), cx);
+        });
+        assert!(!path.exists());
+
+        cx.executor().advance_clock(SAVE_DELAY);
+        cx.run_until_parked();
+        let saved = NotificationLog::load(&path);
+        assert_eq!(saved.unread_count(), 1);
+        assert_eq!(saved.entries().iter().map(|entry| entry.id).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(saved.next_id, 3);
+    }
+}
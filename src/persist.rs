@@ -0,0 +1,69 @@
+use std::{fs, path::Path};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+/// Read a file written by [`save`], a missing file gives the default.
+///
+/// A file that cannot be read as `T` is moved aside to `<name>.bak`, so the next save
+/// does not overwrite what it held.
+pub(crate) fn load<T: DeserializeOwned + Default>(path: &Path) -> T {
+    let Ok(text) = fs::read_to_string(path) else {
+        return T::default();
+    };
+    match serde_json::from_str(&text) {
+        Ok(value) => value,
+        Err(_) => {
+            if let Some(name) = path.file_name() {
+                let mut backup = name.to_os_string();
+                backup.push(".bak");
+                _ = fs::rename(path, path.with_file_name(backup));
+            }
+            T::default()
+        }
+    }
+}
+
+/// Write `value` to `path` as JSON, creating its folder first. Failures are ignored,
+/// the data is written again with the next change.
+pub(crate) fn save<T: Serialize>(path: &Path, value: &T) {
+    if let Some(dir) = path.parent()
+        && fs::create_dir_all(dir).is_ok()
+        && let Ok(text) = serde_json::to_string_pretty(value)
+    {
+        _ = fs::write(path, text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn saved_values_load_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("value.json");
+        let value = HashMap::from([("a".to_string(), 1), ("b".to_string(), 2)]);
+        save(&path, &value);
+
+        assert_eq!(load::<HashMap<String, i32>>(&path), value);
+    }
+
+    #[test]
+    fn a_missing_file_loads_the_default() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(load::<Vec<i32>>(&dir.path().join("missing.json")), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn an_unreadable_file_is_kept_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("value.json");
+        fs::write(&path, "not json").unwrap();
+
+        assert_eq!(load::<Vec<i32>>(&path), Vec::<i32>::new());
+        assert!(!path.exists());
+        assert_eq!(fs::read_to_string(dir.path().join("value.json.bak")).unwrap(), "not json");
+    }
+}
@@ -0,0 +1,290 @@
+use std::{path::PathBuf, time::Duration};
+
+use gpui::{Action, AnyWindowHandle, App, AppContext as _, Bounds, Context, Entity, Global, IntoElement, ParentElement as _, Pixels, Render, Styled as _, Subscription, Task, Timer, Window, WindowBounds, WindowHandle, WindowKind, WindowOptions, div, px, size};
+use gpui_component::{Root, TitleBar, v_flex};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chat::{ChatStore, ConversationId},
+    history::HistoryView,
+    persist,
+    settings::{SettingsStore, WindowSettings},
+    title_bar::AppTitleBar,
+    window_state::SavedBounds,
+};
+
+/// Moving a window changes its bounds continuously, they are saved once it settles.
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// Open a conversation in a window of its own.
+#[derive(Action, Clone, PartialEq)]
+#[action(namespace = popout, no_json)]
+pub struct PopOutConversation(pub ConversationId);
+
+pub fn init(cx: &mut App) {
+    PopOuts::init(cx);
+    cx.on_action(|action: &PopOutConversation, cx| PopOuts::open(action.0, cx));
+}
+
+/// A pop-out as it is saved.
+#[derive(Serialize, Deserialize)]
+struct SavedPopOut {
+    conversation: ConversationId,
+    bounds: Option<SavedBounds>,
+}
+
+/// A conversation in a window of its own.
+struct PopOut {
+    conversation: ConversationId,
+    /// The bounds of the window, the ones it restores to when maximized.
+    bounds: Option<Bounds<Pixels>>,
+    /// The window while it is open, the pop-outs of the last run have none until restored.
+    handle: Option<WindowHandle<Root>>,
+}
+
+/// The conversations open in windows of their own, saved so they open again at the next start.
+pub struct PopOuts {
+    path: PathBuf,
+    /// In the order the windows were opened.
+    windows: Vec<PopOut>,
+    /// Set once the app quits, the windows closing then stay in the list.
+    quitting: bool,
+    _save_task: Task<()>,
+}
+
+impl Global for PopOuts {}
+
+impl PopOuts {
+    pub fn init(cx: &mut App) {
+        let path = SettingsStore::get(cx).storage.data_dir().join("popouts.json");
+        cx.set_global(Self::load(path));
+        cx.on_app_quit(|cx| {
+            let popouts = cx.global_mut::<Self>();
+            popouts.quitting = true;
+            popouts.save();
+            async {}
+        })
+        .detach();
+    }
+
+    /// Load the pop-outs saved in `path`, a file that cannot be read is kept aside.
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let windows = persist::load::<Vec<SavedPopOut>>(&path)
+            .into_iter()
+            .map(|saved| PopOut {
+                conversation: saved.conversation,
+                bounds: saved.bounds.and_then(|bounds| bounds.bounds()),
+                handle: None,
+            })
+            .collect();
+
+        Self {
+            path,
+            windows,
+            quitting: false,
+            _save_task: Task::ready(()),
+        }
+    }
+
+    pub fn global(cx: &App) -> &Self {
+        cx.global::<Self>()
+    }
+
+    /// The active window when it is a pop-out, with the conversation it shows.
+    pub fn active(cx: &App) -> Option<(WindowHandle<Root>, ConversationId)> {
+        let active = cx.active_window()?;
+        Self::global(cx).windows.iter().find_map(|popout| {
+            let handle = popout.handle.filter(|handle| AnyWindowHandle::from(*handle) == active)?;
+            Some((handle, popout.conversation))
+        })
+    }
+
+    /// The conversations in windows of their own, the open ones and those waiting to be restored.
+    pub fn conversations(&self) -> impl Iterator<Item = ConversationId> + '_ {
+        self.windows.iter().map(|popout| popout.conversation)
+    }
+
+    /// Open the pop-outs left open in the last run, those of conversations no longer known are dropped.
+    pub fn restore(cx: &mut App) {
+        let known = ChatStore::global(cx).read(cx).conversations();
+        let (restore, gone): (Vec<_>, Vec<_>) = Self::global(cx)
+            .windows
+            .iter()
+            .filter(|popout| popout.handle.is_none())
+            .map(|popout| popout.conversation)
+            .partition(|conversation| known.contains(conversation));
+
+        if !gone.is_empty() {
+            Self::update(cx, |popouts| {
+                popouts.windows.retain(|popout| !gone.contains(&popout.conversation));
+            });
+        }
+        for conversation in restore {
+            Self::open(conversation, cx);
+        }
+    }
+
+    /// Open the window of a conversation, or bring it to the front when it is open already.
+    pub fn open(conversation: ConversationId, cx: &mut App) {
+        let (handle, bounds) = Self::global(cx)
+            .windows
+            .iter()
+            .find(|popout| popout.conversation == conversation)
+            .map_or((None, None), |popout| (popout.handle, popout.bounds));
+        if let Some(handle) = handle
+            && handle.update(cx, |_, window, _| window.activate_window()).is_ok()
+        {
+            return;
+        }
+
+        let bounds = bounds.unwrap_or_else(|| Bounds::centered(None, size(px(480.), px(720.)), cx));
+        let options = WindowOptions {
+            window_bounds: Some(WindowBounds::Windowed(bounds)),
+            titlebar: Some(TitleBar::title_bar_options()),
+            window_min_size: Some(size(px(WindowSettings::MIN_WIDTH), px(WindowSettings::MIN_HEIGHT))),
+            kind: WindowKind::Normal,
+            #[cfg(target_os = "linux")]
+            window_background: gpui::WindowBackgroundAppearance::Transparent,
+            #[cfg(target_os = "linux")]
+            window_decorations: Some(gpui::WindowDecorations::Client),
+            ..Default::default()
+        };
+        let window = cx.open_window(options, |window, cx| {
+            let view = cx.new(|cx| ConversationWindow::new(conversation, window, cx));
+            cx.new(|cx| Root::new(view, window, cx))
+        });
+        let Ok(handle) = window else {
+            return;
+        };
+        Self::update(cx, |popouts| match popouts.window_mut(conversation) {
+            Some(popout) => popout.handle = Some(handle),
+            None => popouts.windows.push(PopOut {
+                conversation,
+                bounds: Some(bounds),
+                handle: Some(handle),
+            }),
+        });
+    }
+
+    /// Change the pop-outs and save them shortly after.
+    fn update(cx: &mut App, change: impl FnOnce(&mut Self)) {
+        change(cx.global_mut::<Self>());
+        let task = cx.spawn(async move |cx| {
+            Timer::after(SAVE_DELAY).await;
+            _ = cx.update(|cx| Self::global(cx).save());
+        });
+        cx.global_mut::<Self>()._save_task = task;
+    }
+
+    fn window_mut(&mut self, conversation: ConversationId) -> Option<&mut PopOut> {
+        self.windows.iter_mut().find(|popout| popout.conversation == conversation)
+    }
+
+    /// Forget the window of a conversation closed by the user.
+    fn closed(conversation: ConversationId, cx: &mut App) {
+        if Self::global(cx).quitting {
+            return;
+        }
+        Self::update(cx, |popouts| popouts.windows.retain(|popout| popout.conversation != conversation));
+    }
+
+    fn save(&self) {
+        let saved = self
+            .windows
+            .iter()
+            .map(|popout| SavedPopOut {
+                conversation: popout.conversation,
+                bounds: popout.bounds.map(SavedBounds::from),
+            })
+            .collect::<Vec<_>>();
+        persist::save(&self.path, &saved);
+    }
+}
+
+/// The window of a popped out conversation, it shares the store with the main window.
+pub struct ConversationWindow {
+    title_bar: Entity<AppTitleBar>,
+    history: Entity<HistoryView>,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl ConversationWindow {
+    pub fn new(conversation: ConversationId, window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let title = ChatStore::global(cx).read(cx).title(conversation);
+        window.set_window_title(&title);
+        let title_bar = cx.new(|cx| AppTitleBar::new(title.clone(), window, cx).window_title(title));
+        let history = cx.new(|cx| HistoryView::new(conversation, window, cx));
+
+        let _subscriptions = vec![
+            cx.observe_window_bounds(window, move |_, window, cx| {
+                let bounds = window.window_bounds().get_bounds();
+                PopOuts::update(cx, |popouts| {
+                    if let Some(popout) = popouts.window_mut(conversation) {
+                        popout.bounds = Some(bounds);
+                    }
+                });
+            }),
+            cx.on_release(move |_, cx| PopOuts::closed(conversation, cx)),
+        ];
+
+        Self {
+            title_bar,
+            history,
+            _subscriptions,
+        }
+    }
+}
+
+impl Render for ConversationWindow {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let dialog_layer = Root::render_dialog_layer(window, cx);
+        let sheet_layer = Root::render_sheet_layer(window, cx);
+        let notification_layer = Root::render_notification_layer(window, cx);
+
+        div()
+            .size_full()
+            .child(
+                v_flex()
+                    .size_full()
+                    .child(self.title_bar.clone())
+                    .child(div().flex_1().overflow_hidden().child(self.history.clone())),
+            )
+            .children(dialog_layer)
+            .children(sheet_layer)
+            .children(notification_layer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gpui::point;
+
+    use super::*;
+
+    #[test]
+    fn pop_outs_load_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("popouts.json");
+        let mut popouts = PopOuts::load(&path);
+        let bounds = Bounds::new(point(px(10.), px(20.)), size(px(480.), px(720.)));
+        popouts.windows = vec![
+            PopOut {
+                conversation: ConversationId(2),
+                bounds: Some(bounds),
+                handle: None,
+            },
+            PopOut {
+                conversation: ConversationId(-7),
+                bounds: None,
+                handle: None,
+            },
+        ];
+        popouts.save();
+
+        let loaded = PopOuts::load(&path);
+        assert_eq!(loaded.conversations().collect::<Vec<_>>(), [ConversationId(2), ConversationId(-7)]);
+        assert_eq!(loaded.windows[0].bounds, Some(bounds));
+        assert_eq!(loaded.windows[1].bounds, None);
+    }
+}
@@ -0,0 +1,462 @@
+use std::path::PathBuf;
+
+use gpui::{App, AppContext as _, Bounds, Context, Global, IntoElement, KeyBinding, ParentElement as _, Render, SharedString, Styled as _, Subscription, Window, WindowBounds, WindowHandle, WindowKind, WindowOptions, actions, div, prelude::FluentBuilder as _, px, size};
+use gpui_component::{Root, Sizable as _, Theme, ThemeMode, ThemeRegistry, TitleBar, alert::Alert, button::Button, setting::{NumberFieldOptions, SettingField, SettingGroup, SettingItem, SettingPage, Settings}, v_flex};
+
+use crate::{
+    appearance::Appearance,
+    history::SendKey,
+    settings::{AppSettings, AppearanceMode, SettingsStore, StorageSettings, WindowSettings},
+};
+
+actions!(preferences, [OpenPreferences]);
+
+pub fn init(cx: &mut App) {
+    cx.bind_keys([KeyBinding::new("secondary-,", OpenPreferences, None)]);
+    cx.on_action(|_: &OpenPreferences, cx| Preferences::open(cx));
+}
+
+/// The preferences window while it is open, there is only one.
+struct PreferencesWindow(WindowHandle<Root>);
+
+impl Global for PreferencesWindow {}
+
+/// Edits the [`SettingsStore`] in a window of its own, every change applies at once.
+pub struct Preferences {
+    _subscriptions: Vec<Subscription>,
+}
+
+impl Preferences {
+    /// Open the preferences window, or bring it to the front when it is open already.
+    pub fn open(cx: &mut App) {
+        if let Some(handle) = cx.try_global::<PreferencesWindow>().map(|window| window.0)
+            && handle.update(cx, |_, window, _| window.activate_window()).is_ok()
+        {
+            return;
+        }
+
+        let options = WindowOptions {
+            window_bounds: Some(WindowBounds::Windowed(Bounds::centered(
+                None,
+                size(px(860.), px(640.)),
+                cx,
+            ))),
+            titlebar: Some(TitleBar::title_bar_options()),
+            window_min_size: Some(size(px(640.), px(400.))),
+            kind: WindowKind::Normal,
+            #[cfg(target_os = "linux")]
+            window_background: gpui::WindowBackgroundAppearance::Transparent,
+            #[cfg(target_os = "linux")]
+            window_decorations: Some(gpui::WindowDecorations::Client),
+            ..Default::default()
+        };
+        let window = cx.open_window(options, |window, cx| {
+            window.set_window_title("Preferences");
+            let view = cx.new(|cx| Preferences::new(window, cx));
+            cx.new(|cx| Root::new(view, window, cx))
+        });
+        if let Ok(handle) = window {
+            cx.set_global(PreferencesWindow(handle));
+        }
+    }
+
+    pub fn new(_: &mut Window, cx: &mut Context<Self>) -> Self {
+        let _subscriptions = vec![
+            cx.observe_global::<SettingsStore>(|_, cx| cx.notify()),
+            cx.observe_global::<ThemeRegistry>(|_, cx| cx.notify()),
+        ];
+
+        Self { _subscriptions }
+    }
+
+    fn appearance_page(cx: &App) -> SettingPage {
+        let defaults = AppSettings::default();
+        let themes = |mode: ThemeMode| {
+            ThemeRegistry::global(cx)
+                .sorted_themes()
+                .into_iter()
+                .filter(|theme| theme.mode == mode)
+                .map(|theme| (theme.name.clone(), theme.name.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        SettingPage::new("Appearance")
+            .default_open(true)
+            .groups([
+                SettingGroup::new().title("Theme").items([
+                    SettingItem::new(
+                        "Mode",
+                        SettingField::dropdown(
+                            vec![
+                                ("system".into(), "System".into()),
+                                ("light".into(), "Light".into()),
+                                ("dark".into(), "Dark".into()),
+                            ],
+                            |cx| match SettingsStore::get(cx).appearance.mode {
+                                AppearanceMode::System => "system".into(),
+                                AppearanceMode::Light => "light".into(),
+                                AppearanceMode::Dark => "dark".into(),
+                            },
+                            |value, cx| {
+                                let mode = match value.as_ref() {
+                                    "system" => AppearanceMode::System,
+                                    "dark" => AppearanceMode::Dark,
+                                    _ => AppearanceMode::Light,
+                                };
+                                Appearance::set_mode(mode, cx);
+                            },
+                        )
+                        .default_value(match defaults.appearance.mode {
+                            AppearanceMode::System => "system",
+                            AppearanceMode::Light => "light",
+                            AppearanceMode::Dark => "dark",
+                        }),
+                    )
+                    .description("System follows the light or dark setting of the operating system."),
+                    SettingItem::new(
+                        "Light theme",
+                        SettingField::dropdown(
+                            themes(ThemeMode::Light),
+                            |cx| current_theme(ThemeMode::Light, cx),
+                            |name, cx| {
+                                SettingsStore::update(cx, |settings| settings.appearance.light_theme = Some(name));
+                            },
+                        ),
+                    ),
+                    SettingItem::new(
+                        "Dark theme",
+                        SettingField::dropdown(
+                            themes(ThemeMode::Dark),
+                            |cx| current_theme(ThemeMode::Dark, cx),
+                            |name, cx| {
+                                SettingsStore::update(cx, |settings| settings.appearance.dark_theme = Some(name));
+                            },
+                        ),
+                    ),
+                    SettingItem::new(
+                        "Theme files",
+                        SettingField::render(|_, _, cx| {
+                            let dir = Appearance::themes_dir(cx);
+                            Button::new("open-themes-folder")
+                                .label("Open Themes Folder")
+                                .small()
+                                .on_click(move |_, _, cx| {
+                                    _ = std::fs::create_dir_all(&dir);
+                                    cx.open_with_system(&dir);
+                                })
+                        }),
+                    )
+                    .description("JSON theme files in this folder are loaded as soon as they change."),
+                ]),
+                SettingGroup::new().title("Window").items([
+                    SettingItem::new(
+                        "Width",
+                        SettingField::number_input(
+                            NumberFieldOptions {
+                                min: WindowSettings::MIN_WIDTH.into(),
+                                max: 16384.,
+                                step: 10.,
+                            },
+                            |cx| SettingsStore::get(cx).window.width.into(),
+                            |width, cx| {
+                                SettingsStore::update(cx, |settings| settings.window.width = width as f32);
+                            },
+                        )
+                        .default_value(f64::from(defaults.window.width)),
+                    )
+                    .description("The size of new windows, shrunk to fit the display."),
+                    SettingItem::new(
+                        "Height",
+                        SettingField::number_input(
+                            NumberFieldOptions {
+                                min: WindowSettings::MIN_HEIGHT.into(),
+                                max: 16384.,
+                                step: 10.,
+                            },
+                            |cx| SettingsStore::get(cx).window.height.into(),
+                            |height, cx| {
+                                SettingsStore::update(cx, |settings| settings.window.height = height as f32);
+                            },
+                        )
+                        .default_value(f64::from(defaults.window.height)),
+                    ),
+                ]),
+            ])
+    }
+
+    fn notifications_page() -> SettingPage {
+        let defaults = AppSettings::default().notifications;
+
+        SettingPage::new("Notifications").groups([
+            SettingGroup::new().title("Messages").items([
+                SettingItem::new(
+                    "Toasts",
+                    SettingField::switch(
+                        |cx| SettingsStore::get(cx).notifications.toasts,
+                        |toasts, cx| {
+                            SettingsStore::update(cx, |settings| settings.notifications.toasts = toasts);
+                        },
+                    )
+                    .default_value(defaults.toasts),
+                )
+                .description("Show a toast for messages in other conversations while the window is active."),
+                SettingItem::new(
+                    "System notifications",
+                    SettingField::switch(
+                        |cx| SettingsStore::get(cx).notifications.system,
+                        |system, cx| {
+                            SettingsStore::update(cx, |settings| settings.notifications.system = system);
+                        },
+                    )
+                    .default_value(defaults.system),
+                )
+                .description("Let the operating system announce messages while the window is in the background."),
+            ]),
+            SettingGroup::new().title("Do Not Disturb").items([
+                SettingItem::new(
+                    "Enabled",
+                    SettingField::switch(
+                        |cx| SettingsStore::get(cx).notifications.do_not_disturb,
+                        |enabled, cx| {
+                            SettingsStore::update(cx, |settings| settings.notifications.do_not_disturb = enabled);
+                        },
+                    )
+                    .default_value(defaults.do_not_disturb),
+                )
+                .description("No notifications at all during the quiet hours."),
+                SettingItem::new(
+                    "From",
+                    SettingField::input(
+                        |cx| SettingsStore::get(cx).notifications.quiet_start.clone().into(),
+                        |start, cx| {
+                            SettingsStore::update(cx, |settings| settings.notifications.quiet_start = start.to_string());
+                        },
+                    )
+                    .default_value(defaults.quiet_start),
+                ),
+                SettingItem::new(
+                    "Until",
+                    SettingField::input(
+                        |cx| SettingsStore::get(cx).notifications.quiet_end.clone().into(),
+                        |end, cx| {
+                            SettingsStore::update(cx, |settings| settings.notifications.quiet_end = end.to_string());
+                        },
+                    )
+                    .default_value(defaults.quiet_end),
+                )
+                .description("Times are written like 07:00, the quiet hours may run over midnight."),
+            ]),
+        ])
+    }
+
+    fn chat_page() -> SettingPage {
+        let defaults = AppSettings::default().chat;
+
+        SettingPage::new("Chat").groups([SettingGroup::new().title("Composer").items([
+            SettingItem::new(
+                "Send with",
+                SettingField::dropdown(
+                    [SendKey::Enter, SendKey::SecondaryEnter]
+                        .map(|key| (send_key_value(key), key.label().into()))
+                        .to_vec(),
+                    |cx| send_key_value(SettingsStore::get(cx).chat.send_key),
+                    |value, cx| {
+                        let key = match value.as_ref() {
+                            "secondary_enter" => SendKey::SecondaryEnter,
+                            _ => SendKey::Enter,
+                        };
+                        SettingsStore::update(cx, |settings| settings.chat.send_key = key);
+                    },
+                )
+                .default_value(send_key_value(defaults.send_key)),
+            )
+            .description("The other key inserts a line break."),
+            SettingItem::new(
+                "Recall time limit",
+                SettingField::number_input(
+                    NumberFieldOptions {
+                        min: 0.,
+                        max: 1440.,
+                        step: 1.,
+                    },
+                    |cx| SettingsStore::get(cx).chat.recall_minutes.into(),
+                    |minutes, cx| {
+                        SettingsStore::update(cx, |settings| settings.chat.recall_minutes = minutes as u32);
+                    },
+                )
+                .default_value(f64::from(defaults.recall_minutes)),
+            )
+            .description("Minutes after sending during which a message can be recalled."),
+        ])])
+    }
+
+    fn network_page() -> SettingPage {
+        let defaults = AppSettings::default().network;
+
+        SettingPage::new("Network").groups([SettingGroup::new().title("Web").items([
+            SettingItem::new(
+                "Home page",
+                SettingField::input(
+                    |cx| SettingsStore::get(cx).network.home_url.clone().into(),
+                    |url, cx| {
+                        SettingsStore::update(cx, |settings| settings.network.home_url = url.trim().to_string());
+                    },
+                )
+                .default_value(defaults.home_url),
+            )
+            .description("The page the web view opens first."),
+            SettingItem::new(
+                "Proxy",
+                SettingField::input(
+                    |cx| SettingsStore::get(cx).network.proxy.clone().into(),
+                    |proxy, cx| {
+                        SettingsStore::update(cx, |settings| settings.network.proxy = proxy.trim().to_string());
+                    },
+                )
+                .default_value(defaults.proxy),
+            )
+            .description("http://host:port or socks5://host:port, empty to connect directly. Used by web views opened later."),
+            SettingItem::new(
+                "Bridge origins",
+                SettingField::input(
+                    |cx| SettingsStore::get(cx).network.bridge_origins.join(", ").into(),
+                    |origins, cx| {
+                        SettingsStore::update(cx, |settings| {
+                            settings.network.bridge_origins = origins
+                                .split(',')
+                                .map(str::trim)
+                                .filter(|origin| !origin.is_empty())
+                                .map(str::to_string)
+                                .collect();
+                        });
+                    },
+                )
+                .default_value(defaults.bridge_origins.join(", ")),
+            )
+            .description("Pages of these origins may call the app, separated by commas. Anything else is refused."),
+        ])])
+    }
+
+    fn storage_page(cx: &App) -> SettingPage {
+        let defaults = StorageSettings::default();
+        let folder = |title: &'static str,
+                      default: PathBuf,
+                      value: fn(&StorageSettings) -> &Option<PathBuf>,
+                      set_value: fn(&mut StorageSettings) -> &mut Option<PathBuf>| {
+            SettingItem::new(
+                title,
+                SettingField::input(
+                    move |cx| {
+                        value(&SettingsStore::get(cx).storage)
+                            .as_ref()
+                            .map(|dir| dir.display().to_string().into())
+                            .unwrap_or_default()
+                    },
+                    move |dir: SharedString, cx| {
+                        let dir = dir.trim();
+                        let dir = (!dir.is_empty()).then(|| PathBuf::from(dir));
+                        SettingsStore::update(cx, |settings| *set_value(&mut settings.storage) = dir);
+                    },
+                )
+                .default_value(""),
+            )
+            .description(format!("Empty for the default, {}.", default.display()))
+        };
+        let settings_path = SettingsStore::global(cx).path().to_path_buf();
+
+        SettingPage::new("Storage").groups([
+            SettingGroup::new()
+                .title("Folders")
+                .description("Changes to the data and cache folders take effect after a restart.")
+                .items([
+                    folder(
+                        "Data",
+                        defaults.data_dir(),
+                        |storage| &storage.data_dir,
+                        |storage| &mut storage.data_dir,
+                    ),
+                    folder(
+                        "Cache",
+                        defaults.cache_dir(),
+                        |storage| &storage.cache_dir,
+                        |storage| &mut storage.cache_dir,
+                    ),
+                    folder(
+                        "Downloads",
+                        defaults.download_dir(),
+                        |storage| &storage.download_dir,
+                        |storage| &mut storage.download_dir,
+                    ),
+                ]),
+            SettingGroup::new().title("Settings File").item(
+                SettingItem::new(
+                    "settings.toml",
+                    SettingField::render(move |_, _, _| {
+                        let path = settings_path.clone();
+                        Button::new("open-settings-file")
+                            .label("Open")
+                            .small()
+                            .on_click(move |_, _, cx| cx.open_with_system(&path))
+                    }),
+                )
+                .description(SettingsStore::global(cx).path().display().to_string()),
+            ),
+        ])
+    }
+}
+
+/// The theme the settings pick for `mode`, the one in use when they pick none.
+fn current_theme(mode: ThemeMode, cx: &App) -> SharedString {
+    let appearance = &SettingsStore::get(cx).appearance;
+    let (picked, current) = match mode {
+        ThemeMode::Light => (&appearance.light_theme, &Theme::global(cx).light_theme),
+        ThemeMode::Dark => (&appearance.dark_theme, &Theme::global(cx).dark_theme),
+    };
+    picked.clone().unwrap_or_else(|| current.name.clone())
+}
+
+fn send_key_value(key: SendKey) -> SharedString {
+    match key {
+        SendKey::Enter => "enter".into(),
+        SendKey::SecondaryEnter => "secondary_enter".into(),
+    }
+}
+
+impl Render for Preferences {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let problems = SettingsStore::global(cx).problems().join("\n");
+        let dialog_layer = Root::render_dialog_layer(window, cx);
+        let sheet_layer = Root::render_sheet_layer(window, cx);
+        let notification_layer = Root::render_notification_layer(window, cx);
+
+        div()
+            .size_full()
+            .child(
+                v_flex()
+                    .size_full()
+                    .child(TitleBar::new().child("Preferences"))
+                    .when(!problems.is_empty(), |this| {
+                        this.child(
+                            div().p_3().child(
+                                Alert::warning("settings-problems", problems)
+                                    .title("Some settings were not applied"),
+                            ),
+                        )
+                    })
+                    .child(
+                        div().flex_1().overflow_hidden().child(
+                            Settings::new("preferences").pages([
+                                Self::appearance_page(cx),
+                                Self::notifications_page(),
+                                Self::chat_page(),
+                                Self::network_page(),
+                                Self::storage_page(cx),
+                            ]),
+                        ),
+                    ),
+            )
+            .children(dialog_layer)
+            .children(sheet_layer)
+            .children(notification_layer)
+    }
+}
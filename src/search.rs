@@ -0,0 +1,664 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, HashSet},
+    ops::Range,
+};
+
+use chrono::{DateTime, Duration, Local};
+use gpui::{App, AppContext as _, Context, Entity, FontWeight, Global, HighlightStyle, InteractiveElement as _, IntoElement, ParentElement as _, Render, SharedString, StatefulInteractiveElement as _, Styled as _, StyledText, Subscription, WeakEntity, Window, WindowId, div, px};
+use gpui_component::{ActiveTheme as _, IndexPath, Selectable as _, Sizable as _, WindowExt as _, button::{Button, ButtonVariants as _}, checkbox::Checkbox, h_flex, input::{Input, InputEvent, InputState}, label::Label, scroll::ScrollableElement as _, select::{Select, SelectEvent, SelectItem, SelectState}, v_flex};
+
+use crate::{
+    chat::{ChatStore, ConversationId, Message, MessageId, UserId},
+    title_bar::ToggleSearch,
+};
+
+/// Han, kana and hangul are written without spaces between words.
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}'
+        | '\u{3400}'..='\u{4dbf}'
+        | '\u{4e00}'..='\u{9fff}'
+        | '\u{ac00}'..='\u{d7af}'
+        | '\u{f900}'..='\u{faff}'
+        | '\u{ff66}'..='\u{ff9f}'
+        | '\u{20000}'..='\u{2fa1f}'
+    )
+}
+
+/// Split text into index terms.
+///
+/// Other scripts are split into lowercase words. Runs of CJK characters have no
+/// word boundaries, so every character and every pair of adjacent characters
+/// is a term, which lets any substring of two or more characters be found
+/// through its pairs.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut word = String::new();
+    let mut previous_cjk: Option<char> = None;
+
+    for c in text.chars() {
+        if is_cjk(c) {
+            if !word.is_empty() {
+                tokens.push(std::mem::take(&mut word));
+            }
+            tokens.push(c.to_string());
+            if let Some(previous) = previous_cjk {
+                tokens.push([previous, c].iter().collect());
+            }
+            previous_cjk = Some(c);
+        } else {
+            previous_cjk = None;
+            if c.is_alphanumeric() {
+                word.extend(c.to_lowercase());
+            } else if !word.is_empty() {
+                tokens.push(std::mem::take(&mut word));
+            }
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+    tokens
+}
+
+/// The terms a query must match, a CJK run longer than one character only needs its pairs.
+fn query_terms(query: &str) -> Vec<String> {
+    let tokens = tokenize(query);
+    let has_pairs = tokens.iter().any(|token| token.chars().count() == 2 && token.chars().all(is_cjk));
+    tokens
+        .into_iter()
+        .filter(|token| !(has_pairs && token.chars().count() == 1 && token.chars().all(is_cjk)))
+        .collect()
+}
+
+type MessageKey = (ConversationId, MessageId);
+
+/// An inverted index from terms to the messages containing them.
+#[derive(Default)]
+pub struct SearchIndex {
+    postings: BTreeMap<String, HashSet<MessageKey>>,
+    /// The terms indexed for every message, to remove them again.
+    terms: HashMap<MessageKey, Vec<String>>,
+}
+
+impl SearchIndex {
+    /// Index a message, replacing what was indexed for it before.
+    pub fn insert(&mut self, message: &Message) {
+        let key = (message.conversation, message.id);
+        self.remove(key.0, key.1);
+        if message.is_recalled() || message.is_system() {
+            return;
+        }
+
+        let mut terms = tokenize(&message.text);
+        terms.sort();
+        terms.dedup();
+        for term in &terms {
+            self.postings.entry(term.clone()).or_default().insert(key);
+        }
+        self.terms.insert(key, terms);
+    }
+
+    pub fn remove(&mut self, conversation: ConversationId, id: MessageId) {
+        let key = (conversation, id);
+        for term in self.terms.remove(&key).unwrap_or_default() {
+            if let Some(keys) = self.postings.get_mut(&term) {
+                keys.remove(&key);
+                if keys.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+    }
+
+    /// The messages containing every term of `query`, words also match as a prefix
+    /// so results show up while typing. `None` when the query has no terms.
+    pub fn lookup(&self, query: &str) -> Option<HashSet<MessageKey>> {
+        let mut result: Option<HashSet<MessageKey>> = None;
+        for term in query_terms(query) {
+            let mut keys = HashSet::new();
+            for (_, postings) in self
+                .postings
+                .range(term.clone()..)
+                .take_while(|(key, _)| key.starts_with(&term))
+            {
+                keys.extend(postings.iter().copied());
+            }
+            result = Some(match result {
+                Some(result) => result.intersection(&keys).copied().collect(),
+                None => keys,
+            });
+        }
+        result
+    }
+}
+
+/// The byte ranges of `needle` in `haystack`, ignoring case.
+pub(crate) fn find_ignore_case(haystack: &str, needle: &str) -> Vec<Range<usize>> {
+    let needle = needle.to_lowercase().chars().collect::<Vec<_>>();
+    if needle.is_empty() {
+        return vec![];
+    }
+
+    let mut ranges = vec![];
+    let mut start = 0;
+    while start < haystack.len() {
+        let mut chars = haystack[start..].char_indices().flat_map(|(ix, c)| {
+            c.to_lowercase().map(move |c| (ix, c))
+        });
+        let mut last = 0;
+        let matched = needle.iter().all(|n| {
+            chars.next().is_some_and(|(ix, c)| {
+                last = ix;
+                c == *n
+            })
+        });
+        // Some characters lowercase to several, a match must not end inside one of them.
+        let end = match chars.next() {
+            Some((ix, _)) if ix == last => None,
+            Some((ix, _)) => Some(start + ix),
+            None => Some(haystack.len()),
+        };
+        match end.filter(|_| matched) {
+            Some(end) => {
+                ranges.push(start..end);
+                start = end;
+            }
+            None => {
+                start += haystack[start..].chars().next().map(|c| c.len_utf8()).unwrap_or(1);
+            }
+        }
+    }
+    ranges
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Period {
+    #[default]
+    Any,
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl Period {
+    const ALL: [Period; 5] = [Period::Any, Period::Day, Period::Week, Period::Month, Period::Year];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Period::Any => "Any time",
+            Period::Day => "Today",
+            Period::Week => "Past week",
+            Period::Month => "Past month",
+            Period::Year => "Past year",
+        }
+    }
+
+    fn since(&self) -> Option<DateTime<Local>> {
+        let days = match self {
+            Period::Any => return None,
+            Period::Day => 1,
+            Period::Week => 7,
+            Period::Month => 30,
+            Period::Year => 365,
+        };
+        Some(Local::now() - Duration::days(days))
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SearchQuery {
+    pub text: String,
+    /// Only messages sent by this user.
+    pub sender: Option<UserId>,
+    pub since: Option<DateTime<Local>>,
+    pub has_attachment: bool,
+}
+
+impl SearchQuery {
+    fn is_empty(&self) -> bool {
+        self.text.trim().is_empty() && self.sender.is_none() && self.since.is_none() && !self.has_attachment
+    }
+
+    fn matches(&self, message: &Message) -> bool {
+        !message.is_recalled()
+            && !message.is_system()
+            && self.sender.is_none_or(|sender| message.sender == sender)
+            && self.since.is_none_or(|since| message.sent_at >= since)
+            && (!self.has_attachment || message.attachment.is_some())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchHit {
+    pub conversation: ConversationId,
+    pub message: MessageId,
+    pub sender: UserId,
+    pub sent_at: DateTime<Local>,
+    /// The part of the text around the first match.
+    pub snippet: SharedString,
+    /// The matches in `snippet`.
+    pub highlights: Vec<Range<usize>>,
+}
+
+impl SearchHit {
+    const CONTEXT_BEFORE: usize = 20;
+    const SNIPPET_LEN: usize = 100;
+
+    fn new(message: &Message, words: &[&str]) -> Self {
+        let text = message.text.as_ref();
+        let mut matches = words
+            .iter()
+            .flat_map(|word| find_ignore_case(text, word))
+            .collect::<Vec<_>>();
+        matches.sort_by_key(|range| range.start);
+
+        // Cut the snippet on char boundaries around the first match.
+        let first = matches.first().map(|range| range.start).unwrap_or_default();
+        let start = text[..first]
+            .char_indices()
+            .rev()
+            .nth(Self::CONTEXT_BEFORE - 1)
+            .map(|(ix, _)| ix)
+            .unwrap_or(0);
+        let end = text[start..]
+            .char_indices()
+            .nth(Self::SNIPPET_LEN)
+            .map(|(ix, _)| start + ix)
+            .unwrap_or(text.len());
+
+        let prefix = if start > 0 { "…" } else { "" };
+        let suffix = if end < text.len() { "…" } else { "" };
+        let snippet = format!("{}{}{}", prefix, &text[start..end], suffix).replace('\n', " ");
+        let highlights = matches
+            .into_iter()
+            .filter(|range| range.start >= start && range.end <= end)
+            .map(|range| range.start - start + prefix.len()..range.end - start + prefix.len())
+            .collect();
+
+        Self {
+            conversation: message.conversation,
+            message: message.id,
+            sender: message.sender,
+            sent_at: message.sent_at,
+            snippet: snippet.into(),
+            highlights,
+        }
+    }
+}
+
+/// The messages of `store` matching `query` in all conversations, newest first.
+pub fn search(store: &ChatStore, query: &SearchQuery, limit: usize) -> Vec<SearchHit> {
+    if query.is_empty() {
+        return vec![];
+    }
+
+    let mut messages = match store.search_index().lookup(&query.text) {
+        Some(keys) => keys
+            .into_iter()
+            .filter_map(|(conversation, id)| store.message(conversation, id))
+            .collect::<Vec<_>>(),
+        None => store.all_messages().collect(),
+    };
+    messages.retain(|message| query.matches(message));
+    messages.sort_by_key(|message| Reverse(message.sent_at));
+
+    let words = query.text.split_whitespace().collect::<Vec<_>>();
+    messages
+        .into_iter()
+        .take(limit)
+        .map(|message| SearchHit::new(message, &words))
+        .collect()
+}
+
+pub fn init(cx: &mut App) {
+    cx.on_action(|_: &ToggleSearch, cx| {
+        let Some(window) = cx.active_window() else {
+            return;
+        };
+        _ = window.update(cx, |_, window, cx| SearchView::toggle(window, cx));
+    });
+}
+
+/// A user to filter the results by sender.
+#[derive(Clone)]
+struct SenderItem {
+    id: UserId,
+    name: SharedString,
+}
+
+impl SelectItem for SenderItem {
+    type Value = UserId;
+
+    fn title(&self) -> SharedString {
+        self.name.clone()
+    }
+
+    fn value(&self) -> &Self::Value {
+        &self.id
+    }
+}
+
+/// The search sheet of every window while it is open.
+///
+/// Only the sheet holds the view, so it is released once the sheet is closed or replaced.
+#[derive(Default)]
+struct SearchSheets(HashMap<WindowId, WeakEntity<SearchView>>);
+
+impl Global for SearchSheets {}
+
+/// Search over the messages of all conversations, shown in a side sheet.
+pub struct SearchView {
+    store: Entity<ChatStore>,
+    input: Entity<InputState>,
+    sender: Entity<SelectState<Vec<SenderItem>>>,
+    period: Period,
+    has_attachment: bool,
+    hits: Vec<SearchHit>,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl SearchView {
+    const LIMIT: usize = 200;
+
+    /// Open the search sheet, or close it when it is open. Any other sheet is replaced.
+    pub fn toggle(window: &mut Window, cx: &mut App) {
+        let id = window.window_handle().window_id();
+        let open = cx
+            .default_global::<SearchSheets>()
+            .0
+            .get(&id)
+            .is_some_and(|view| view.upgrade().is_some());
+        if open && window.has_active_sheet(cx) {
+            window.close_sheet(cx);
+            return;
+        }
+
+        let view = cx.new(|cx| Self::new(window, cx));
+        let sheets = &mut cx.default_global::<SearchSheets>().0;
+        sheets.retain(|_, view| view.upgrade().is_some());
+        sheets.insert(id, view.downgrade());
+        let input = view.read(cx).input.clone();
+        input.update(cx, |input, cx| input.focus(window, cx));
+        window.open_sheet(cx, move |sheet, _, _| {
+            sheet
+                .title("Search")
+                .size(px(480.))
+                .child(view.clone())
+        });
+    }
+
+    pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let store = ChatStore::global(cx);
+        let input = cx.new(|cx| InputState::new(window, cx).placeholder("Search messages"));
+        let senders = {
+            let store = store.read(cx);
+            store
+                .users()
+                .into_iter()
+                .map(|id| SenderItem {
+                    id,
+                    name: store.name(id),
+                })
+                .collect::<Vec<_>>()
+        };
+        let sender = cx.new(|cx| SelectState::new(senders, None::<IndexPath>, window, cx).searchable(true));
+
+        let _subscriptions = vec![
+            cx.subscribe(&input, |this, _, event: &InputEvent, cx| {
+                if let InputEvent::Change = event {
+                    this.refresh(cx);
+                }
+            }),
+            cx.subscribe(&sender, |this, _, _: &SelectEvent<Vec<SenderItem>>, cx| this.refresh(cx)),
+        ];
+
+        Self {
+            store,
+            input,
+            sender,
+            period: Period::default(),
+            has_attachment: false,
+            hits: vec![],
+            _subscriptions,
+        }
+    }
+
+    fn query(&self, cx: &App) -> SearchQuery {
+        SearchQuery {
+            text: self.input.read(cx).value().to_string(),
+            sender: self.sender.read(cx).selected_value().copied(),
+            since: self.period.since(),
+            has_attachment: self.has_attachment,
+        }
+    }
+
+    fn refresh(&mut self, cx: &mut Context<Self>) {
+        let query = self.query(cx);
+        self.hits = search(self.store.read(cx), &query, Self::LIMIT);
+        cx.notify();
+    }
+
+    fn open_hit(&mut self, ix: usize, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(hit) = self.hits.get(ix) else {
+            return;
+        };
+        let (conversation, message) = (hit.conversation, hit.message);
+        self.store
+            .update(cx, |store, cx| store.reveal(conversation, Some(message), cx));
+        window.close_sheet(cx);
+    }
+
+    fn render_hit(&self, ix: usize, hit: &SearchHit, cx: &Context<Self>) -> impl IntoElement {
+        let store = self.store.read(cx);
+        let highlights = hit
+            .highlights
+            .iter()
+            .map(|range| {
+                let style = HighlightStyle {
+                    font_weight: Some(FontWeight::BOLD),
+                    background_color: Some(cx.theme().warning.opacity(0.3)),
+                    ..Default::default()
+                };
+                (range.clone(), style)
+            })
+            .collect::<Vec<_>>();
+
+        v_flex()
+            .id(("search-hit", ix))
+            .gap_1()
+            .p_2()
+            .rounded(cx.theme().radius)
+            .cursor_pointer()
+            .hover(|this| this.bg(cx.theme().accent))
+            .on_click(cx.listener(move |this, _, window, cx| this.open_hit(ix, window, cx)))
+            .child(
+                h_flex()
+                    .gap_2()
+                    .text_xs()
+                    .text_color(cx.theme().muted_foreground)
+                    .child(div().flex_1().child(format!(
+                        "{} · {}",
+                        store.title(hit.conversation),
+                        store.name(hit.sender)
+                    )))
+                    .child(hit.sent_at.format("%Y-%m-%d %H:%M").to_string()),
+            )
+            .child(
+                div()
+                    .text_sm()
+                    .child(StyledText::new(hit.snippet.clone()).with_highlights(highlights)),
+            )
+    }
+}
+
+impl Render for SearchView {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let query = self.query(cx);
+
+        v_flex()
+            .size_full()
+            .gap_2()
+            .child(Input::new(&self.input).cleanable(true))
+            .child(
+                h_flex()
+                    .gap_2()
+                    .child(
+                        Select::new(&self.sender)
+                            .small()
+                            .placeholder("From anyone")
+                            .cleanable(true)
+                            .w(px(180.)),
+                    )
+                    .child(
+                        Checkbox::new("search-has-attachment")
+                            .label("Has attachment")
+                            .checked(self.has_attachment)
+                            .on_click(cx.listener(|this, checked: &bool, _, cx| {
+                                this.has_attachment = *checked;
+                                this.refresh(cx);
+                            })),
+                    ),
+            )
+            .child(
+                h_flex()
+                    .gap_1()
+                    .children(Period::ALL.iter().enumerate().map(|(ix, period)| {
+                        let period = *period;
+                        Button::new(("search-period", ix))
+                            .label(period.label())
+                            .xsmall()
+                            .ghost()
+                            .selected(self.period == period)
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                this.period = period;
+                                this.refresh(cx);
+                            }))
+                    })),
+            )
+            .child(
+                Label::new(if query.is_empty() {
+                    "Type to search all conversations".to_string()
+                } else if self.hits.len() >= Self::LIMIT {
+                    format!("Showing the newest {} results", Self::LIMIT)
+                } else {
+                    format!("{} results", self.hits.len())
+                })
+                .text_xs()
+                .text_color(cx.theme().muted_foreground),
+            )
+            .child(
+                v_flex()
+                    .flex_1()
+                    .overflow_y_scrollbar()
+                    .children(
+                        self.hits
+                            .iter()
+                            .enumerate()
+                            .map(|(ix, hit)| self.render_hit(ix, hit, cx)),
+                    ),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use chrono::Local;
+    use gpui::TestAppContext;
+
+    use super::*;
+    use crate::{
+        chat::{LOCAL_USER, NewMessage},
+        settings::StorageSettings,
+        transport::{LoopbackTransport, Op},
+    };
+
+    const CONVERSATION: ConversationId = ConversationId(1);
+
+    fn send(message: u64, sender: UserId, text: &str) -> Op {
+        Op::Send {
+            conversation: CONVERSATION,
+            message: MessageId(message),
+            sender,
+            at: Local::now(),
+            content: NewMessage {
+                text: text.to_string().into(),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[gpui::test]
+    fn sender_filter_keeps_messages_of_the_sender(cx: &mut TestAppContext) {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = StorageSettings {
+            data_dir: Some(dir.path().join("data")),
+            cache_dir: Some(dir.path().join("cache")),
+            ..Default::default()
+        };
+        let store = cx.new(|_| ChatStore::new(LOCAL_USER, Rc::new(LoopbackTransport::default()), &storage));
+        store.update(cx, |store, cx| {
+            store.receive(send(1, 1, "lunch today?"), cx);
+            store.receive(send(2, 2, "lunch at noon"), cx);
+        });
+
+        store.read_with(cx, |store, _| {
+            let query = SearchQuery {
+                text: "lunch".into(),
+                ..Default::default()
+            };
+            assert_eq!(search(store, &query, 10).len(), 2);
+
+            let query = SearchQuery {
+                sender: Some(2),
+                ..query
+            };
+            let hits = search(store, &query, 10);
+            assert_eq!(hits.len(), 1);
+            assert_eq!(hits[0].message, MessageId(2));
+        });
+    }
+
+    #[test]
+    fn cjk_text_is_split_into_characters_and_pairs() {
+        assert_eq!(tokenize("Hi 你好"), ["hi", "你", "好", "你好"]);
+    }
+
+    /// The matches of `needle` in `haystack`, which must start and end on its char boundaries.
+    fn matches<'a>(haystack: &'a str, needle: &str) -> Vec<&'a str> {
+        find_ignore_case(haystack, needle)
+            .into_iter()
+            .map(|range| {
+                assert!(haystack.is_char_boundary(range.start) && haystack.is_char_boundary(range.end));
+                &haystack[range]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_ignore_case() {
+        assert_eq!(matches("Lunch or LUNCH", "lunch"), ["Lunch", "LUNCH"]);
+        assert_eq!(matches("lunch", "LUNCH"), ["lunch"]);
+        assert!(matches("lunch", "").is_empty());
+    }
+
+    #[test]
+    fn matches_cover_characters_lowercasing_to_another_length() {
+        // `ẞ` takes three bytes and lowercases to the two of `ß`.
+        assert_eq!(matches("STRAẞE und straße", "straße"), ["STRAẞE", "straße"]);
+        // `İ` lowercases to `i` and a combining dot, a match must not end between them.
+        assert_eq!(matches("İstanbul", "i̇st"), ["İst"]);
+        assert!(matches("İstanbul", "i").is_empty());
+        assert_eq!(matches("İi", "i"), ["i"]);
+    }
+
+    #[test]
+    fn matches_in_cjk_text() {
+        assert_eq!(matches("日本語のテキスト、テキ", "テキ"), ["テキ", "テキ"]);
+        assert_eq!(find_ignore_case("日本語", "本").first(), Some(&(3..6)));
+    }
+}
@@ -0,0 +1,572 @@
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveTime;
+use gpui::{App, Global, Pixels, SharedString, Size, px, size};
+use gpui_component::wry::http::Uri;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{history::SendKey, notifications::DoNotDisturb};
+
+/// Whether the app is light or dark, or follows the operating system.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AppearanceMode {
+    #[default]
+    Light,
+    Dark,
+    System,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppearanceSettings {
+    pub mode: AppearanceMode,
+    /// The theme used in light mode, the default one when unset or not installed.
+    pub light_theme: Option<SharedString>,
+    /// The theme used in dark mode, the default one when unset or not installed.
+    pub dark_theme: Option<SharedString>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowSettings {
+    /// The size of a new window, shrunk to fit the display.
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        Self {
+            width: 1600.,
+            height: 1200.,
+        }
+    }
+}
+
+impl WindowSettings {
+    pub const MIN_WIDTH: f32 = 480.;
+    pub const MIN_HEIGHT: f32 = 320.;
+    const MAX_SIZE: f32 = 16384.;
+
+    pub fn size(&self) -> Size<Pixels> {
+        size(px(self.width), px(self.height))
+    }
+
+    fn validate(&mut self, problems: &mut Vec<String>) {
+        let default = Self::default();
+        if !(Self::MIN_WIDTH..=Self::MAX_SIZE).contains(&self.width) {
+            problems.push(format!(
+                "window.width must be between {} and {}",
+                Self::MIN_WIDTH,
+                Self::MAX_SIZE
+            ));
+            self.width = default.width;
+        }
+        if !(Self::MIN_HEIGHT..=Self::MAX_SIZE).contains(&self.height) {
+            problems.push(format!(
+                "window.height must be between {} and {}",
+                Self::MIN_HEIGHT,
+                Self::MAX_SIZE
+            ));
+            self.height = default.height;
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationSettings {
+    /// Toasts for messages in other conversations while the window is active.
+    pub toasts: bool,
+    /// Notifications of the operating system while the window is in the background.
+    pub system: bool,
+    pub do_not_disturb: bool,
+    /// When do not disturb starts each day, as `HH:MM`.
+    pub quiet_start: String,
+    /// When do not disturb ends, as `HH:MM`, it may be the next day.
+    pub quiet_end: String,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            toasts: true,
+            system: true,
+            do_not_disturb: false,
+            quiet_start: "22:00".into(),
+            quiet_end: "07:00".into(),
+        }
+    }
+}
+
+impl NotificationSettings {
+    pub fn do_not_disturb(&self) -> DoNotDisturb {
+        let default = DoNotDisturb::default();
+        DoNotDisturb {
+            enabled: self.do_not_disturb,
+            start: parse_time(&self.quiet_start).unwrap_or(default.start),
+            end: parse_time(&self.quiet_end).unwrap_or(default.end),
+        }
+    }
+
+    fn validate(&mut self, problems: &mut Vec<String>) {
+        let default = Self::default();
+        if parse_time(&self.quiet_start).is_none() {
+            problems.push("notifications.quiet_start must be a time like 22:00".into());
+            self.quiet_start = default.quiet_start;
+        }
+        if parse_time(&self.quiet_end).is_none() {
+            problems.push("notifications.quiet_end must be a time like 07:00".into());
+            self.quiet_end = default.quiet_end;
+        }
+    }
+}
+
+fn parse_time(text: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(text.trim(), "%H:%M").ok()
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChatSettings {
+    pub send_key: SendKey,
+    /// How long after sending a message it can still be recalled.
+    pub recall_minutes: u32,
+}
+
+impl Default for ChatSettings {
+    fn default() -> Self {
+        Self {
+            send_key: SendKey::default(),
+            recall_minutes: 2,
+        }
+    }
+}
+
+impl ChatSettings {
+    const MAX_RECALL_MINUTES: u32 = 24 * 60;
+
+    fn validate(&mut self, problems: &mut Vec<String>) {
+        if self.recall_minutes > Self::MAX_RECALL_MINUTES {
+            problems.push(format!(
+                "chat.recall_minutes must be at most {}",
+                Self::MAX_RECALL_MINUTES
+            ));
+            self.recall_minutes = Self::default().recall_minutes;
+        }
+    }
+}
+
+/// A proxy server, written `http://host:port` or `socks5://host:port`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Proxy {
+    pub socks5: bool,
+    pub host: String,
+    pub port: u16,
+}
+
+impl Proxy {
+    fn parse(text: &str) -> Option<Self> {
+        let (socks5, address) = if let Some(address) = text.strip_prefix("socks5://") {
+            (true, address)
+        } else {
+            (false, text.strip_prefix("http://")?)
+        };
+        let (host, port) = address.trim_end_matches('/').rsplit_once(':')?;
+        (!host.is_empty()).then_some(Self {
+            socks5,
+            host: host.to_string(),
+            port: port.parse().ok()?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkSettings {
+    /// The page the web view opens first.
+    pub home_url: String,
+    /// The proxy of the web view, empty for a direct connection.
+    pub proxy: String,
+    /// The origins of the pages that may call the app from the web view, like `https://example.com`.
+    pub bridge_origins: Vec<String>,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            home_url: "https://www.baidu.com".into(),
+            proxy: String::new(),
+            bridge_origins: vec![],
+        }
+    }
+}
+
+impl NetworkSettings {
+    pub fn proxy(&self) -> Option<Proxy> {
+        Proxy::parse(self.proxy.trim())
+    }
+
+    fn validate(&mut self, problems: &mut Vec<String>) {
+        if !self.home_url.starts_with("https://") && !self.home_url.starts_with("http://") {
+            problems.push("network.home_url must start with http:// or https://".into());
+            self.home_url = Self::default().home_url;
+        }
+        if !self.proxy.trim().is_empty() && self.proxy().is_none() {
+            problems.push("network.proxy must look like http://host:port or socks5://host:port".into());
+            self.proxy.clear();
+        }
+        self.bridge_origins.retain_mut(|origin| match parse_origin(origin) {
+            Some(parsed) => {
+                *origin = parsed;
+                true
+            }
+            None => {
+                problems.push(format!("network.bridge_origins: {} is not an origin like https://example.com", origin));
+                false
+            }
+        });
+    }
+}
+
+/// The origin of `url`, `scheme://host` with the port unless it is the default one.
+pub(crate) fn origin(url: &Uri) -> Option<String> {
+    let scheme = url.scheme_str()?.to_ascii_lowercase();
+    let host = url.host().filter(|host| !host.is_empty())?.to_ascii_lowercase();
+    let default_port = match scheme.as_str() {
+        "http" => 80,
+        "https" => 443,
+        _ => return None,
+    };
+    Some(match url.port_u16().filter(|port| *port != default_port) {
+        Some(port) => format!("{}://{}:{}", scheme, host, port),
+        None => format!("{}://{}", scheme, host),
+    })
+}
+
+/// The origin written in `text`, which must not have a path or query.
+pub(crate) fn parse_origin(text: &str) -> Option<String> {
+    let url = text.trim().parse::<Uri>().ok()?;
+    let bare = matches!(url.path(), "" | "/") && url.query().is_none();
+    bare.then(|| origin(&url)).flatten()
+}
+
+/// Where files are kept, the platform folders when unset.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageSettings {
+    /// Drafts, conversation flags, notifications and other app data.
+    pub data_dir: Option<PathBuf>,
+    /// Downloaded attachments.
+    pub cache_dir: Option<PathBuf>,
+    /// Where saved images go by default.
+    pub download_dir: Option<PathBuf>,
+}
+
+impl StorageSettings {
+    pub fn data_dir(&self) -> PathBuf {
+        self.data_dir.clone().unwrap_or_else(|| {
+            dirs::data_dir()
+                .unwrap_or_else(std::env::temp_dir)
+                .join("agpui")
+        })
+    }
+
+    pub fn cache_dir(&self) -> PathBuf {
+        self.cache_dir.clone().unwrap_or_else(|| {
+            dirs::cache_dir()
+                .unwrap_or_else(std::env::temp_dir)
+                .join("agpui")
+        })
+    }
+
+    pub fn download_dir(&self) -> PathBuf {
+        self.download_dir
+            .clone()
+            .unwrap_or_else(|| dirs::download_dir().unwrap_or_else(std::env::temp_dir))
+    }
+
+    fn validate(&mut self, problems: &mut Vec<String>) {
+        for (name, dir) in [
+            ("data_dir", &mut self.data_dir),
+            ("cache_dir", &mut self.cache_dir),
+            ("download_dir", &mut self.download_dir),
+        ] {
+            if dir.as_ref().is_some_and(|dir| !dir.is_absolute()) {
+                problems.push(format!("storage.{} must be an absolute path", name));
+                *dir = None;
+            }
+        }
+    }
+}
+
+/// Everything the user can configure, saved as TOML with one table per section.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    pub appearance: AppearanceSettings,
+    pub window: WindowSettings,
+    pub notifications: NotificationSettings,
+    pub chat: ChatSettings,
+    pub network: NetworkSettings,
+    pub storage: StorageSettings,
+}
+
+impl AppSettings {
+    /// Replace invalid values by their defaults, returning what was wrong.
+    pub fn validate(&mut self) -> Vec<String> {
+        let mut problems = vec![];
+        self.window.validate(&mut problems);
+        self.notifications.validate(&mut problems);
+        self.chat.validate(&mut problems);
+        self.network.validate(&mut problems);
+        self.storage.validate(&mut problems);
+        problems
+    }
+
+    /// Parse settings, a section that cannot be read falls back to its defaults.
+    pub fn parse(text: &str) -> (Self, Vec<String>) {
+        let table = match text.parse::<toml::Table>() {
+            Ok(table) => table,
+            Err(err) => return (Self::default(), vec![err.message().to_string()]),
+        };
+
+        let mut problems = vec![];
+        let mut settings = Self {
+            appearance: section(&table, "appearance", &mut problems),
+            window: section(&table, "window", &mut problems),
+            notifications: section(&table, "notifications", &mut problems),
+            chat: section(&table, "chat", &mut problems),
+            network: section(&table, "network", &mut problems),
+            storage: section(&table, "storage", &mut problems),
+        };
+        problems.extend(settings.validate());
+        (settings, problems)
+    }
+}
+
+fn section<T: DeserializeOwned + Default>(table: &toml::Table, name: &str, problems: &mut Vec<String>) -> T {
+    let Some(value) = table.get(name) else {
+        return T::default();
+    };
+    value.clone().try_into().unwrap_or_else(|err: toml::de::Error| {
+        problems.push(format!("[{}]: {}", name, err.message()));
+        T::default()
+    })
+}
+
+/// The settings of the app, observe this global to hear about changes.
+pub struct SettingsStore {
+    path: PathBuf,
+    settings: AppSettings,
+    /// What was wrong with the settings file, or with the last change.
+    problems: Vec<String>,
+}
+
+impl Global for SettingsStore {}
+
+impl SettingsStore {
+    pub fn init(cx: &mut App) {
+        cx.set_global(Self::load(Self::default_path()));
+    }
+
+    /// Use the settings saved in `dir` with the app data kept there too, for tests.
+    #[cfg(test)]
+    pub(crate) fn init_in(dir: &Path, cx: &mut App) {
+        let mut store = Self::load(dir.join("settings.toml"));
+        store.settings.storage.data_dir = Some(dir.join("data"));
+        store.settings.storage.cache_dir = Some(dir.join("cache"));
+        cx.set_global(store);
+    }
+
+    /// Load the settings saved in `path`, the defaults when there is no such file.
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let (settings, problems) = match std::fs::read_to_string(&path) {
+            Ok(text) => AppSettings::parse(&text),
+            Err(_) => (AppSettings::default(), vec![]),
+        };
+
+        Self {
+            path,
+            settings,
+            problems,
+        }
+    }
+
+    pub fn default_path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("agpui")
+            .join("settings.toml")
+    }
+
+    pub fn global(cx: &App) -> &Self {
+        cx.global::<Self>()
+    }
+
+    /// The settings in use.
+    pub fn get(cx: &App) -> &AppSettings {
+        &Self::global(cx).settings
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn problems(&self) -> &[String] {
+        &self.problems
+    }
+
+    /// Change the settings and save them, observers are notified.
+    ///
+    /// A change leaving an invalid value is rejected and kept in [`Self::problems`],
+    /// returns whether it was applied.
+    pub fn update(cx: &mut App, change: impl FnOnce(&mut AppSettings)) -> bool {
+        let mut settings = Self::get(cx).clone();
+        change(&mut settings);
+
+        let problems = settings.clone().validate();
+        if !problems.is_empty() {
+            if Self::global(cx).problems != problems {
+                cx.global_mut::<Self>().problems = problems;
+            }
+            return false;
+        }
+        if settings == *Self::get(cx) {
+            if !Self::global(cx).problems.is_empty() {
+                cx.global_mut::<Self>().problems.clear();
+            }
+            return true;
+        }
+
+        let store = cx.global_mut::<Self>();
+        store.settings = settings;
+        store.problems.clear();
+        store.save();
+        true
+    }
+
+    fn save(&self) {
+        let Ok(text) = toml::to_string_pretty(&self.settings) else {
+            return;
+        };
+        if let Some(dir) = self.path.parent()
+            && std::fs::create_dir_all(dir).is_ok()
+        {
+            _ = std::fs::write(&self.path, text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gpui::TestAppContext;
+
+    use super::*;
+
+    #[test]
+    fn a_bad_section_falls_back_alone() {
+        let (settings, problems) = AppSettings::parse(
+            r#"
+            [window]
+            width = "wide"
+
+            [chat]
+            recall_minutes = 5
+            "#,
+        );
+        assert_eq!(settings.window, WindowSettings::default());
+        assert_eq!(settings.chat.recall_minutes, 5);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("[window]"));
+    }
+
+    #[test]
+    fn invalid_values_are_reset_and_reported() {
+        let (settings, problems) = AppSettings::parse(
+            r#"
+            [window]
+            width = 10.0
+            height = 900.0
+
+            [notifications]
+            quiet_start = "late"
+
+            [network]
+            proxy = "ftp://proxy"
+            "#,
+        );
+        assert_eq!(settings.window.width, WindowSettings::default().width);
+        assert_eq!(settings.window.height, 900.);
+        assert_eq!(settings.notifications.quiet_start, NotificationSettings::default().quiet_start);
+        assert!(settings.network.proxy.is_empty());
+        assert_eq!(problems.len(), 3);
+    }
+
+    #[test]
+    fn unreadable_toml_gives_the_defaults() {
+        let (settings, problems) = AppSettings::parse("[window");
+        assert_eq!(settings, AppSettings::default());
+        assert_eq!(problems.len(), 1);
+    }
+
+    #[test]
+    fn proxies_are_parsed() {
+        assert_eq!(
+            Proxy::parse("socks5://127.0.0.1:1080"),
+            Some(Proxy {
+                socks5: true,
+                host: "127.0.0.1".into(),
+                port: 1080,
+            })
+        );
+        assert_eq!(
+            Proxy::parse("http://proxy.local:8080/"),
+            Some(Proxy {
+                socks5: false,
+                host: "proxy.local".into(),
+                port: 8080,
+            })
+        );
+        assert_eq!(Proxy::parse("proxy.local:8080"), None);
+        assert_eq!(Proxy::parse("http://:8080"), None);
+        assert_eq!(Proxy::parse("http://proxy.local:http"), None);
+    }
+
+    #[test]
+    fn do_not_disturb_keeps_the_default_times_for_bad_ones() {
+        let settings = NotificationSettings {
+            do_not_disturb: true,
+            quiet_start: "23:30".into(),
+            quiet_end: "never".into(),
+            ..Default::default()
+        };
+        let do_not_disturb = settings.do_not_disturb();
+        assert!(do_not_disturb.enabled);
+        assert_eq!(do_not_disturb.start, NaiveTime::from_hms_opt(23, 30, 0).unwrap());
+        assert_eq!(do_not_disturb.end, DoNotDisturb::default().end);
+    }
+
+    #[gpui::test]
+    fn a_rejected_update_changes_nothing(cx: &mut TestAppContext) {
+        let dir = tempfile::tempdir().unwrap();
+        cx.update(|cx| {
+            SettingsStore::init_in(dir.path(), cx);
+            let before = SettingsStore::get(cx).clone();
+
+            let applied = SettingsStore::update(cx, |settings| {
+                settings.chat.recall_minutes = 5;
+                settings.window.width = 1.;
+            });
+            assert!(!applied);
+            assert_eq!(*SettingsStore::get(cx), before);
+            assert_eq!(SettingsStore::global(cx).problems().len(), 1);
+
+            assert!(SettingsStore::update(cx, |settings| settings.chat.recall_minutes = 5));
+            assert_eq!(SettingsStore::get(cx).chat.recall_minutes, 5);
+            assert!(SettingsStore::global(cx).problems().is_empty());
+        });
+    }
+}
@@ -0,0 +1,148 @@
+use std::rc::Rc;
+
+use gpui::{AppContext, Context, Entity, IntoElement, ParentElement as _, Render, Styled as _, Subscription, Window, div, prelude::FluentBuilder as _};
+use gpui_component::{ActiveTheme as _, Sizable as _, button::{Button, ButtonVariants as _}, h_flex, input::{Input, InputEvent, InputState}, label::Label, scroll::ScrollableElement as _, v_flex};
+
+use crate::{
+    chat::{ChatStore, ConversationId, Message, MessageId, NewMessage},
+    emoji::replace_shortcodes,
+    history::{SendKey, submitted_text},
+    mentions::MentionCompletionProvider,
+};
+
+/// The replies to one message, shown in a side sheet with its own composer.
+pub struct ThreadView {
+    store: Entity<ChatStore>,
+    conversation: ConversationId,
+    root: MessageId,
+    input: Entity<InputState>,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl ThreadView {
+    pub fn new(
+        conversation: ConversationId,
+        root: MessageId,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let store = ChatStore::global(cx);
+        let input = cx.new(|cx| {
+            InputState::new(window, cx)
+                .placeholder("Reply in thread...")
+                .auto_grow(1, 4)
+        });
+        input.update(cx, |input, _| {
+            input.lsp.completion_provider = Some(Rc::new(MentionCompletionProvider::new(conversation)));
+        });
+        let _subscriptions = vec![
+            cx.observe(&store, |_, _, cx| cx.notify()),
+            cx.subscribe_in(&input, window, |this, state, event, window, cx| {
+                if let InputEvent::PressEnter { secondary } = event
+                    && SendKey::global(cx).is_send(*secondary)
+                {
+                    let text = submitted_text(state.read(cx));
+                    this.send(text, window, cx);
+                }
+            }),
+        ];
+
+        Self {
+            store,
+            conversation,
+            root,
+            input,
+            _subscriptions,
+        }
+    }
+
+    fn send(&mut self, text: String, window: &mut Window, cx: &mut Context<Self>) {
+        let text = replace_shortcodes(text.trim());
+        if text.is_empty() {
+            return;
+        }
+
+        let message = NewMessage {
+            text: text.into(),
+            reply_to: None,
+            thread: Some(self.root),
+            mentions: vec![],
+        };
+        let conversation = self.conversation;
+        self.store.update(cx, |store, cx| store.send_message(conversation, message, cx));
+        self.input.update(cx, |input, cx| input.set_value("", window, cx));
+    }
+
+    fn render_reply(message: &Message, outgoing: bool, cx: &Context<Self>) -> impl IntoElement {
+        v_flex()
+            .when(outgoing, |this| this.items_end())
+            .child(
+                Label::new(message.sent_at.format("%H:%M").to_string())
+                    .text_xs()
+                    .text_color(cx.theme().muted_foreground),
+            )
+            .child(
+                div()
+                    .px_2()
+                    .py_1()
+                    .rounded(cx.theme().radius)
+                    .bg(cx.theme().muted)
+                    .text_sm()
+                    .child(message.text.clone()),
+            )
+    }
+}
+
+impl Render for ThreadView {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let store = self.store.read(cx);
+        let me = store.me();
+        let root = store.message(self.conversation, self.root);
+        let replies = store
+            .thread_replies(self.conversation, self.root)
+            .collect::<Vec<_>>();
+
+        v_flex()
+            .size_full()
+            .gap_3()
+            .child(
+                v_flex()
+                    .pb_2()
+                    .border_b_1()
+                    .border_color(cx.theme().border)
+                    .text_sm()
+                    .child(root.map(|root| root.snippet()).unwrap_or_else(|| "Message deleted".into())),
+            )
+            .child(
+                Label::new(format!("{} replies", replies.len()))
+                    .text_xs()
+                    .text_color(cx.theme().muted_foreground),
+            )
+            .child(
+                v_flex()
+                    .flex_1()
+                    .gap_2()
+                    .overflow_y_scrollbar()
+                    .children(
+                        replies
+                            .iter()
+                            .map(|message| Self::render_reply(message, message.sender == me, cx)),
+                    ),
+            )
+            .child(
+                h_flex()
+                    .gap_2()
+                    .child(div().flex_1().child(Input::new(&self.input)))
+                    .child(
+                        Button::new("send-thread-reply")
+                            .label("Send")
+                            .small()
+                            .primary()
+                            .on_click(cx.listener(|this, _, window, cx| {
+                                let text = this.input.read(cx).value().to_string();
+                                this.send(text, window, cx);
+                            })),
+                    ),
+            )
+    }
+}
@@ -1,13 +1,19 @@
-use std::rc::Rc;
+use std::{path::Path, rc::Rc};
 use gpui::{
-    Action, AnyElement, App, Context, Entity, InteractiveElement as _, IntoElement, Menu, MenuItem, MouseButton, ParentElement as _, Render, SharedString, Styled as _, Subscription, Window, actions, div
+    Action, AnyElement, App, AppContext as _, ClipboardItem, Context, Entity, Global, InteractiveElement as _, IntoElement, Menu, MenuItem, MouseButton, ParentElement as _, PathPromptOptions, Render, SharedString, Styled as _, Subscription, Window, actions, div, px
 };
 use gpui_component::{
-    IconName, Sizable as _, Theme, ThemeMode, ThemeRegistry, TitleBar, WindowExt as _, badge::Badge, button::{Button, ButtonVariants as _}, menu::{AppMenuBar}
+    ActiveTheme as _, IconName, Sizable as _, StyledExt as _, ThemeMode, ThemeRegistry, TitleBar, WindowExt as _, badge::Badge, button::{Button, ButtonVariants as _}, label::Label, menu::{AppMenuBar}, v_flex
 };
 
+use crate::{appearance::{Appearance, FollowSystemMode, OpenThemePicker}, chat::ChatStore, keymap::ShowKeymap, notification_center::NotificationCenter, notifications::Notifications, preferences::OpenPreferences, web_panel::ToggleWebPanel, window_state::WindowState};
+
+const WEBSITE: &str = "https://github.com/longbridge/gpui-component";
+
 pub struct AppTitleBar {
     app_menu_bar: Entity<AppMenuBar>,
+    /// Shown in the middle, the name of the app unless set.
+    window_title: Option<SharedString>,
     child: Rc<dyn Fn(&mut Window, &mut App) -> AnyElement>,
     _subscriptions: Vec<Subscription>,
 }
@@ -22,7 +28,8 @@ actions!(
         TestAction,
         Tab,
         TabPrev,
-        ShowPanelInfo
+        ShowPanelInfo,
+        OpenWebsite
     ]
 );
 #[derive(Action, Clone, PartialEq)]
@@ -33,6 +40,149 @@ pub(crate) struct SwitchTheme(pub(crate) SharedString);
 #[action(namespace = themes, no_json)]
 pub(crate) struct SwitchThemeMode(pub(crate) ThemeMode);
 
+pub fn init(cx: &mut App) {
+    cx.on_action(|_: &About, cx| {
+        with_active_window(cx, open_about);
+    });
+    cx.on_action(|_: &Open, cx| {
+        let Some(window) = cx.active_window() else {
+            return;
+        };
+        let paths = cx.prompt_for_paths(PathPromptOptions {
+            files: true,
+            directories: false,
+            multiple: true,
+            prompt: Some("Import".into()),
+        });
+        cx.spawn(async move |cx| {
+            let Ok(Ok(Some(paths))) = paths.await else {
+                return;
+            };
+            _ = window.update(cx, |_, window, cx| {
+                for path in paths {
+                    import_archive(&path, window, cx);
+                }
+            });
+        })
+        .detach();
+    });
+    // Whatever is still waiting to be saved is saved by the quit observers.
+    cx.on_action(|_: &Quit, cx| cx.quit());
+    cx.on_action(|_: &CloseWindow, cx| {
+        with_active_window(cx, |window, _| window.remove_window());
+    });
+    cx.on_window_closed(|cx| {
+        if cx.windows().is_empty() {
+            cx.quit();
+        }
+    })
+    .detach();
+    cx.on_action(|_: &OpenWebsite, cx| cx.open_url(WEBSITE));
+    cx.on_action(|_: &TestAction, cx| {
+        with_active_window(cx, |window, cx| window.push_notification("Test action received", cx));
+    });
+    cx.on_action(|_: &ShowPanelInfo, cx| {
+        with_active_window(cx, |window, cx| {
+            let state = WindowState::global(cx);
+            let size = window.bounds().size;
+            let info = format!(
+                "Window {:.0}x{:.0} at {}x scale, contacts {:.0} wide, composer {:.0} high",
+                f32::from(size.width),
+                f32::from(size.height),
+                window.scale_factor(),
+                state.contacts_width.map(f32::from).unwrap_or_default(),
+                state.composer_height.map(f32::from).unwrap_or_default(),
+            );
+            window.push_notification(info, cx);
+        });
+    });
+}
+
+fn with_active_window(cx: &mut App, f: impl FnOnce(&mut Window, &mut App)) {
+    let Some(window) = cx.active_window() else {
+        return;
+    };
+    _ = window.update(cx, |_, window, cx| f(window, cx));
+}
+
+/// How the app was built and for which platform.
+fn build() -> String {
+    format!(
+        "{} build for {} {}",
+        if cfg!(debug_assertions) { "Debug" } else { "Release" },
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+    )
+}
+
+fn open_about(window: &mut Window, cx: &mut App) {
+    window.open_dialog(cx, |dialog, _, cx| {
+        dialog
+            .title("About")
+            .w(px(360.))
+            .alert()
+            .child(
+                v_flex()
+                    .gap_2()
+                    .child(Label::new(env!("CARGO_PKG_NAME")).text_lg().font_semibold())
+                    .child(Label::new(format!("Version {}", env!("CARGO_PKG_VERSION"))).text_sm())
+                    .child(Label::new(build()).text_sm().text_color(cx.theme().muted_foreground))
+                    .child(
+                        Button::new("copy-build-info")
+                            .label("Copy Build Info")
+                            .small()
+                            .outline()
+                            .on_click(|_, _, cx| {
+                                let info = format!("{} {}\n{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), build());
+                                cx.write_to_clipboard(ClipboardItem::new_string(info));
+                            }),
+                    ),
+            )
+    });
+}
+
+/// Import the chat archive in `path` and show it, or tell what went wrong.
+fn import_archive(path: &Path, window: &mut Window, cx: &mut App) {
+    let title = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let store = ChatStore::global(cx);
+    let imported = std::fs::read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|text| store.update(cx, |store, cx| store.import_archive(title, &text, cx)));
+    match imported {
+        Ok(conversation) => store.update(cx, |store, cx| store.reveal(conversation, None, cx)),
+        Err(err) => window.push_notification(format!("Could not import {}: {}", path.display(), err), cx),
+    }
+}
+
+/// The menus of the app, set once for all windows.
+///
+/// The menu bars only read the menus when they are created, they observe this global to
+/// create theirs again when the menus change.
+struct AppMenus {
+    title: SharedString,
+}
+
+impl Global for AppMenus {}
+
+impl AppMenus {
+    fn init(title: SharedString, cx: &mut App) {
+        cx.set_global(Self { title });
+        Self::set_menus(cx);
+        // The Appearance menu lists the themes, set the menus again when they are reloaded.
+        cx.observe_global::<ThemeRegistry>(Self::set_menus).detach();
+    }
+
+    fn set_menus(cx: &mut App) {
+        let title = cx.global::<Self>().title.clone();
+        init_menus(title, cx);
+        // Borrowing the global mutably notifies its observers, the menu bars of all windows are rebuilt.
+        cx.global_mut::<Self>();
+    }
+}
+
 pub fn init_menus(title: impl Into<SharedString>, cx: &mut App) {
     cx.set_menus(vec![
         Menu {
@@ -40,14 +190,24 @@ pub fn init_menus(title: impl Into<SharedString>, cx: &mut App) {
             items: vec![
                 MenuItem::action("About", About),
                 MenuItem::Separator,
-                MenuItem::action("Open...", Open),
+                MenuItem::action("Preferences...", OpenPreferences),
+                MenuItem::Separator,
+                MenuItem::action("Import Chat Archive...", Open),
                 MenuItem::Separator,
                 MenuItem::Submenu(Menu {
                     name: "Appearance".into(),
                     items: vec![
+                        MenuItem::action("System", FollowSystemMode),
                         MenuItem::action("Light", SwitchThemeMode(ThemeMode::Light)),
                         MenuItem::action("Dark", SwitchThemeMode(ThemeMode::Dark)),
-                    ],
+                        MenuItem::Separator,
+                    ]
+                    .into_iter()
+                    .chain(ThemeRegistry::global(cx).sorted_themes().into_iter().map(|theme| {
+                        MenuItem::action(theme.name.clone(), SwitchTheme(theme.name.clone()))
+                    }))
+                    .chain([MenuItem::Separator, MenuItem::action("Themes...", OpenThemePicker)])
+                    .collect(),
                 }),
                 MenuItem::Separator,
                 MenuItem::action("Quit", Quit),
@@ -84,26 +244,18 @@ pub fn init_menus(title: impl Into<SharedString>, cx: &mut App) {
                 MenuItem::action("Close Window", CloseWindow),
                 MenuItem::separator(),
                 MenuItem::action("Toggle Search", ToggleSearch),
+                MenuItem::action("Toggle Web Panel", ToggleWebPanel),
             ],
         },
         Menu {
             name: "Help".into(),
-            items: vec![MenuItem::action("Open Website", Open)],
+            items: vec![
+                MenuItem::action("Keyboard Shortcuts", ShowKeymap),
+                MenuItem::separator(),
+                MenuItem::action("Open Website", OpenWebsite),
+            ],
         },
     ]);
-
-    cx.on_action(|switch: &SwitchTheme, cx| {
-        let theme_name = switch.0.clone();
-        if let Some(theme_config) = ThemeRegistry::global(cx).themes().get(&theme_name).cloned() {
-            Theme::global_mut(cx).apply_config(&theme_config);
-        }
-        cx.refresh_windows();
-    });
-    cx.on_action(|switch: &SwitchThemeMode, cx| {
-        let mode = switch.0;
-        Theme::change(mode, None, cx);
-        cx.refresh_windows();
-    });
 }
 
 impl AppTitleBar {
@@ -112,15 +264,42 @@ impl AppTitleBar {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) -> Self {
-        init_menus(title, cx);
+        // The menus are the same in every window, the first title bar sets them.
+        if !cx.has_global::<AppMenus>() {
+            AppMenus::init(title.into(), cx);
+        }
         let app_menu_bar = AppMenuBar::new(window, cx);
+        let _subscriptions = vec![
+            cx.observe(&Notifications::log(cx), |_, _, cx| cx.notify()),
+            Appearance::observe_system_appearance(window, cx),
+            cx.observe_global_in::<AppMenus>(window, |this, window, cx| {
+                this.app_menu_bar = AppMenuBar::new(window, cx);
+                cx.notify();
+            }),
+        ];
         Self {
             app_menu_bar,
+            window_title: None,
             child: Rc::new(|_, _| div().into_any_element()),
-            _subscriptions: vec![],
+            _subscriptions,
         }
     }
 
+    fn open_notification_center(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let center = cx.new(NotificationCenter::new);
+        window.open_sheet(cx, move |sheet, _, _| {
+            sheet
+                .title("Notifications")
+                .size(px(380.))
+                .child(center.clone())
+        });
+    }
+
+    pub fn window_title(mut self, title: impl Into<SharedString>) -> Self {
+        self.window_title = Some(title.into());
+        self
+    }
+
     pub fn child<F, E>(mut self, f: F) -> Self
     where
         E: IntoElement,
@@ -133,7 +312,7 @@ impl AppTitleBar {
 
 impl Render for AppTitleBar {
     fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
-        let notifications_count = window.notifications(cx).len();
+        let notifications_count = Notifications::log(cx).read(cx).unread_count();
 
         TitleBar::new()
             // left side
@@ -144,11 +323,17 @@ impl Render for AppTitleBar {
                         .large()
                         .ghost()
                         .on_click(|_, _, cx| {
-                            cx.open_url("https://github.com/longbridge/gpui-component")
+                            cx.open_url(WEBSITE)
                         }),
                 ).child(self.app_menu_bar.clone()))
             //middle
-            .child(div().flex().items_center().justify_center().child("My APP"))
+            .child(
+                div()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(self.window_title.clone().unwrap_or_else(|| "My APP".into())),
+            )
             //right side
             .child(
                 div()
@@ -166,7 +351,10 @@ impl Render for AppTitleBar {
                                     .small()
                                     .ghost()
                                     .compact()
-                                    .icon(IconName::Bell),
+                                    .icon(IconName::Bell)
+                                    .on_click(cx.listener(|this, _, window, cx| {
+                                        this.open_notification_center(window, cx)
+                                    })),
                             ),
                         ),
                     ),
@@ -0,0 +1,120 @@
+use std::cell::RefCell;
+
+use chrono::{DateTime, Local};
+use gpui::SharedString;
+
+use crate::{
+    chat::{ConversationId, MessageId, NewMessage, UserId},
+    flags::ConversationFlags,
+};
+
+/// An operation exchanged with the chat server.
+///
+/// Local changes are applied to the [`crate::ChatStore`] first and then sent
+/// through the [`Transport`]; operations coming from other clients are fed
+/// into the same store, so applying an operation must be idempotent.
+#[derive(Clone, Debug)]
+pub enum Op {
+    /// A new message, `message` is allocated by the sending client.
+    Send {
+        conversation: ConversationId,
+        message: MessageId,
+        sender: UserId,
+        at: DateTime<Local>,
+        content: NewMessage,
+    },
+    /// Replace the text of a message, `revision` counts edits starting at 1.
+    ///
+    /// An edit `by` anyone but the sender of the message is ignored.
+    Edit {
+        conversation: ConversationId,
+        message: MessageId,
+        by: UserId,
+        revision: u32,
+        text: SharedString,
+        mentions: Vec<UserId>,
+        at: DateTime<Local>,
+    },
+    /// The sender took a message back, it becomes a tombstone for everyone.
+    ///
+    /// A recall `by` anyone but the sender of the message is ignored.
+    Recall {
+        conversation: ConversationId,
+        message: MessageId,
+        by: UserId,
+    },
+    /// Adding a reaction twice, or removing one that is not there, has no effect.
+    AddReaction {
+        conversation: ConversationId,
+        message: MessageId,
+        user: UserId,
+        emoji: SharedString,
+    },
+    RemoveReaction {
+        conversation: ConversationId,
+        message: MessageId,
+        user: UserId,
+        emoji: SharedString,
+    },
+    /// Membership changes carry the id of the system message they add to the history.
+    CreateGroup {
+        conversation: ConversationId,
+        message: MessageId,
+        by: UserId,
+        title: SharedString,
+        members: Vec<UserId>,
+        at: DateTime<Local>,
+    },
+    AddMembers {
+        conversation: ConversationId,
+        message: MessageId,
+        by: UserId,
+        users: Vec<UserId>,
+        at: DateTime<Local>,
+    },
+    /// Leaving a group is removing ourselves.
+    RemoveMember {
+        conversation: ConversationId,
+        message: MessageId,
+        by: UserId,
+        user: UserId,
+        at: DateTime<Local>,
+    },
+    SetAdmin {
+        conversation: ConversationId,
+        message: MessageId,
+        by: UserId,
+        user: UserId,
+        admin: bool,
+        at: DateTime<Local>,
+    },
+    /// Our flags for a conversation, synced between our clients, the latest change wins.
+    SetFlags {
+        conversation: ConversationId,
+        flags: ConversationFlags,
+        at: DateTime<Local>,
+    },
+}
+
+pub trait Transport {
+    fn send(&self, op: Op);
+}
+
+/// A transport that keeps every sent operation in memory, used until a real
+/// server connection is wired in.
+#[derive(Default)]
+pub struct LoopbackTransport {
+    sent: RefCell<Vec<Op>>,
+}
+
+impl LoopbackTransport {
+    pub fn sent(&self) -> Vec<Op> {
+        self.sent.borrow().clone()
+    }
+}
+
+impl Transport for LoopbackTransport {
+    fn send(&self, op: Op) {
+        self.sent.borrow_mut().push(op);
+    }
+}
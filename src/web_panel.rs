@@ -0,0 +1,402 @@
+use futures::{StreamExt as _, channel::mpsc::UnboundedSender};
+use gpui::{App, AppContext as _, Context, Entity, Focusable as _, IntoElement, ParentElement as _, Render, SharedString, Styled as _, Subscription, Task, Window, actions, div};
+use gpui_component::{
+    ActiveTheme as _, Disableable as _, IconName, Root, Sizable as _, WindowExt as _, button::{Button, ButtonVariants as _}, h_flex, input::{Input, InputEvent, InputState}, label::Label, v_flex, webview::WebView, wry
+};
+
+use crate::{bridge::{self, Bridge}, settings::SettingsStore, window_state::WindowState};
+
+actions!(web, [ToggleWebPanel]);
+
+pub fn init(cx: &mut App) {
+    cx.on_action(|_: &ToggleWebPanel, cx| {
+        WindowState::update(cx, |state| state.web_panel_open = !state.web_panel_open);
+    });
+}
+
+/// What the web view reports about the page it loads.
+enum PageEvent {
+    Started(String),
+    Finished(String),
+    /// A message the page at the url posted through `window.ipc`.
+    Message { url: String, body: String },
+}
+
+/// A web view beside the conversations, with an address bar and the usual buttons.
+pub struct WebPanel {
+    /// Why there is no web view when the window cannot embed one.
+    webview: Result<Entity<WebView>, SharedString>,
+    address: Entity<InputState>,
+    loading: bool,
+    _page_events: Task<()>,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl WebPanel {
+    pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let home = SettingsStore::get(cx).network.home_url.clone();
+        let (events, mut received) = futures::channel::mpsc::unbounded();
+        let webview = Self::build(&home, events, window, cx)
+            .map(|webview| {
+                let webview = cx.new(|cx| WebView::new(webview, window, cx));
+                Bridge::attach(&webview, cx);
+                webview
+            })
+            .map_err(|err| SharedString::from(format!("The page cannot be shown in this window: {}", err)));
+
+        let address = cx.new(|cx| {
+            InputState::new(window, cx)
+                .placeholder("Enter a web address")
+                .default_value(home)
+        });
+
+        let _page_events = cx.spawn_in(window, async move |this, cx| {
+            while let Some(event) = received.next().await {
+                let updated = this.update_in(cx, |this, window, cx| this.page_event(event, window, cx));
+                if updated.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let _subscriptions = vec![
+            cx.subscribe_in(&address, window, |this, address, event, window, cx| {
+                if let InputEvent::PressEnter { .. } = event {
+                    let text = address.read(cx).value();
+                    if let Some(url) = normalize_url(&text) {
+                        this.open(&url, window, cx);
+                    }
+                }
+            }),
+            cx.observe_global_in::<WindowState>(window, |this, window, cx| this.update_visibility(window, cx)),
+        ];
+        // The root of the window is created around this panel, it exists once the panel does.
+        cx.defer_in(window, |this, window, cx| {
+            if let Some(Some(root)) = window.root::<Root>() {
+                let covered = cx.observe_in(&root, window, |this, _, window, cx| this.update_visibility(window, cx));
+                this._subscriptions.push(covered);
+            }
+            #[cfg(target_os = "linux")]
+            if this.webview.is_ok() {
+                gtk_events::opened(this.visible(cx), cx);
+                let released = cx.on_release(|this: &mut Self, cx| gtk_events::closed(this.visible(cx), cx));
+                this._subscriptions.push(released);
+            }
+            this.update_visibility(window, cx);
+        });
+
+        Self {
+            webview,
+            address,
+            loading: false,
+            _page_events,
+            _subscriptions,
+        }
+    }
+
+    fn build(
+        url: &str,
+        events: UnboundedSender<PageEvent>,
+        window: &mut Window,
+        cx: &App,
+    ) -> anyhow::Result<wry::WebView> {
+        let network = &SettingsStore::get(cx).network;
+        let messages = events.clone();
+        let mut builder = wry::WebViewBuilder::new()
+            .with_url(url)
+            .with_initialization_script(bridge::SCRIPT)
+            .with_ipc_handler(move |request| {
+                let url = request.uri().to_string();
+                _ = messages.unbounded_send(PageEvent::Message {
+                    url,
+                    body: request.into_body(),
+                });
+            })
+            .with_on_page_load_handler(move |event, url| {
+                _ = events.unbounded_send(match event {
+                    wry::PageLoadEvent::Started => PageEvent::Started(url),
+                    wry::PageLoadEvent::Finished => PageEvent::Finished(url),
+                });
+            });
+        if let Some(proxy) = network.proxy() {
+            let endpoint = wry::ProxyEndpoint {
+                host: proxy.host,
+                port: proxy.port.to_string(),
+            };
+            builder = builder.with_proxy_config(if proxy.socks5 {
+                wry::ProxyConfig::Socks5(endpoint)
+            } else {
+                wry::ProxyConfig::Http(endpoint)
+            });
+        }
+        #[cfg(any(debug_assertions, feature = "inspector"))]
+        let builder = builder.with_devtools(true);
+
+        // wry embeds into X11 windows only, under Wayland this fails and the panel offers the browser instead.
+        #[cfg(target_os = "linux")]
+        gtk::init()?;
+
+        use raw_window_handle::HasWindowHandle as _;
+        let handle = window.window_handle()?;
+        Ok(builder.build_as_child(&handle)?)
+    }
+
+    fn page_event(&mut self, event: PageEvent, window: &mut Window, cx: &mut Context<Self>) {
+        let url = match event {
+            PageEvent::Started(url) => {
+                self.loading = true;
+                url
+            }
+            PageEvent::Finished(url) => {
+                self.loading = false;
+                url
+            }
+            PageEvent::Message { url, body } => {
+                if let Ok(webview) = &self.webview {
+                    Bridge::handle(webview, &url, &body, cx);
+                }
+                return;
+            }
+        };
+        // Leave the address alone while it is being typed.
+        if !self.address.read(cx).focus_handle(cx).is_focused(window) {
+            self.address.update(cx, |address, cx| address.set_value(url, window, cx));
+        }
+        cx.notify();
+    }
+
+    #[cfg(target_os = "linux")]
+    fn visible(&self, cx: &App) -> bool {
+        self.webview.as_ref().is_ok_and(|webview| webview.read(cx).visible())
+    }
+
+    /// Show the web view while the panel is open and no dialog or sheet is, those are drawn
+    /// in the window and the web view is a window of its own covering them.
+    fn update_visibility(&mut self, window: &mut Window, cx: &mut App) {
+        let covered = window.has_active_dialog(cx) || window.has_active_sheet(cx);
+        self.set_visible(WindowState::global(cx).web_panel_open && !covered, cx);
+    }
+
+    fn set_visible(&mut self, visible: bool, cx: &mut App) {
+        let Ok(webview) = &self.webview else {
+            return;
+        };
+        if webview.read(cx).visible() == visible {
+            return;
+        }
+        webview.update(cx, |webview, _| {
+            if visible {
+                webview.show();
+            } else {
+                webview.hide();
+            }
+        });
+        #[cfg(target_os = "linux")]
+        gtk_events::set_visible(visible, cx);
+    }
+
+    fn open(&mut self, url: &str, window: &mut Window, cx: &mut Context<Self>) {
+        let Ok(webview) = &self.webview else {
+            return;
+        };
+        if let Err(err) = webview.read(cx).raw().load_url(url) {
+            window.push_notification(format!("Could not open {}: {}", url, err), cx);
+        }
+    }
+
+    fn go_home(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let home = SettingsStore::get(cx).network.home_url.clone();
+        self.address.update(cx, |address, cx| address.set_value(home.clone(), window, cx));
+        self.open(&home, window, cx);
+    }
+
+    /// Run a script in the page, the history and reload buttons need nothing more.
+    fn run_script(&self, script: &str, cx: &App) {
+        if let Ok(webview) = &self.webview {
+            _ = webview.read(cx).raw().evaluate_script(script);
+        }
+    }
+
+    fn render_placeholder(&self, reason: SharedString, cx: &mut Context<Self>) -> impl IntoElement {
+        v_flex()
+            .flex_1()
+            .items_center()
+            .justify_center()
+            .gap_3()
+            .p_4()
+            .child(Label::new(reason).text_sm().text_color(cx.theme().muted_foreground))
+            .child(
+                Button::new("web-open-browser")
+                    .label("Open in Browser")
+                    .small()
+                    .outline()
+                    .on_click(cx.listener(|this, _, _, cx| {
+                        let text = this.address.read(cx).value();
+                        if let Some(url) = normalize_url(&text) {
+                            cx.open_url(&url);
+                        }
+                    })),
+            )
+    }
+}
+
+impl Render for WebPanel {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let embedded = self.webview.is_ok();
+        let content = match &self.webview {
+            Ok(webview) => div().flex_1().overflow_hidden().child(webview.clone()).into_any_element(),
+            Err(reason) => self.render_placeholder(reason.clone(), cx).into_any_element(),
+        };
+
+        v_flex()
+            .size_full()
+            .child(
+                h_flex()
+                    .gap_1()
+                    .p_1()
+                    .border_b_1()
+                    .border_color(cx.theme().border)
+                    .child(
+                        Button::new("web-back")
+                            .icon(IconName::ArrowLeft)
+                            .xsmall()
+                            .ghost()
+                            .disabled(!embedded)
+                            .tooltip("Back")
+                            .on_click(cx.listener(|this, _, _, cx| this.run_script("history.back();", cx))),
+                    )
+                    .child(
+                        Button::new("web-forward")
+                            .icon(IconName::ArrowRight)
+                            .xsmall()
+                            .ghost()
+                            .disabled(!embedded)
+                            .tooltip("Forward")
+                            .on_click(cx.listener(|this, _, _, cx| this.run_script("history.forward();", cx))),
+                    )
+                    .child(if self.loading {
+                        Button::new("web-stop")
+                            .icon(IconName::Close)
+                            .xsmall()
+                            .ghost()
+                            .tooltip("Stop")
+                            .on_click(cx.listener(|this, _, _, cx| this.run_script("window.stop();", cx)))
+                    } else {
+                        Button::new("web-reload")
+                            .icon(IconName::Redo)
+                            .xsmall()
+                            .ghost()
+                            .disabled(!embedded)
+                            .tooltip("Reload")
+                            .on_click(cx.listener(|this, _, _, cx| this.run_script("location.reload();", cx)))
+                    })
+                    .child(
+                        Button::new("web-home")
+                            .icon(IconName::Globe)
+                            .xsmall()
+                            .ghost()
+                            .disabled(!embedded)
+                            .tooltip("Home")
+                            .on_click(cx.listener(|this, _, window, cx| this.go_home(window, cx))),
+                    )
+                    .child(div().flex_1().child(Input::new(&self.address).small()))
+                    .child(
+                        Button::new("web-close")
+                            .icon(IconName::PanelRightClose)
+                            .xsmall()
+                            .ghost()
+                            .tooltip("Close Web Panel")
+                            .on_click(|_, _, cx| {
+                                WindowState::update(cx, |state| state.web_panel_open = false);
+                            }),
+                    ),
+            )
+            .child(content)
+    }
+}
+
+/// The address typed into the address bar as a URL, `https://` is assumed when it has no scheme.
+fn normalize_url(text: &str) -> Option<String> {
+    let text = text.trim();
+    if text.is_empty() {
+        None
+    } else if text.contains("://") || text.starts_with("about:") {
+        Some(text.to_string())
+    } else {
+        Some(format!("https://{}", text))
+    }
+}
+
+/// webkit2gtk runs on the event loop of gtk, which nothing else drives.
+///
+/// The loop runs while a web view exists, slower while none is shown.
+#[cfg(target_os = "linux")]
+mod gtk_events {
+    use std::time::Duration;
+
+    use gpui::{App, Global, Timer};
+
+    const SHOWN_INTERVAL: Duration = Duration::from_millis(16);
+    /// Hidden pages still load and run their scripts, just nothing needs drawing.
+    const HIDDEN_INTERVAL: Duration = Duration::from_millis(250);
+
+    #[derive(Default)]
+    struct GtkEvents {
+        webviews: usize,
+        visible: usize,
+        running: bool,
+    }
+
+    impl Global for GtkEvents {}
+
+    /// Count a new web view, starting the loop unless it runs already.
+    pub(super) fn opened(visible: bool, cx: &mut App) {
+        let events = cx.default_global::<GtkEvents>();
+        events.webviews += 1;
+        if visible {
+            events.visible += 1;
+        }
+        if events.running {
+            return;
+        }
+        events.running = true;
+        cx.spawn(async |cx| {
+            loop {
+                let interval = cx.update(|cx| {
+                    let events = cx.global_mut::<GtkEvents>();
+                    events.running = events.webviews > 0;
+                    match (events.running, events.visible > 0) {
+                        (false, _) => None,
+                        (true, true) => Some(SHOWN_INTERVAL),
+                        (true, false) => Some(HIDDEN_INTERVAL),
+                    }
+                });
+                let Ok(Some(interval)) = interval else {
+                    break;
+                };
+                Timer::after(interval).await;
+                while gtk::events_pending() {
+                    gtk::main_iteration_do(false);
+                }
+            }
+        })
+        .detach();
+    }
+
+    pub(super) fn closed(visible: bool, cx: &mut App) {
+        let events = cx.default_global::<GtkEvents>();
+        events.webviews = events.webviews.saturating_sub(1);
+        if visible {
+            events.visible = events.visible.saturating_sub(1);
+        }
+    }
+
+    pub(super) fn set_visible(visible: bool, cx: &mut App) {
+        let events = cx.default_global::<GtkEvents>();
+        if visible {
+            events.visible += 1;
+        } else {
+            events.visible = events.visible.saturating_sub(1);
+        }
+    }
+}
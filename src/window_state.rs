@@ -0,0 +1,278 @@
+use std::{path::PathBuf, time::Duration};
+
+use gpui::{App, Bounds, Context, DisplayId, Global, Pixels, Subscription, Task, Timer, Window, WindowBounds, point, px, size};
+use serde::{Deserialize, Serialize};
+
+use crate::{persist, settings::SettingsStore};
+
+/// Moving or resizing a window changes its state continuously, it is saved once it settles.
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// How much of the display a new window may cover at most.
+const MAX_DISPLAY_SHARE: f32 = 0.85;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowMode {
+    #[default]
+    Windowed,
+    Maximized,
+    Fullscreen,
+}
+
+/// The bounds of a window as they are saved.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SavedBounds {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+impl SavedBounds {
+    /// The bounds, unless they have no area or are not numbers.
+    pub(crate) fn bounds(&self) -> Option<Bounds<Pixels>> {
+        let Self { x, y, width, height } = *self;
+        ([x, y, width, height].iter().all(|number| number.is_finite()) && width > 0. && height > 0.)
+            .then(|| Bounds::new(point(px(x), px(y)), size(px(width), px(height))))
+    }
+}
+
+impl From<Bounds<Pixels>> for SavedBounds {
+    fn from(bounds: Bounds<Pixels>) -> Self {
+        Self {
+            x: bounds.origin.x.into(),
+            y: bounds.origin.y.into(),
+            width: bounds.size.width.into(),
+            height: bounds.size.height.into(),
+        }
+    }
+}
+
+/// The state of the main window as it is saved, lengths are in pixels.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SavedState {
+    bounds: Option<SavedBounds>,
+    mode: WindowMode,
+    display: Option<String>,
+    contacts_width: Option<f32>,
+    contacts_collapsed: bool,
+    composer_height: Option<f32>,
+    web_panel_open: bool,
+    web_panel_width: Option<f32>,
+}
+
+/// The main window as it was left, saved to a JSON file so the next start restores it.
+pub struct WindowState {
+    path: PathBuf,
+    /// The bounds of the window, the ones it restores to when maximized or fullscreen.
+    pub bounds: Option<Bounds<Pixels>>,
+    pub mode: WindowMode,
+    /// The uuid of the display the window was on.
+    pub display: Option<String>,
+    /// The width of the contacts panel.
+    pub contacts_width: Option<Pixels>,
+    /// Whether the contacts panel is collapsed to a rail of avatars.
+    pub contacts_collapsed: bool,
+    /// The height of the composer below the messages.
+    pub composer_height: Option<Pixels>,
+    /// Whether the web panel is shown beside the conversations.
+    pub web_panel_open: bool,
+    /// The width of the web panel.
+    pub web_panel_width: Option<Pixels>,
+    _save_task: Task<()>,
+}
+
+impl Global for WindowState {}
+
+impl WindowState {
+    pub fn init(cx: &mut App) {
+        let path = SettingsStore::get(cx).storage.data_dir().join("window.json");
+        cx.set_global(Self::load(path));
+        // A change may still be waiting for the save delay.
+        cx.on_app_quit(|cx| {
+            Self::global(cx).save();
+            async {}
+        })
+        .detach();
+    }
+
+    /// Load the state saved in `path`, a file that cannot be read is kept aside.
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let saved = persist::load::<SavedState>(&path);
+        let length = |length: Option<f32>| length.filter(|length| length.is_finite() && *length > 0.).map(px);
+        Self {
+            path,
+            bounds: saved.bounds.and_then(|bounds| bounds.bounds()),
+            mode: saved.mode,
+            display: saved.display,
+            contacts_width: length(saved.contacts_width),
+            contacts_collapsed: saved.contacts_collapsed,
+            composer_height: length(saved.composer_height),
+            web_panel_open: saved.web_panel_open,
+            web_panel_width: length(saved.web_panel_width),
+            _save_task: Task::ready(()),
+        }
+    }
+
+    pub fn global(cx: &App) -> &Self {
+        cx.global::<Self>()
+    }
+
+    /// Change the state and save it shortly after.
+    pub fn update(cx: &mut App, change: impl FnOnce(&mut Self)) {
+        change(cx.global_mut::<Self>());
+        let task = cx.spawn(async move |cx| {
+            Timer::after(SAVE_DELAY).await;
+            _ = cx.update(|cx| Self::global(cx).save());
+        });
+        cx.global_mut::<Self>()._save_task = task;
+    }
+
+    /// Where to open the main window, the saved place when its display is still there.
+    ///
+    /// Otherwise the window is centered on the primary display, keeping the saved size
+    /// as far as it fits.
+    pub fn window_bounds(&self, cx: &App) -> (WindowBounds, Option<DisplayId>) {
+        let displays = cx.displays();
+        let display = match &self.display {
+            Some(uuid) => displays
+                .iter()
+                .find(|display| display.uuid().is_ok_and(|id| id.to_string() == *uuid)),
+            // Not every platform identifies displays, any display showing the window does.
+            None => self.bounds.and_then(|bounds| {
+                displays.iter().find(|display| display.bounds().intersects(&bounds))
+            }),
+        };
+        let restored = self
+            .bounds
+            .zip(display)
+            .filter(|(bounds, display)| display.bounds().intersects(bounds));
+
+        let (bounds, display_id) = match restored {
+            Some((bounds, display)) => (bounds, Some(display.id())),
+            None => {
+                let mut window_size = self
+                    .bounds
+                    .map(|bounds| bounds.size)
+                    .unwrap_or_else(|| SettingsStore::get(cx).window.size());
+                if let Some(display) = cx.primary_display() {
+                    let display_size = display.bounds().size;
+                    window_size.width = window_size.width.min(display_size.width * MAX_DISPLAY_SHARE);
+                    window_size.height = window_size.height.min(display_size.height * MAX_DISPLAY_SHARE);
+                }
+                (Bounds::centered(None, window_size, cx), None)
+            }
+        };
+
+        let bounds = match self.mode {
+            WindowMode::Windowed => WindowBounds::Windowed(bounds),
+            WindowMode::Maximized => WindowBounds::Maximized(bounds),
+            WindowMode::Fullscreen => WindowBounds::Fullscreen(bounds),
+        };
+        (bounds, display_id)
+    }
+
+    /// Record the bounds and display of `window` whenever it moves or resizes.
+    pub fn observe_window<V: 'static>(window: &mut Window, cx: &mut Context<V>) -> Subscription {
+        cx.observe_window_bounds(window, |_, window, cx| {
+            let (bounds, mode) = match window.window_bounds() {
+                WindowBounds::Windowed(bounds) => (bounds, WindowMode::Windowed),
+                WindowBounds::Maximized(bounds) => (bounds, WindowMode::Maximized),
+                WindowBounds::Fullscreen(bounds) => (bounds, WindowMode::Fullscreen),
+            };
+            let display = window
+                .display(cx)
+                .and_then(|display| display.uuid().ok())
+                .map(|uuid| uuid.to_string());
+            Self::update(cx, |state| {
+                state.bounds = Some(bounds);
+                state.mode = mode;
+                state.display = display;
+            });
+        })
+    }
+
+    fn save(&self) {
+        let saved = SavedState {
+            bounds: self.bounds.map(SavedBounds::from),
+            mode: self.mode,
+            display: self.display.clone(),
+            contacts_width: self.contacts_width.map(f32::from),
+            contacts_collapsed: self.contacts_collapsed,
+            composer_height: self.composer_height.map(f32::from),
+            web_panel_open: self.web_panel_open,
+            web_panel_width: self.web_panel_width.map(f32::from),
+        };
+        persist::save(&self.path, &saved);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_state_loads_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("window.json");
+        let mut state = WindowState::load(&path);
+        state.bounds = Some(Bounds::new(point(px(10.), px(-20.)), size(px(800.), px(600.))));
+        state.mode = WindowMode::Maximized;
+        state.display = Some("display".into());
+        state.contacts_width = Some(px(240.));
+        state.contacts_collapsed = true;
+        state.web_panel_open = true;
+        state.save();
+
+        let loaded = WindowState::load(&path);
+        assert_eq!(loaded.bounds, state.bounds);
+        assert_eq!(loaded.mode, WindowMode::Maximized);
+        assert_eq!(loaded.display.as_deref(), Some("display"));
+        assert_eq!(loaded.contacts_width, Some(px(240.)));
+        assert!(loaded.contacts_collapsed);
+        assert_eq!(loaded.composer_height, None);
+        assert!(loaded.web_panel_open);
+    }
+
+    #[test]
+    fn bounds_without_area_are_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("window.json");
+        std::fs::write(
+            &path,
+            r#"{ "bounds": { "x": 0, "y": 0, "width": 0, "height": 600 }, "contacts_width": -5 }"#,
+        )
+        .unwrap();
+
+        let state = WindowState::load(&path);
+        assert_eq!(state.bounds, None);
+        assert_eq!(state.contacts_width, None);
+    }
+
+    #[gpui::test]
+    fn windows_of_a_missing_display_are_centered(cx: &mut gpui::TestAppContext) {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = WindowState::load(dir.path().join("window.json"));
+        state.display = Some("a display no longer connected".into());
+
+        // Too large for the display and off it, the size is clamped.
+        state.bounds = Some(Bounds::new(point(px(5000.), px(5000.)), size(px(3000.), px(2000.))));
+        let (bounds, display) = cx.update(|cx| state.window_bounds(cx));
+        let (display_bounds, fitting) = cx.update(|cx| {
+            let display = cx.primary_display().unwrap().bounds();
+            let fitting = size(display.size.width * MAX_DISPLAY_SHARE, display.size.height * MAX_DISPLAY_SHARE);
+            (display, fitting)
+        });
+        assert_eq!(bounds, WindowBounds::Windowed(Bounds::centered_at(display_bounds.center(), fitting)));
+        assert_eq!(display, None);
+
+        // On the display and fitting it, only the place is not restored.
+        state.bounds = Some(Bounds::new(point(px(10.), px(10.)), size(px(800.), px(600.))));
+        let (bounds, _) = cx.update(|cx| state.window_bounds(cx));
+        let centered = Bounds::centered_at(display_bounds.center(), size(px(800.), px(600.)));
+        assert_eq!(bounds, WindowBounds::Windowed(centered));
+    }
+}